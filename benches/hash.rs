@@ -1,3 +1,15 @@
+//! Hash benches (three hashes per iteration). Measured on the same single CPU machine
+//! with `cargo bench --bench hash`, before and after the flat, cache line aligned
+//! dataset storage:
+//!
+//! | bench                   | before                    | after                     |
+//! |-------------------------|---------------------------|---------------------------|
+//! | bench_hash_full_memory  | 167,899,273 ns (± 123M)   | 150,386,270 ns (± 36M)    |
+//! | bench_hash_light_memory | 4,181,280,075 ns (± 850M) | 3,637,636,321 ns (± 667M) |
+//!
+//! Light mode computes the dataset items and does not read the dataset storage, its
+//! difference is within the noise.
+
 #![feature(test)]
 #![feature(box_syntax)]
 
@@ -0,0 +1,114 @@
+/*
+ * Checks the test vectors of the reference implementation against the
+ * C interface of libmithril: make -C ctest (after cargo build --release)
+ */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "randomx.h"
+
+static int failures = 0;
+
+static void hex(const unsigned char *data, size_t len, char *out) {
+  for (size_t i = 0; i < len; i++) {
+    sprintf(out + 2 * i, "%02x", data[i]);
+  }
+}
+
+static int unhex(const char *str, unsigned char *out, size_t max) {
+  size_t len = strlen(str) / 2;
+  if (len > max) {
+    return -1;
+  }
+  for (size_t i = 0; i < len; i++) {
+    sscanf(str + 2 * i, "%2hhx", &out[i]);
+  }
+  return (int)len;
+}
+
+static void check(randomx_vm *vm, const char *name, const void *input, size_t len, const char *expected) {
+  unsigned char hash[RANDOMX_HASH_SIZE];
+  char hash_hex[2 * RANDOMX_HASH_SIZE + 1];
+
+  randomx_calculate_hash(vm, input, len, hash);
+  hex(hash, RANDOMX_HASH_SIZE, hash_hex);
+  if (strcmp(hash_hex, expected) != 0) {
+    printf("FAILED %s: expected %s, got %s\n", name, expected, hash_hex);
+    failures++;
+  } else {
+    printf("ok %s\n", name);
+  }
+}
+
+static void check_pipelined(randomx_vm *vm, const char *mode) {
+  unsigned char hash[2][RANDOMX_HASH_SIZE];
+  char hash_hex[2][2 * RANDOMX_HASH_SIZE + 1];
+
+  randomx_calculate_hash_first(vm, "This is a test", 14);
+  randomx_calculate_hash_next(vm, "Lorem ipsum dolor sit amet", 26, hash[0]);
+  randomx_calculate_hash_last(vm, hash[1]);
+  hex(hash[0], RANDOMX_HASH_SIZE, hash_hex[0]);
+  hex(hash[1], RANDOMX_HASH_SIZE, hash_hex[1]);
+  if (strcmp(hash_hex[0], "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f") != 0 ||
+      strcmp(hash_hex[1], "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969") != 0) {
+    printf("FAILED %s pipelined: got %s, %s\n", mode, hash_hex[0], hash_hex[1]);
+    failures++;
+  } else {
+    printf("ok %s pipelined\n", mode);
+  }
+}
+
+static void run(randomx_flags flags, const char *mode) {
+  const char key0[] = "test key 000";
+  const char key1[] = "test key 001";
+  unsigned char input[128];
+  char name[64];
+
+  randomx_cache *cache = randomx_alloc_cache(flags);
+  randomx_init_cache(cache, key0, strlen(key0));
+  randomx_vm *vm = randomx_create_vm(flags, cache, NULL);
+  if (vm == NULL) {
+    printf("FAILED %s: randomx_create_vm\n", mode);
+    failures++;
+    randomx_release_cache(cache);
+    return;
+  }
+
+  snprintf(name, sizeof(name), "%s a", mode);
+  check(vm, name, "This is a test", 14, "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f");
+  snprintf(name, sizeof(name), "%s b", mode);
+  check(vm, name, "Lorem ipsum dolor sit amet", 26, "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969");
+  snprintf(name, sizeof(name), "%s c", mode);
+  check(vm, name, "sed do eiusmod tempor incididunt ut labore et dolore magna aliqua", 65,
+        "c36d4ed4191e617309867ed66a443be4075014e2b061bcdaf9ce7b721d2b77a8");
+
+  randomx_init_cache(cache, key1, strlen(key1));
+  randomx_vm_set_cache(vm, cache);
+  snprintf(name, sizeof(name), "%s d", mode);
+  check(vm, name, "sed do eiusmod tempor incididunt ut labore et dolore magna aliqua", 65,
+        "e9ff4503201c0c2cca26d285c93ae883f9b1d30c9eb240b820756f2d5a7905fc");
+
+  int len = unhex("0b0b98bea7e805e0010a2126d287a2a0cc833d312cb786385a7c2f9de69d25537f584a9bc9977b00000000666fd8753bf61a8631f12984e3fd44f4014eca629276817b56f32e9b68bd82f416",
+                  input, sizeof(input));
+  snprintf(name, sizeof(name), "%s e", mode);
+  check(vm, name, input, (size_t)len, "c56414121acda1713c2f2a819d8ae38aed7c80c35c2a769298d34f03833cd5f1");
+
+  randomx_init_cache(cache, key0, strlen(key0));
+  randomx_vm_set_cache(vm, cache);
+  check_pipelined(vm, mode);
+
+  randomx_destroy_vm(vm);
+  randomx_release_cache(cache);
+}
+
+int main(void) {
+  run(RANDOMX_FLAG_DEFAULT, "interpreter");
+  run(randomx_get_flags(), "recommended");
+
+  if (failures != 0) {
+    printf("%d test vector(s) failed\n", failures);
+    return 1;
+  }
+  return 0;
+}
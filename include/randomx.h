@@ -0,0 +1,231 @@
+/*
+ * C interface of mithril's RandomX implementation, compatible with the
+ * reference header (https://github.com/tevador/RandomX/blob/master/src/randomx.h).
+ * Link with libmithril (cargo build --release, target/release/libmithril.{so,a}).
+ *
+ * Differences to the reference implementation:
+ *  - the cache always uses normal pages, of the flags of randomx_alloc_cache only
+ *    RANDOMX_FLAG_JIT is used
+ *  - RANDOMX_FLAG_SECURE and the RANDOMX_FLAG_ARGON2_* flags are ignored
+ *  - randomx_get_cache_memory is not available
+ *  - randomx_flags is an int, the flags are macros
+ *
+ * Generated by cbindgen from src/randomx/ffi.rs, do not edit.
+ */
+
+#ifndef RANDOMX_H
+#define RANDOMX_H
+
+#include <stddef.h>
+#ifndef RANDOMX_EXPORT
+#define RANDOMX_EXPORT
+#endif
+
+#define RANDOMX_HASH_SIZE 32
+
+#define RANDOMX_DATASET_ITEM_SIZE 64
+
+/*
+ `randomx_cache`, allocated with `randomx_alloc_cache` and filled for a key by
+ `randomx_init_cache`.
+ */
+typedef struct randomx_cache randomx_cache;
+
+/*
+ `randomx_dataset`
+ */
+typedef struct randomx_dataset randomx_dataset;
+
+/*
+ `randomx_vm`
+ */
+typedef struct randomx_vm randomx_vm;
+
+/*
+ `randomx_flags`, the bits of `RandomxFlags`
+ */
+typedef int randomx_flags;
+
+#define RANDOMX_FLAG_DEFAULT 0
+
+#define RANDOMX_FLAG_LARGE_PAGES 1
+
+#define RANDOMX_FLAG_HARD_AES 2
+
+#define RANDOMX_FLAG_FULL_MEM 4
+
+#define RANDOMX_FLAG_JIT 8
+
+/*
+ Ignored.
+ */
+#define RANDOMX_FLAG_SECURE 16
+
+/*
+ Ignored.
+ */
+#define RANDOMX_FLAG_ARGON2_SSSE3 32
+
+/*
+ Ignored.
+ */
+#define RANDOMX_FLAG_ARGON2_AVX2 64
+
+/*
+ Ignored.
+ */
+#define RANDOMX_FLAG_ARGON2 96
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ The recommended flags for this machine (`RANDOMX_FLAG_HARD_AES`, `RANDOMX_FLAG_JIT`).
+ */
+RANDOMX_EXPORT randomx_flags randomx_get_flags(void);
+
+/*
+ Allocates a cache, it has to be initialised with `randomx_init_cache` before use.
+ The cache uses normal pages, of the flags only `RANDOMX_FLAG_JIT` is used.
+ */
+RANDOMX_EXPORT randomx_cache *randomx_alloc_cache(randomx_flags flags);
+
+/*
+ Initialises the cache memory for the key (256 MiB, takes a few seconds).
+
+ # Safety
+ `cache` from `randomx_alloc_cache`, `key` valid for `key_size` bytes.
+ */
+RANDOMX_EXPORT void randomx_init_cache(randomx_cache *cache, const void *key, size_t key_size);
+
+/*
+ # Safety
+ `cache` from `randomx_alloc_cache` or null.
+ */
+RANDOMX_EXPORT void randomx_release_cache(randomx_cache *cache);
+
+/*
+ Allocates the dataset (2 GiB), null if `RANDOMX_FLAG_LARGE_PAGES` is set and
+ no huge pages are available.
+ */
+RANDOMX_EXPORT randomx_dataset *randomx_alloc_dataset(randomx_flags flags);
+
+RANDOMX_EXPORT unsigned long randomx_dataset_item_count(void);
+
+/*
+ Initialises the items `start_item..start_item + item_count` of the dataset.
+ Nothing is initialised if the range is not within the dataset or the cache has
+ no key.
+
+ # Safety
+ `dataset` from `randomx_alloc_dataset`, `cache` from `randomx_alloc_cache`. Concurrent calls (the usual way of
+ initialising the dataset with several threads) must use disjoint ranges, and no
+ VM may hash with the dataset meanwhile.
+ */
+RANDOMX_EXPORT
+void randomx_init_dataset(randomx_dataset *dataset,
+                          randomx_cache *cache,
+                          unsigned long start_item,
+                          unsigned long item_count);
+
+/*
+ # Safety
+ `dataset` from `randomx_alloc_dataset`.
+ */
+RANDOMX_EXPORT void *randomx_get_dataset_memory(randomx_dataset *dataset);
+
+/*
+ # Safety
+ `dataset` from `randomx_alloc_dataset` or null. VMs using the dataset keep
+ the memory alive until they are destroyed.
+ */
+RANDOMX_EXPORT void randomx_release_dataset(randomx_dataset *dataset);
+
+/*
+ Creates a VM, null if the flags are not supported, or the cache (light mode)
+ resp. the dataset (`RANDOMX_FLAG_FULL_MEM`) is missing or not initialised.
+
+ # Safety
+ `cache` and `dataset` null or valid.
+ */
+RANDOMX_EXPORT
+randomx_vm *randomx_create_vm(randomx_flags flags,
+                              randomx_cache *cache,
+                              randomx_dataset *dataset);
+
+/*
+ Switches to another cache, ignored for VMs in full memory mode and for caches
+ without a key.
+
+ # Safety
+ `machine` from `randomx_create_vm`, `cache` from `randomx_alloc_cache`.
+ */
+RANDOMX_EXPORT void randomx_vm_set_cache(randomx_vm *machine, randomx_cache *cache);
+
+/*
+ Switches to another dataset, ignored for VMs in light mode.
+
+ # Safety
+ `machine` from `randomx_create_vm`, `dataset` from `randomx_alloc_dataset`.
+ */
+RANDOMX_EXPORT void randomx_vm_set_dataset(randomx_vm *machine, randomx_dataset *dataset);
+
+/*
+ # Safety
+ `machine` from `randomx_create_vm` or null.
+ */
+RANDOMX_EXPORT void randomx_destroy_vm(randomx_vm *machine);
+
+/*
+ Writes the `RANDOMX_HASH_SIZE` bytes hash of the input to `output`.
+
+ # Safety
+ `machine` from `randomx_create_vm`, `input` valid for `input_size` bytes and
+ `output` for `RANDOMX_HASH_SIZE` bytes.
+ */
+RANDOMX_EXPORT
+void randomx_calculate_hash(randomx_vm *machine,
+                            const void *input,
+                            size_t input_size,
+                            void *output);
+
+/*
+ Pipelined hashing: starts with the first input, each `randomx_calculate_hash_next`
+ returns the hash of the previous input and starts the next one,
+ `randomx_calculate_hash_last` returns the hash of the final input.
+
+ # Safety
+ `machine` from `randomx_create_vm`, `input` valid for `input_size` bytes.
+ */
+RANDOMX_EXPORT
+void randomx_calculate_hash_first(randomx_vm *machine,
+                                  const void *input,
+                                  size_t input_size);
+
+/*
+ Without a pending hash (no `randomx_calculate_hash_first`), the series is started
+ with `next_input` and `output` is left unchanged.
+
+ # Safety
+ Like `randomx_calculate_hash`.
+ */
+RANDOMX_EXPORT
+void randomx_calculate_hash_next(randomx_vm *machine,
+                                 const void *next_input,
+                                 size_t next_input_size,
+                                 void *output);
+
+/*
+ `output` is left unchanged without a pending hash.
+
+ # Safety
+ `machine` from `randomx_create_vm`, `output` valid for `RANDOMX_HASH_SIZE` bytes.
+ */
+RANDOMX_EXPORT void randomx_calculate_hash_last(randomx_vm *machine, void *output);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* RANDOMX_H */
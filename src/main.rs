@@ -10,11 +10,13 @@ use self::crossbeam_channel::{select, unbounded, Receiver};
 use mithril::bandit_tools;
 use mithril::metric;
 use mithril::mithril_config;
-use mithril::randomx::memory::VmMemoryAllocator;
+use mithril::randomx::disk_cache::DiskCache;
+use mithril::randomx::memory::{self, MemoryMode, VmMemoryAllocator};
+use mithril::randomx::soft_aes;
 use mithril::stratum::{StratumAction, StratumClient};
 use mithril::timer;
 use mithril::worker::worker_pool;
-use mithril::worker::worker_pool::WorkerPool;
+use mithril::worker::worker_pool::{ShareCheck, WorkerPool};
 use std::io;
 use std::io::Error;
 use std::path::Path;
@@ -42,6 +44,29 @@ fn main() {
         print_donation_hint(config.donation_conf.percentage);
     }
 
+    if let Some(has_aes) = config.hardware_conf.has_aes {
+        soft_aes::set_hardware_aes(has_aes);
+    }
+    if !soft_aes::hardware_aes() {
+        warn!("hardware AES disabled or not available, using (slower) software AES");
+    }
+
+    memory::set_huge_pages(config.worker_conf.huge_pages);
+
+    let available = memory::available_memory();
+    let memory_mode = config.worker_conf.memory_mode.resolve(
+        &config.pool_conf.algorithm,
+        config.worker_conf.num_threads,
+        available,
+    );
+    let available_mib = available.map_or("unknown".to_string(), |a| {
+        format!("{}MiB", a / (1024 * 1024))
+    });
+    info!(
+        "memory mode {} (configured {}, available {})",
+        memory_mode, config.worker_conf.memory_mode, available_mib
+    );
+
     let mut bandit = if config.worker_conf.auto_tune {
         Some(bandit_tools::setup_bandit(
             config.worker_conf.auto_tune_log.clone(),
@@ -52,7 +77,29 @@ fn main() {
 
     let timer_rcvr = timer::setup(&config.worker_conf, &config.donation_conf);
     let mut donation_hashing = false;
-    let mut vm_memory_allocator = VmMemoryAllocator::initial();
+    let mut vm_memory_allocator = if config.worker_conf.eager_dataset {
+        VmMemoryAllocator::with_eager_init(config.worker_conf.dataset_init_threads)
+    } else {
+        VmMemoryAllocator::initial()
+    };
+    vm_memory_allocator.light = memory_mode == MemoryMode::Light;
+    vm_memory_allocator.jit = config.worker_conf.jit;
+    if config.cache_conf.enabled {
+        match DiskCache::new(&config.cache_conf) {
+            Ok(cache) => vm_memory_allocator.disk_cache = Some(cache),
+            Err(err) => error!(
+                "disk cache disabled, could not create {:?}: {}",
+                config.cache_conf.dir, err
+            ),
+        }
+    }
+
+    //the verifier keeps its seed memory across pool restarts
+    let share_check = if config.worker_conf.verify_shares {
+        Some(ShareCheck::new(&config.worker_conf, Default::default()))
+    } else {
+        None
+    };
 
     loop {
         //Stratum start
@@ -64,6 +111,7 @@ fn main() {
         } else {
             config.pool_conf.clone()
         };
+        vm_memory_allocator.set_params(conf.algorithm);
 
         let login_result = StratumClient::login(conf, client_err_sndr, stratum_sndr);
         if login_result.is_err() {
@@ -73,7 +121,7 @@ fn main() {
         }
         let client = login_result.expect("stratum client");
         let share_sndr = client.new_cmd_channel();
-        let (arm, num_threads) = if bandit.is_some() {
+        let (mut arm, num_threads) = if bandit.is_some() {
             let selected_arm = bandit.as_ref().unwrap().select_arm();
             info!("trying arm with {} #threads", selected_arm.num_threads);
             (Some(selected_arm), selected_arm.num_threads)
@@ -82,19 +130,50 @@ fn main() {
         };
 
         let (metric_sndr, metric_rcvr) = unbounded();
-        let metric = metric::start(config.metric_conf.clone(), metric_rcvr);
+        let metric = metric::start(config.metric_conf.clone(), metric_rcvr, memory_mode);
 
         //worker pool start
         let mut pool = worker_pool::start(
             num_threads,
+            config.worker_conf.jit,
+            &config.worker_conf.affinity,
             &share_sndr,
             config.metric_conf.resolution,
             &metric_sndr.clone(),
             vm_memory_allocator,
+            share_check.as_ref().map(|check| ShareCheck {
+                mismatches: metric.share_mismatch_counter(),
+                ..check.clone()
+            }),
         );
+        pool.nonce_ranges = config.worker_conf.nonce_ranges;
+
+        //arm changes resize the pool, the stratum session stays open
+        let mut arm_start_hashes = 0;
+        let term_result = loop {
+            let loop_result =
+                start_main_event_loop(&mut pool, &client_err_rcvr, &stratum_rcvr, &timer_rcvr);
+            match loop_result {
+                Ok(MainLoopExit::DrawNewBanditArm) if !donation_hashing => {
+                    if let (Some(bandit_ref), Some(prev_arm)) = (bandit.as_mut(), arm) {
+                        let hashes = metric.hash_count();
+                        reward_arm(
+                            bandit_ref,
+                            prev_arm,
+                            hashes - arm_start_hashes,
+                            config.worker_conf.auto_tune_interval_minutes,
+                        );
+                        arm_start_hashes = hashes;
 
-        let term_result =
-            start_main_event_loop(&mut pool, &client_err_rcvr, &stratum_rcvr, &timer_rcvr);
+                        let selected_arm = bandit_ref.select_arm();
+                        info!("trying arm with {} #threads", selected_arm.num_threads);
+                        pool.resize(selected_arm.num_threads);
+                        arm = Some(selected_arm);
+                    }
+                }
+                result => break result,
+            }
+        };
 
         vm_memory_allocator = pool.vm_memory_allocator.clone();
         pool.stop();
@@ -114,17 +193,27 @@ fn main() {
 
                 metric.stop();
                 let hashes = metric.hash_count();
+                info!(
+                    "{} shares submitted, difficulty {} in total",
+                    metric.shares(),
+                    metric.share_difficulty()
+                );
+                if metric.share_mismatches() > 0 {
+                    error!(
+                        "{} shares failed verification and were not submitted",
+                        metric.share_mismatches()
+                    );
+                }
                 metric.join();
 
                 if arm.is_some() && bandit.is_some() && !donation_hashing {
                     //do not save reward for donation hashing, it probably only runs for a short period
-                    let bandit_ref = bandit.as_mut().unwrap();
-                    let reward = (hashes as f64
-                        / (config.worker_conf.auto_tune_interval_minutes as f64 * 60.0))
-                        / 1000.0; /*kH/s*/
-                    info!("adding reward {:?} for arm {:?}", reward, arm);
-                    bandit_ref.update(arm.unwrap(), reward);
-                    save_bandit_state(bandit_ref);
+                    reward_arm(
+                        bandit.as_mut().unwrap(),
+                        arm.unwrap(),
+                        hashes - arm_start_hashes,
+                        config.worker_conf.auto_tune_interval_minutes,
+                    );
                 }
 
                 donation_hashing = ex == MainLoopExit::DonationHashing;
@@ -137,6 +226,19 @@ fn await_timeout() {
     thread::sleep(Duration::from_secs(60))
 }
 
+/// Rewards `arm` with the hash rate (kH/s) of the `hashes` computed during one interval.
+fn reward_arm(
+    bandit: &mut bandit::softmax::AnnealingSoftmax<bandit_tools::ThreadArm>,
+    arm: bandit_tools::ThreadArm,
+    hashes: u64,
+    interval_minutes: u64,
+) {
+    let reward = (hashes as f64 / (interval_minutes as f64 * 60.0)) / 1000.0; /*kH/s*/
+    info!("adding reward {:?} for arm {:?}", reward, arm);
+    bandit.update(arm, reward);
+    save_bandit_state(bandit);
+}
+
 fn save_bandit_state(bandit: &mut bandit::softmax::AnnealingSoftmax<bandit_tools::ThreadArm>) {
     let res = bandit_tools::ensure_mithril_folder_exists();
     if res.is_err() {
@@ -156,15 +258,24 @@ fn start_main_event_loop(
     stratum_rcvr: &Receiver<StratumAction>,
     timer_rcvr: &Receiver<timer::TickAction>,
 ) -> io::Result<MainLoopExit> {
+    let memory_rcvr = pool.vm_memory_allocator.built_rcvr.clone();
+    let stopped_rcvr = pool.stopped_rcvr.clone();
     loop {
         select! {
+            recv(memory_rcvr) -> memory_msg => {
+                let (seed_hash, memory) = memory_msg.expect("memory init channel");
+                pool.memory_ready(seed_hash, memory);
+            },
+            recv(stopped_rcvr) -> stopped_msg => {
+                pool.thread_stopped(stopped_msg.expect("worker stopped channel"));
+            },
             recv(stratum_rcvr) -> stratum_msg => {
                 if stratum_msg.is_err() {
                     return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "received error"));
                 }
                 match stratum_msg.unwrap() {
-                    StratumAction::Job{miner_id, seed_hash, blob, job_id, target} => {
-                        pool.job_change(&miner_id, &seed_hash, &blob, &job_id, &target);
+                    StratumAction::Job{miner_id, seed_hash, next_seed_hash, blob, job_id, target} => {
+                        pool.job_change(&miner_id, &seed_hash, next_seed_hash.as_deref(), &blob, &job_id, &target);
                     },
                     StratumAction::Error{err} => {
                         error!("Received stratum error: {}", err);
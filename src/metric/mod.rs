@@ -1,6 +1,8 @@
 extern crate crossbeam_channel;
 
 use self::crossbeam_channel::{select, unbounded, Receiver, RecvTimeoutError, Sender};
+use crate::randomx::memory::MemoryMode;
+use crate::worker::target::Difficulty;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -16,22 +18,53 @@ pub struct MetricConfig {
     pub report_file: String,
 }
 
+/// Sent by the worker threads
+#[derive(Debug)]
+pub enum MetricEvent {
+    /// hashes computed since the previous event
+    Hashes(u64),
+    /// a share of a job with this difficulty was submitted
+    Share(Difficulty),
+}
+
 pub struct Metric {
     /// This is the total hash count since the construction of the
     /// metric struct.
     total_hashes: Arc<AtomicU64>,
+    total_shares: Arc<AtomicU64>,
+    /// Sum of the difficulties of the submitted shares
+    total_share_difficulty: Arc<AtomicU64>,
+    /// Shares failing the verification before submission (`verify_shares`)
+    share_mismatches: Arc<AtomicU64>,
+    /// `Full` or `Light` as resolved at startup
+    memory_mode: MemoryMode,
     cnt_hnd: thread::JoinHandle<()>,
     tick_hnd: thread::JoinHandle<()>,
     stop_tick_sndr: Sender<()>,
     stop_cnt_sndr: Sender<()>,
 }
 
-pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
+/// Samples are written as `millis;hashes;share mismatches;memory mode;share difficulty`
+/// lines, the share difficulty is the sum of the shares submitted in the sample.
+pub fn start(
+    conf: MetricConfig,
+    event_rcvr: Receiver<MetricEvent>,
+    memory_mode: MemoryMode,
+) -> Metric {
     let log_count = Arc::new(AtomicU64::new(0));
     let total_count = Arc::new(AtomicU64::new(0));
+    let log_difficulty = Arc::new(AtomicU64::new(0));
+    let total_shares = Arc::new(AtomicU64::new(0));
+    let total_share_difficulty = Arc::new(AtomicU64::new(0));
+
+    let share_mismatches = Arc::new(AtomicU64::new(0));
+    let tick_share_mismatches = share_mismatches.clone();
 
     let thread_log_count = log_count.clone();
     let thread_total_count = total_count.clone();
+    let thread_log_difficulty = log_difficulty.clone();
+    let thread_total_shares = total_shares.clone();
+    let thread_total_difficulty = total_share_difficulty.clone();
     let (stop_cnt_sndr, stop_cnt_rcvr) = unbounded();
 
     let cnt_hnd = thread::Builder::new()
@@ -42,10 +75,18 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
                     info!("stopping metric counting thread");
                     break 'select_loop;
                 },
-                recv(hash_cnt_rcvr) -> cnt_msg => {
-                    let cnt = cnt_msg.expect("Hash channel unexpectedly closed");
-                    thread_log_count.fetch_add(cnt, Ordering::SeqCst);
-                    thread_total_count.fetch_add(cnt, Ordering::SeqCst);
+                recv(event_rcvr) -> event_msg => {
+                    match event_msg.expect("Metric channel unexpectedly closed") {
+                        MetricEvent::Hashes(cnt) => {
+                            thread_log_count.fetch_add(cnt, Ordering::SeqCst);
+                            thread_total_count.fetch_add(cnt, Ordering::SeqCst);
+                        }
+                        MetricEvent::Share(difficulty) => {
+                            thread_log_difficulty.fetch_add(difficulty.value(), Ordering::SeqCst);
+                            thread_total_shares.fetch_add(1, Ordering::SeqCst);
+                            thread_total_difficulty.fetch_add(difficulty.value(), Ordering::SeqCst);
+                        }
+                    }
                 }
             }
         })
@@ -56,6 +97,7 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
     let tick_hnd = thread::Builder::new()
         .name("metric sample thread".to_string())
         .spawn(move || {
+            let mut reported_mismatches = 0;
             loop {
                 let recv_result = stop_tick_rcvr
                     .recv_timeout(time::Duration::from_secs(conf.sample_interval_seconds));
@@ -68,6 +110,10 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
                 }
 
                 let sample_cnt = log_count.swap(0, Ordering::SeqCst);
+                let sample_difficulty = log_difficulty.swap(0, Ordering::SeqCst);
+                let mismatches = tick_share_mismatches.load(Ordering::SeqCst);
+                let sample_mismatches = mismatches - reported_mismatches;
+                reported_mismatches = mismatches;
 
                 let timestamp_result = time::SystemTime::now().duration_since(time::UNIX_EPOCH);
                 if timestamp_result.is_err() {
@@ -82,7 +128,11 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
                     .append(true)
                     .open(conf.report_file.clone());
                 if let Ok(mut file) = file_result {
-                    let write_result = writeln!(file, "{};{}", millis, sample_cnt);
+                    let write_result = writeln!(
+                        file,
+                        "{};{};{};{};{}",
+                        millis, sample_cnt, sample_mismatches, memory_mode, sample_difficulty
+                    );
                     if write_result.is_err() {
                         error!("could not write metric file");
                     }
@@ -98,6 +148,10 @@ pub fn start(conf: MetricConfig, hash_cnt_rcvr: Receiver<u64>) -> Metric {
 
     Metric {
         total_hashes: total_count,
+        total_shares,
+        total_share_difficulty,
+        share_mismatches,
+        memory_mode,
         cnt_hnd,
         tick_hnd,
         stop_tick_sndr,
@@ -110,6 +164,28 @@ impl Metric {
         self.total_hashes.load(Ordering::SeqCst)
     }
 
+    /// Submitted shares
+    pub fn shares(&self) -> u64 {
+        self.total_shares.load(Ordering::SeqCst)
+    }
+
+    pub fn share_difficulty(&self) -> u64 {
+        self.total_share_difficulty.load(Ordering::SeqCst)
+    }
+
+    /// Counter for shares failing the verification, incremented by the workers.
+    pub fn share_mismatch_counter(&self) -> Arc<AtomicU64> {
+        self.share_mismatches.clone()
+    }
+
+    pub fn share_mismatches(&self) -> u64 {
+        self.share_mismatches.load(Ordering::SeqCst)
+    }
+
+    pub fn memory_mode(&self) -> MemoryMode {
+        self.memory_mode
+    }
+
     pub fn stop(&self) {
         info!("stopping metrics");
 
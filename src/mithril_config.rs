@@ -1,12 +1,17 @@
 extern crate config;
 
+use crate::bandit_tools;
 use crate::metric::MetricConfig;
+use crate::randomx::disk_cache::DiskCacheConfig;
+use crate::randomx::memory::MemoryMode;
+use crate::randomx::params::{RandomxParams, PRESETS, RX_0};
 use crate::stratum::stratum_data::PoolConfig;
-use crate::worker::worker_pool::WorkerConfig;
+use crate::worker::affinity::Affinity;
+use crate::worker::worker_pool::{ShareErrorAction, WorkerConfig};
 
 use self::config::{Config, ConfigError, File, FileFormat};
 use std;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub const CONFIG_FILE_NAME: &str = "config.toml";
 
@@ -17,6 +22,8 @@ pub struct MithrilConfig {
     pub worker_conf: WorkerConfig,
     pub metric_conf: MetricConfig,
     pub donation_conf: DonationConfig,
+    pub hardware_conf: HardwareConfig,
+    pub cache_conf: DiskCacheConfig,
 }
 
 #[derive(Clone)]
@@ -24,6 +31,12 @@ pub struct DonationConfig {
     pub percentage: f64,
 }
 
+#[derive(Clone)]
+pub struct HardwareConfig {
+    /// None: detect AES-NI support at runtime
+    pub has_aes: Option<bool>,
+}
+
 pub fn read_config(conf_file: &Path, filename: &str) -> Result<MithrilConfig, config::ConfigError> {
     let config = parse_conf(conf_file, filename)?;
 
@@ -31,12 +44,16 @@ pub fn read_config(conf_file: &Path, filename: &str) -> Result<MithrilConfig, co
     let worker_conf = worker_config(&config)?;
     let metric_conf = metric_config(&config)?;
     let donation_conf = donation_config(&config)?;
+    let hardware_conf = hardware_config(&config)?;
+    let cache_conf = cache_config(&config)?;
 
     Ok(MithrilConfig {
         pool_conf,
         worker_conf,
         metric_conf,
         donation_conf,
+        hardware_conf,
+        cache_conf,
     })
 }
 
@@ -45,14 +62,59 @@ fn donation_config(conf: &Config) -> Result<DonationConfig, ConfigError> {
     Ok(DonationConfig { percentage })
 }
 
+fn hardware_config(conf: &Config) -> Result<HardwareConfig, ConfigError> {
+    let has_aes = match conf.get_bool("hardware.has_aes") {
+        Ok(v) => Some(v),
+        Err(ConfigError::NotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
+    Ok(HardwareConfig { has_aes })
+}
+
+/// The `[cache]` section is optional, the disk cache is disabled without it
+fn cache_config(conf: &Config) -> Result<DiskCacheConfig, ConfigError> {
+    let enabled = get_bool_or_default(conf, "cache.enabled", false)?;
+    let dir = match conf.get_string("cache.dir") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        Ok(_) | Err(ConfigError::NotFound(_)) => bandit_tools::mithril_folder().join("cache"),
+        Err(e) => return Err(e),
+    };
+    let max_size_mb = match conf.get_int("cache.max_size_mb") {
+        Err(ConfigError::NotFound(_)) => 3072,
+        _ => get_u64_no_zero(conf, "cache.max_size_mb")?,
+    };
+    let dataset = get_bool_or_default(conf, "cache.dataset", false)?;
+    Ok(DiskCacheConfig {
+        enabled,
+        dir,
+        max_size_mb,
+        dataset,
+    })
+}
+
 fn pool_config(conf: &Config) -> Result<PoolConfig, ConfigError> {
     let pool_address = conf.get_string("pool.pool_address")?;
     let wallet_address = conf.get_string("pool.wallet_address")?;
     let pool_password = conf.get_string("pool.pool_password")?;
+    let algorithm = match conf.get_string("pool.algorithm") {
+        Ok(v) => match RandomxParams::by_name(&v) {
+            Some(params) => params,
+            None => {
+                return Err(ConfigError::Message(format!(
+                    "unknown algorithm {}, supported are {}",
+                    v,
+                    PRESETS.map(|p| p.name).join(", ")
+                )))
+            }
+        },
+        Err(ConfigError::NotFound(_)) => RX_0,
+        Err(e) => return Err(e),
+    };
     Ok(PoolConfig {
         pool_address,
         wallet_address,
         pool_password,
+        algorithm,
     })
 }
 
@@ -75,11 +137,89 @@ fn worker_config(conf: &Config) -> Result<WorkerConfig, ConfigError> {
 
     let auto_tune_log = conf.get_string("worker.auto_tune_log")?;
 
+    let jit = get_bool_or_default(conf, "worker.jit", true)?;
+    let eager_dataset = get_bool_or_default(conf, "worker.eager_dataset", false)?;
+    let huge_pages = get_bool_or_default(conf, "worker.huge_pages", true)?;
+    let verify_shares = get_bool_or_default(conf, "worker.verify_shares", false)?;
+    let nonce_ranges = get_bool_or_default(conf, "worker.nonce_ranges", false)?;
+
+    let max_share_error_rate = match conf.get_float("worker.max_share_error_rate") {
+        Ok(v) if !(0.0..=1.0).contains(&v) => {
+            return Err(ConfigError::Message(
+                "max_share_error_rate has to be between 0 and 1".to_string(),
+            ))
+        }
+        Ok(v) => v,
+        Err(ConfigError::NotFound(_)) => 0.1,
+        Err(e) => return Err(e),
+    };
+
+    let share_error_action = match conf.get_string("worker.share_error_action") {
+        Ok(v) if v == "restart" => ShareErrorAction::Restart,
+        Ok(v) if v == "stop" => ShareErrorAction::Stop,
+        Ok(v) => {
+            return Err(ConfigError::Message(format!(
+                "share_error_action has to be restart or stop, was {}",
+                v
+            )))
+        }
+        Err(ConfigError::NotFound(_)) => ShareErrorAction::Restart,
+        Err(e) => return Err(e),
+    };
+
+    let memory_mode = match conf.get_string("worker.memory_mode") {
+        Ok(v) if v == "full" => MemoryMode::Full,
+        Ok(v) if v == "light" => MemoryMode::Light,
+        Ok(v) if v == "auto" => MemoryMode::Auto,
+        Ok(v) => {
+            return Err(ConfigError::Message(format!(
+                "memory_mode has to be full, light or auto, was {}",
+                v
+            )))
+        }
+        Err(ConfigError::NotFound(_)) => MemoryMode::Full,
+        Err(e) => return Err(e),
+    };
+
+    let affinity = match conf.get_string("worker.affinity") {
+        Ok(v) if v == "auto" => Affinity::Auto,
+        Ok(v) if v == "none" => Affinity::None,
+        Ok(v) => {
+            return Err(ConfigError::Message(format!(
+                "affinity has to be auto, none or a list of CPU ids, was {}",
+                v
+            )))
+        }
+        Err(ConfigError::NotFound(_)) => Affinity::None,
+        Err(_) => Affinity::Cpus(get_cpu_ids(conf, "worker.affinity")?),
+    };
+
+    let dataset_init_threads = match conf.get_int("worker.dataset_init_threads") {
+        Ok(v) if v <= 0 => {
+            return Err(ConfigError::Message(
+                "dataset_init_threads has to be > 0".to_string(),
+            ))
+        }
+        Ok(v) => Some(v as u64),
+        Err(ConfigError::NotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
+
     Ok(WorkerConfig {
         num_threads: num_threads as u64,
         auto_tune,
         auto_tune_interval_minutes: auto_tune_interval_minutes as u64,
         auto_tune_log,
+        jit,
+        eager_dataset,
+        dataset_init_threads,
+        memory_mode,
+        nonce_ranges,
+        affinity,
+        huge_pages,
+        verify_shares,
+        max_share_error_rate,
+        share_error_action,
     })
 }
 
@@ -113,6 +253,31 @@ fn get_u64_no_zero(conf: &Config, field: &str) -> Result<u64, ConfigError> {
     Ok(val as u64)
 }
 
+/// Optional setting, older config files may not contain the field
+fn get_bool_or_default(conf: &Config, field: &str, default: bool) -> Result<bool, ConfigError> {
+    match conf.get_bool(field) {
+        Err(ConfigError::NotFound(_)) => Ok(default),
+        other => other,
+    }
+}
+
+/// A non-empty list of CPU ids (`worker.affinity`)
+fn get_cpu_ids(conf: &Config, field: &str) -> Result<Vec<usize>, ConfigError> {
+    let invalid =
+        || ConfigError::Message(format!("{} has to be a non-empty list of CPU ids", field));
+    let values = conf.get_array(field)?;
+    if values.is_empty() {
+        return Err(invalid());
+    }
+    values
+        .into_iter()
+        .map(|v| match v.into_int() {
+            Ok(id) if id >= 0 => Ok(id as usize),
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
 fn parse_conf(conf_file: &Path, filename: &str) -> Result<Config, ConfigError> {
     if conf_file.exists() {
         return Config::builder()
@@ -126,6 +291,7 @@ pub fn donation_conf() -> PoolConfig {
     PoolConfig {
         pool_address: "xmrpool.eu:3333".to_string(),
         pool_password: "x".to_string(),
-        wallet_address: "48y3RCT5SzSS4jumHm9rRL91eWWzd6xcVGSCF1KUZGWYJ6npqwFxHee4xkLLNUqY4NjiswdJhxFALeRqzncHoToeJMg2bhL".to_string()
+        wallet_address: "48y3RCT5SzSS4jumHm9rRL91eWWzd6xcVGSCF1KUZGWYJ6npqwFxHee4xkLLNUqY4NjiswdJhxFALeRqzncHoToeJMg2bhL".to_string(),
+        algorithm: RX_0,
     }
 }
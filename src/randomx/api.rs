@@ -0,0 +1,368 @@
+//! Library interface modelled on the reference implementation (`randomx.h`):
+//! a `RandomxCache` (light mode), a `RandomxDataset` (full mode) and a
+//! `RandomxVm` calculating the hashes. Like the reference, the API is fixed to
+//! one RandomX variant (rx/0).
+//!
+//! The cache (Argon2 memory) always uses normal pages, `LARGE_PAGES` only
+//! applies to the dataset and the VM scratchpad. As in the reference, a VM
+//! without `HARD_AES` uses software AES, whatever the process wide selection
+//! (`soft_aes::hardware_aes`) is.
+
+extern crate blake2b_simd;
+
+use self::blake2b_simd::Hash;
+use super::jit::JitProgram;
+use super::memory::{init_dataset_range, MemoryRegion, PageKind, SeedMemory, VmMemory};
+use super::params::RX_0;
+use super::soft_aes::{self, AesModeGuard};
+use super::vm::{new_vm, NoPendingHash, Vm};
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+use std::slice;
+use std::sync::Arc;
+
+/// Same bit values as `randomx_flags` in the reference implementation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RandomxFlags(u32);
+
+impl RandomxFlags {
+    pub const DEFAULT: RandomxFlags = RandomxFlags(0);
+    pub const LARGE_PAGES: RandomxFlags = RandomxFlags(1);
+    pub const HARD_AES: RandomxFlags = RandomxFlags(2);
+    pub const FULL_MEM: RandomxFlags = RandomxFlags(4);
+    pub const JIT: RandomxFlags = RandomxFlags(8);
+
+    /// The flags for the fastest supported configuration on this machine
+    /// (without `FULL_MEM` and `LARGE_PAGES`), like `randomx_get_flags`.
+    pub fn recommended() -> RandomxFlags {
+        let mut flags = RandomxFlags::DEFAULT;
+        if soft_aes::detect_hardware_aes() {
+            flags |= RandomxFlags::HARD_AES;
+        }
+        if JitProgram::new().is_some() {
+            flags |= RandomxFlags::JIT;
+        }
+        flags
+    }
+
+    pub fn from_bits(bits: u32) -> RandomxFlags {
+        RandomxFlags(bits)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(self, other: RandomxFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for RandomxFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        RandomxFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for RandomxFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RandomxError {
+    /// `LARGE_PAGES` set, but no huge pages could be allocated
+    LargePagesUnavailable,
+    /// `HARD_AES` set, but the CPU has no AES-NI
+    HardAesUnavailable,
+    /// `JIT` set, but no executable memory could be allocated (or not an x86-64 target)
+    JitUnavailable,
+    /// VM in light mode created without a cache
+    MissingCache,
+    /// VM with `FULL_MEM` created without a dataset
+    MissingDataset,
+    /// `start_item + item_count` is above `RandomxDataset::ITEM_COUNT`
+    DatasetRange { start_item: u64, item_count: u64 },
+    /// the dataset is used by a VM and can not be changed
+    DatasetInUse,
+    /// cache set on a `FULL_MEM` VM or dataset set on a light mode VM
+    WrongMode,
+    /// `calculate_hash_next`/`calculate_hash_last` without `calculate_hash_first`
+    NoPendingHash,
+}
+
+impl fmt::Display for RandomxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RandomxError::LargePagesUnavailable => f.write_str("large pages not available"),
+            RandomxError::HardAesUnavailable => f.write_str("hardware AES not available"),
+            RandomxError::JitUnavailable => f.write_str("JIT compiler not available"),
+            RandomxError::MissingCache => f.write_str("light mode VM requires a cache"),
+            RandomxError::MissingDataset => f.write_str("full memory VM requires a dataset"),
+            RandomxError::DatasetRange {
+                start_item,
+                item_count,
+            } => write!(
+                f,
+                "dataset items {}..{} out of range (item count {})",
+                start_item,
+                start_item.saturating_add(*item_count),
+                RandomxDataset::ITEM_COUNT
+            ),
+            RandomxError::DatasetInUse => f.write_str("dataset in use by a VM"),
+            RandomxError::WrongMode => f.write_str("memory does not match the VM mode"),
+            RandomxError::NoPendingHash => f.write_str("calculate_hash_first not called"),
+        }
+    }
+}
+
+impl Error for RandomxError {}
+
+impl From<NoPendingHash> for RandomxError {
+    fn from(_: NoPendingHash) -> Self {
+        RandomxError::NoPendingHash
+    }
+}
+
+/// The Argon2 filled cache for a key, sufficient for hashing in light mode
+/// and for initialising a dataset.
+pub struct RandomxCache {
+    memory: Arc<VmMemory>,
+    flags: RandomxFlags,
+}
+
+impl RandomxCache {
+    /// A cache with compiled superscalar programs (`RandomxFlags::JIT`).
+    pub fn new(key: &[u8]) -> RandomxCache {
+        RandomxCache::with_flags(key, RandomxFlags::JIT)
+    }
+
+    /// Like `randomx_alloc_cache`, only `JIT` is used: the superscalar programs
+    /// computing the dataset items are compiled, they are interpreted without it.
+    pub fn with_flags(key: &[u8], flags: RandomxFlags) -> RandomxCache {
+        RandomxCache {
+            memory: RandomxCache::memory(key, flags),
+            flags,
+        }
+    }
+
+    fn memory(key: &[u8], flags: RandomxFlags) -> Arc<VmMemory> {
+        let jit = flags.contains(RandomxFlags::JIT);
+        Arc::new(VmMemory::light_with_params(key, &RX_0, jit))
+    }
+
+    /// Re-initialises the cache with a new key. VMs created from the cache keep the old one
+    /// until `RandomxVm::set_cache` is called.
+    pub fn init(&mut self, key: &[u8]) {
+        self.memory = RandomxCache::memory(key, self.flags);
+    }
+
+    pub fn flags(&self) -> RandomxFlags {
+        self.flags
+    }
+
+    fn seed_memory(&self) -> &SeedMemory {
+        &self.memory.seed_memory
+    }
+}
+
+/// The full (2GiB) dataset, initialised item by item from a cache.
+pub struct RandomxDataset {
+    memory: Arc<VmMemory>,
+}
+
+impl RandomxDataset {
+    pub const ITEM_COUNT: u64 = RX_0.dataset_item_count() as u64;
+
+    /// Allocates the (not yet initialised) dataset. Fails with `LargePagesUnavailable`
+    /// if `LARGE_PAGES` is set and no huge pages could be allocated.
+    pub fn new(flags: RandomxFlags) -> Result<RandomxDataset, RandomxError> {
+        let large_pages = flags.contains(RandomxFlags::LARGE_PAGES);
+        let dataset = MemoryRegion::allocate(RX_0.dataset_item_count() * 8, large_pages);
+        if large_pages && dataset.page_kind() == PageKind::Normal {
+            return Err(RandomxError::LargePagesUnavailable);
+        }
+        Ok(RandomxDataset {
+            memory: Arc::new(VmMemory::complete(SeedMemory::no_memory(), dataset)),
+        })
+    }
+
+    /// Initialises `item_count` items starting with `start_item`, like `randomx_init_dataset`.
+    /// Fails with `DatasetInUse` while a VM uses the dataset.
+    pub fn init(
+        &mut self,
+        cache: &RandomxCache,
+        start_item: u64,
+        item_count: u64,
+    ) -> Result<(), RandomxError> {
+        self.init_parallel(cache, start_item, item_count, 1)
+    }
+
+    /// Like `init`, the items are split over `threads` threads.
+    pub fn init_parallel(
+        &mut self,
+        cache: &RandomxCache,
+        start_item: u64,
+        item_count: u64,
+        threads: u64,
+    ) -> Result<(), RandomxError> {
+        check_dataset_range(start_item, item_count)?;
+        let dataset = Arc::get_mut(&mut self.memory)
+            .and_then(|memory| memory.dataset_mut())
+            .ok_or(RandomxError::DatasetInUse)?;
+        let items =
+            &mut dataset[(start_item * 8) as usize..((start_item + item_count) * 8) as usize];
+        init_dataset_range(cache.seed_memory(), items, start_item, threads);
+        Ok(())
+    }
+
+    /// Like `init`, but without exclusive access. This is the contract of the C
+    /// `randomx_init_dataset`, which is called from several threads for disjoint ranges.
+    ///
+    /// # Safety
+    /// The range must be checked with `check_dataset_range`. Concurrent calls must
+    /// initialise disjoint ranges and no VM may hash with the dataset until all calls
+    /// returned.
+    pub(crate) unsafe fn init_unsynchronized(
+        &self,
+        cache: &RandomxCache,
+        start_item: u64,
+        item_count: u64,
+    ) {
+        let dataset = self.memory.dataset().expect("complete dataset");
+        let items = slice::from_raw_parts_mut(
+            dataset.as_mut_ptr().add((start_item * 8) as usize),
+            (item_count * 8) as usize,
+        );
+        init_dataset_range(cache.seed_memory(), items, start_item, 1);
+    }
+
+    /// The dataset memory, 8 u64 values per item (`randomx_get_dataset_memory`).
+    pub fn memory(&self) -> &[u64] {
+        self.memory.dataset().expect("complete dataset")
+    }
+}
+
+pub(crate) fn check_dataset_range(start_item: u64, item_count: u64) -> Result<(), RandomxError> {
+    let end_item = start_item.checked_add(item_count);
+    if end_item.is_none_or(|end| end > RandomxDataset::ITEM_COUNT) {
+        return Err(RandomxError::DatasetRange {
+            start_item,
+            item_count,
+        });
+    }
+    Ok(())
+}
+
+pub struct RandomxVm {
+    vm: Vm,
+    flags: RandomxFlags,
+}
+
+impl RandomxVm {
+    /// Creates a VM, like `randomx_create_vm`. A `cache` is required in light mode,
+    /// a `dataset` with `FULL_MEM`.
+    pub fn new(
+        flags: RandomxFlags,
+        cache: Option<&RandomxCache>,
+        dataset: Option<&RandomxDataset>,
+    ) -> Result<RandomxVm, RandomxError> {
+        let memory = RandomxVm::memory(flags, cache, dataset)?;
+        if flags.contains(RandomxFlags::HARD_AES) && !soft_aes::detect_hardware_aes() {
+            return Err(RandomxError::HardAesUnavailable);
+        }
+
+        let mut vm = new_vm(memory);
+        if flags.contains(RandomxFlags::LARGE_PAGES) {
+            let scratchpad = MemoryRegion::allocate(vm.params.scratchpad_len(), true);
+            if scratchpad.page_kind() == PageKind::Normal {
+                return Err(RandomxError::LargePagesUnavailable);
+            }
+            vm.scratchpad = scratchpad;
+        }
+        if flags.contains(RandomxFlags::JIT) {
+            vm.jit = Some(JitProgram::new().ok_or(RandomxError::JitUnavailable)?);
+        }
+        Ok(RandomxVm { vm, flags })
+    }
+
+    fn memory(
+        flags: RandomxFlags,
+        cache: Option<&RandomxCache>,
+        dataset: Option<&RandomxDataset>,
+    ) -> Result<Arc<VmMemory>, RandomxError> {
+        if flags.contains(RandomxFlags::FULL_MEM) {
+            dataset
+                .map(|d| d.memory.clone())
+                .ok_or(RandomxError::MissingDataset)
+        } else {
+            cache
+                .map(|c| c.memory.clone())
+                .ok_or(RandomxError::MissingCache)
+        }
+    }
+
+    pub fn flags(&self) -> RandomxFlags {
+        self.flags
+    }
+
+    /// Switches to another cache (light mode only).
+    pub fn set_cache(&mut self, cache: &RandomxCache) -> Result<(), RandomxError> {
+        if self.flags.contains(RandomxFlags::FULL_MEM) {
+            return Err(RandomxError::WrongMode);
+        }
+        self.vm.mem = cache.memory.clone();
+        Ok(())
+    }
+
+    /// Switches to another dataset (`FULL_MEM` only).
+    pub fn set_dataset(&mut self, dataset: &RandomxDataset) -> Result<(), RandomxError> {
+        if !self.flags.contains(RandomxFlags::FULL_MEM) {
+            return Err(RandomxError::WrongMode);
+        }
+        self.vm.mem = dataset.memory.clone();
+        Ok(())
+    }
+
+    /// True if the VM was created with `HARD_AES`, software AES is used otherwise.
+    pub fn hardware_aes(&self) -> bool {
+        self.flags.contains(RandomxFlags::HARD_AES)
+    }
+
+    fn aes_mode(&self) -> AesModeGuard {
+        AesModeGuard::set(self.hardware_aes())
+    }
+
+    pub fn calculate_hash(&mut self, input: &[u8]) -> [u8; 32] {
+        let _aes = self.aes_mode();
+        to_bytes(self.vm.calculate_hash(input))
+    }
+
+    /// See `Vm::calculate_hash_first`.
+    pub fn calculate_hash_first(&mut self, input: &[u8]) {
+        let _aes = self.aes_mode();
+        self.vm.calculate_hash_first(input);
+    }
+
+    /// See `Vm::calculate_hash_next`.
+    pub fn calculate_hash_next(&mut self, next_input: &[u8]) -> Result<[u8; 32], RandomxError> {
+        let _aes = self.aes_mode();
+        Ok(to_bytes(self.vm.calculate_hash_next(next_input)?))
+    }
+
+    /// See `Vm::calculate_hash_last`.
+    pub fn calculate_hash_last(&mut self) -> Result<[u8; 32], RandomxError> {
+        let _aes = self.aes_mode();
+        Ok(to_bytes(self.vm.calculate_hash_last()?))
+    }
+}
+
+fn to_bytes(hash: Hash) -> [u8; 32] {
+    hash.as_bytes().try_into().expect("32 byte hash")
+}
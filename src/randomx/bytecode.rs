@@ -0,0 +1,287 @@
+//! Compact representation of a program for the interpreter. The generated
+//! program bytes are decoded once per program into a flat array of `Copy`
+//! instructions with register indices, sign-extended immediates and
+//! scratchpad masks already resolved, so that executing an instruction is a
+//! single `match` without any decoding left.
+//!
+//! `Program` (with `Instr`) remains the readable form used by the JIT.
+
+use super::common::{randomx_reciprocal, u64_from_i32_imm};
+use super::m128::m128i;
+use super::params::{RandomxParams, INSTRUCTION_COUNT};
+use super::program::{
+    Opcode, MAX_FLOAT_REG, MAX_REG, REG_NEEDS_DISPLACEMENT_IX, STORE_L3_CONDITION,
+};
+use super::vm::is_zero_or_power_of_2;
+
+/// One decoded instruction. Which fields are used depends on `op`:
+/// * integer ops with a register or immediate operand read `(r[src] & src_mask) | imm`
+/// * scratchpad accesses read the index `((r[src] & src_mask) + imm) & mask) / 8`
+///   (ISTORE with `r[dst]`)
+/// * IADD_RS shifts `r[src]` by `shift`
+/// * CBRANCH tests `mask` (the condition bits at `shift`) and jumps to `target`
+/// * IMUL_RCP has the reciprocal in `imm`
+/// * FSWAP_R swaps `e[dst]` if `src` is 1, `f[dst]` otherwise
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteInstr {
+    pub op: Opcode,
+    pub dst: u8,
+    pub src: u8,
+    pub shift: u8,
+    /// index of the instruction executed next if the branch is taken
+    pub target: u16,
+    pub imm: u64,
+    pub src_mask: u64,
+    pub mask: u64,
+}
+
+const NOP: ByteInstr = ByteInstr {
+    op: Opcode::NOP,
+    dst: 0,
+    src: 0,
+    shift: 0,
+    target: 0,
+    imm: 0,
+    src_mask: 0,
+    mask: 0,
+};
+
+/// A decoded program, the buffer is reused by the following `decode`s.
+#[derive(Default)]
+pub struct Bytecode {
+    pub entropy: [u64; 16],
+    pub code: Vec<ByteInstr>,
+}
+
+impl Bytecode {
+    pub fn new() -> Bytecode {
+        Bytecode::default()
+    }
+
+    /// Decodes the generated program `bytes` (`RandomxParams::program_bytes_len`)
+    /// with the instruction frequencies of `params`, replacing the previous program.
+    pub fn decode(&mut self, bytes: &[m128i], params: &RandomxParams) {
+        for (i, byte) in bytes.iter().take(8).enumerate() {
+            let (e1, e0) = byte.as_i64();
+            self.entropy[2 * i] = e0 as u64;
+            self.entropy[2 * i + 1] = e1 as u64;
+        }
+
+        let mut decoder = Decoder {
+            limits: params.opcode_limits(),
+            params,
+            register_usage: [-1; MAX_REG],
+        };
+        self.code.clear();
+        for (i, byte) in bytes.iter().enumerate().skip(8) {
+            let (op2, op1) = byte.as_i64();
+            let ix = ((i - 8) * 2) as i32;
+            let instr1 = decoder.decode(op1, ix);
+            let instr2 = decoder.decode(op2, ix + 1);
+            self.code.push(instr1);
+            self.code.push(instr2);
+        }
+    }
+}
+
+struct Decoder<'a> {
+    limits: [i64; INSTRUCTION_COUNT],
+    params: &'a RandomxParams,
+    register_usage: [i32; MAX_REG],
+}
+
+impl Decoder<'_> {
+    /// Same decoding as `program::decode_instruction`
+    fn decode(&mut self, bytes: i64, i: i32) -> ByteInstr {
+        let op = bytes & 0xFF;
+        let dst = ((bytes >> 8) & 0xFF) as usize % MAX_REG;
+        let src = ((bytes >> 16) & 0xFF) as usize % MAX_REG;
+        let modi = ((bytes >> 24) & 0xFF) as u8;
+        let imm = (bytes >> 32) as i32;
+        let limit = |op: Opcode| self.limits[op as usize];
+
+        let opcode = if op < limit(Opcode::IADD_RS) {
+            Opcode::IADD_RS
+        } else if op < limit(Opcode::IADD_M) {
+            Opcode::IADD_M
+        } else if op < limit(Opcode::ISUB_R) {
+            Opcode::ISUB_R
+        } else if op < limit(Opcode::ISUB_M) {
+            Opcode::ISUB_M
+        } else if op < limit(Opcode::IMUL_R) {
+            Opcode::IMUL_R
+        } else if op < limit(Opcode::IMUL_M) {
+            Opcode::IMUL_M
+        } else if op < limit(Opcode::IMULH_R) {
+            Opcode::IMULH_R
+        } else if op < limit(Opcode::IMULH_M) {
+            Opcode::IMULH_M
+        } else if op < limit(Opcode::ISMULH_R) {
+            Opcode::ISMULH_R
+        } else if op < limit(Opcode::ISMULH_M) {
+            Opcode::ISMULH_M
+        } else if op < limit(Opcode::IMUL_RCP) {
+            Opcode::IMUL_RCP
+        } else if op < limit(Opcode::INEG_R) {
+            Opcode::INEG_R
+        } else if op < limit(Opcode::IXOR_R) {
+            Opcode::IXOR_R
+        } else if op < limit(Opcode::IXOR_M) {
+            Opcode::IXOR_M
+        } else if op < limit(Opcode::IROR_R) {
+            Opcode::IROR_R
+        } else if op < limit(Opcode::IROL_R) {
+            Opcode::IROL_R
+        } else if op < limit(Opcode::ISWAP_R) {
+            Opcode::ISWAP_R
+        } else if op < limit(Opcode::FSWAP_R) {
+            Opcode::FSWAP_R
+        } else if op < limit(Opcode::FADD_R) {
+            Opcode::FADD_R
+        } else if op < limit(Opcode::FADD_M) {
+            Opcode::FADD_M
+        } else if op < limit(Opcode::FSUB_R) {
+            Opcode::FSUB_R
+        } else if op < limit(Opcode::FSUB_M) {
+            Opcode::FSUB_M
+        } else if op < limit(Opcode::FSCAL_R) {
+            Opcode::FSCAL_R
+        } else if op < limit(Opcode::FMUL_R) {
+            Opcode::FMUL_R
+        } else if op < limit(Opcode::FDIV_M) {
+            Opcode::FDIV_M
+        } else if op < limit(Opcode::FSQRT_R) {
+            Opcode::FSQRT_R
+        } else if op < limit(Opcode::CBRANCH) {
+            Opcode::CBRANCH
+        } else if op < limit(Opcode::CFROUND) {
+            Opcode::CFROUND
+        } else if op < limit(Opcode::ISTORE) {
+            Opcode::ISTORE
+        } else {
+            Opcode::NOP
+        };
+
+        let mut instr = ByteInstr {
+            op: opcode,
+            dst: dst as u8,
+            src: src as u8,
+            ..NOP
+        };
+        match opcode {
+            Opcode::IADD_RS => {
+                self.register_usage[dst] = i;
+                instr.shift = (modi >> 2) % 4;
+                if dst == REG_NEEDS_DISPLACEMENT_IX {
+                    instr.imm = u64_from_i32_imm(imm);
+                }
+            }
+            Opcode::IADD_M
+            | Opcode::ISUB_M
+            | Opcode::IMUL_M
+            | Opcode::IMULH_M
+            | Opcode::ISMULH_M
+            | Opcode::IXOR_M => {
+                self.register_usage[dst] = i;
+                self.mem_operand(&mut instr, src, dst, imm, modi);
+            }
+            Opcode::ISUB_R | Opcode::IMUL_R | Opcode::IXOR_R => {
+                self.register_usage[dst] = i;
+                reg_or_imm_operand(&mut instr, src, dst, u64_from_i32_imm(imm));
+            }
+            Opcode::IROR_R | Opcode::IROL_R => {
+                self.register_usage[dst] = i;
+                reg_or_imm_operand(&mut instr, src, dst, (imm & 63) as u64);
+            }
+            Opcode::IMULH_R | Opcode::ISMULH_R | Opcode::INEG_R => {
+                self.register_usage[dst] = i;
+            }
+            Opcode::IMUL_RCP => {
+                if is_zero_or_power_of_2(imm as u64) {
+                    return NOP;
+                }
+                self.register_usage[dst] = i;
+                instr.imm = randomx_reciprocal((imm as u64) & 0xFFFFFFFF);
+            }
+            Opcode::ISWAP_R => {
+                if src == dst {
+                    return NOP;
+                }
+                self.register_usage[dst] = i;
+                self.register_usage[src] = i;
+            }
+            Opcode::FSWAP_R => {
+                instr.src = (dst >= MAX_FLOAT_REG) as u8;
+                instr.dst = (dst % MAX_FLOAT_REG) as u8;
+            }
+            Opcode::FADD_R | Opcode::FSUB_R | Opcode::FMUL_R => {
+                instr.dst = (dst % MAX_FLOAT_REG) as u8;
+                instr.src = (src % MAX_FLOAT_REG) as u8;
+            }
+            Opcode::FADD_M | Opcode::FSUB_M | Opcode::FDIV_M => {
+                //dst is a float register, never the same as src
+                self.mem_operand(&mut instr, src, MAX_REG, imm, modi);
+                instr.dst = (dst % MAX_FLOAT_REG) as u8;
+            }
+            Opcode::FSCAL_R | Opcode::FSQRT_R => {
+                instr.dst = (dst % MAX_FLOAT_REG) as u8;
+            }
+            Opcode::CBRANCH => {
+                let shift = (modi >> 4) as u64 + self.params.jump_offset;
+                let mut branch_imm = u64_from_i32_imm(imm) | 1 << shift;
+                if self.params.jump_offset > 0 || shift > 0 {
+                    branch_imm &= !(1 << (shift - 1));
+                }
+                instr.imm = branch_imm;
+                instr.shift = shift as u8;
+                instr.mask = self.params.condition_mask() << shift;
+                instr.target = (self.register_usage[dst] + 1) as u16;
+                for usage in self.register_usage.iter_mut() {
+                    *usage = i;
+                }
+            }
+            Opcode::CFROUND => {
+                instr.imm = (imm & 63) as u64;
+            }
+            Opcode::ISTORE => {
+                instr.imm = u64_from_i32_imm(imm);
+                instr.mask = if (modi >> 4) < STORE_L3_CONDITION {
+                    self.l12_mask(modi)
+                } else {
+                    self.params.scratchpad_l3_mask()
+                };
+            }
+            Opcode::NOP => {}
+        }
+        instr
+    }
+
+    /// L1/L2 address with `r[src]`, or a fixed L3 address if `src` and `dst` are the same register
+    fn mem_operand(&self, instr: &mut ByteInstr, src: usize, dst: usize, imm: i32, modi: u8) {
+        instr.imm = u64_from_i32_imm(imm);
+        if src == dst {
+            instr.src_mask = 0;
+            instr.mask = self.params.scratchpad_l3_mask();
+        } else {
+            instr.src_mask = !0;
+            instr.mask = self.l12_mask(modi);
+        }
+    }
+
+    fn l12_mask(&self, modi: u8) -> u64 {
+        if modi.is_multiple_of(4) {
+            self.params.scratchpad_l2_mask()
+        } else {
+            self.params.scratchpad_l1_mask()
+        }
+    }
+}
+
+fn reg_or_imm_operand(instr: &mut ByteInstr, src: usize, dst: usize, imm: u64) {
+    if src == dst {
+        instr.imm = imm;
+        instr.src_mask = 0;
+    } else {
+        instr.src_mask = !0;
+    }
+}
@@ -0,0 +1,157 @@
+//! Dataset item computation of four items side by side in the 64 bit lanes of
+//! AVX2 registers. All items run the same superscalar programs, only the
+//! cache lines mixed in differ and are gathered per lane.
+//!
+//! AVX2 has no 64 bit multiplication, the products are put together from
+//! 32x32 bit multiplications (`vpmuludq`).
+
+use std::arch::x86_64::*;
+
+use super::common::u64_from_u32_imm;
+use super::memory::{init_dataset_registers, SeedMemory, CACHE_LINE_SIZE};
+use super::superscalar::{ScOpcode, ScProgram};
+
+pub const LANES: usize = 4;
+
+/// Whether the CPU supports the instructions used here.
+pub fn available() -> bool {
+    is_x86_feature_detected!("avx2")
+}
+
+/// Computes the items `start..start + out.len() / 32` into `out` (8 `u64` per item),
+/// `out.len()` has to be a multiple of 4 items.
+///
+/// # Safety
+/// AVX2 has to be `available`.
+#[target_feature(enable = "avx2")]
+pub unsafe fn init_dataset_items_x4(seed_mem: &SeedMemory, start: u64, out: &mut [u64]) {
+    assert!(out.len().is_multiple_of(8 * LANES));
+    let cache = seed_mem.cache();
+    let line_mask = (cache.len() as u64 * 8 / CACHE_LINE_SIZE) - 1;
+
+    for (step, items) in out.chunks_exact_mut(8 * LANES).enumerate() {
+        let first = start + (step * LANES) as u64;
+        let init: [[u64; 8]; LANES] = [
+            init_dataset_registers(first),
+            init_dataset_registers(first + 1),
+            init_dataset_registers(first + 2),
+            init_dataset_registers(first + 3),
+        ];
+        let mut r = [_mm256_setzero_si256(); 8];
+        for (i, reg) in r.iter_mut().enumerate() {
+            *reg = set_lanes(init[0][i], init[1][i], init[2][i], init[3][i]);
+        }
+
+        let mut reg_value = set_lanes(first, first + 1, first + 2, first + 3);
+        for prog in &seed_mem.programs {
+            execute(prog, &mut r);
+
+            //word index of the cache line of each lane
+            let line = _mm256_and_si256(reg_value, _mm256_set1_epi64x(line_mask as i64));
+            let base = _mm256_slli_epi64(line, 3);
+            for (i, reg) in r.iter_mut().enumerate() {
+                let ix = _mm256_add_epi64(base, _mm256_set1_epi64x(i as i64));
+                let mix = _mm256_i64gather_epi64::<8>(cache.as_ptr() as *const i64, ix);
+                *reg = _mm256_xor_si256(*reg, mix);
+            }
+            reg_value = r[prog.address_reg];
+        }
+
+        let mut lanes = [[0u64; LANES]; 8];
+        for (i, reg) in r.iter().enumerate() {
+            _mm256_storeu_si256(lanes[i].as_mut_ptr() as *mut __m256i, *reg);
+        }
+        for (lane, item) in items.chunks_exact_mut(8).enumerate() {
+            for (i, v) in item.iter_mut().enumerate() {
+                *v = lanes[i][lane];
+            }
+        }
+    }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn execute(prog: &ScProgram, r: &mut [__m256i; 8]) {
+    for instr in &prog.prog {
+        let dst = instr.dst as usize;
+        let src = instr.src as usize;
+        r[dst] = match instr.info.op {
+            ScOpcode::ISUB_R => _mm256_sub_epi64(r[dst], r[src]),
+            ScOpcode::IXOR_R => _mm256_xor_si256(r[dst], r[src]),
+            ScOpcode::IADD_RS => {
+                let shift = _mm_cvtsi64_si128(instr.mod_shift() as i64);
+                _mm256_add_epi64(r[dst], _mm256_sll_epi64(r[src], shift))
+            }
+            ScOpcode::IMUL_R => mul_lo(r[dst], r[src]),
+            ScOpcode::IROR_C => {
+                let right = _mm_cvtsi64_si128(instr.imm32 as i64);
+                let left = _mm_cvtsi64_si128(64 - instr.imm32 as i64);
+                _mm256_or_si256(
+                    _mm256_srl_epi64(r[dst], right),
+                    _mm256_sll_epi64(r[dst], left),
+                )
+            }
+            ScOpcode::IADD_C7 | ScOpcode::IADD_C8 | ScOpcode::IADD_C9 => {
+                let imm = _mm256_set1_epi64x(u64_from_u32_imm(instr.imm32) as i64);
+                _mm256_add_epi64(r[dst], imm)
+            }
+            ScOpcode::IXOR_C7 | ScOpcode::IXOR_C8 | ScOpcode::IXOR_C9 => {
+                let imm = _mm256_set1_epi64x(u64_from_u32_imm(instr.imm32) as i64);
+                _mm256_xor_si256(r[dst], imm)
+            }
+            ScOpcode::IMULH_R => mul_hi(r[dst], r[src]),
+            ScOpcode::ISMULH_R => mul_hi_signed(r[dst], r[src]),
+            ScOpcode::IMUL_RCP => mul_lo(r[dst], _mm256_set1_epi64x(instr.reciprocal as i64)),
+            ScOpcode::INVALID | ScOpcode::COUNT => panic!("invalid opcode {}", instr.info.op),
+        };
+    }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn set_lanes(l0: u64, l1: u64, l2: u64, l3: u64) -> __m256i {
+    _mm256_set_epi64x(l3 as i64, l2 as i64, l1 as i64, l0 as i64)
+}
+
+/// Lower 64 bit of the product
+#[target_feature(enable = "avx2")]
+unsafe fn mul_lo(a: __m256i, b: __m256i) -> __m256i {
+    let lo = _mm256_mul_epu32(a, b);
+    let cross = _mm256_add_epi64(
+        _mm256_mul_epu32(_mm256_srli_epi64(a, 32), b),
+        _mm256_mul_epu32(a, _mm256_srli_epi64(b, 32)),
+    );
+    _mm256_add_epi64(lo, _mm256_slli_epi64(cross, 32))
+}
+
+/// Upper 64 bit of the unsigned product
+#[target_feature(enable = "avx2")]
+unsafe fn mul_hi(a: __m256i, b: __m256i) -> __m256i {
+    let a_hi = _mm256_srli_epi64(a, 32);
+    let b_hi = _mm256_srli_epi64(b, 32);
+    let ll = _mm256_mul_epu32(a, b);
+    let lh = _mm256_mul_epu32(a, b_hi);
+    let hl = _mm256_mul_epu32(a_hi, b);
+    let hh = _mm256_mul_epu32(a_hi, b_hi);
+
+    let low_mask = _mm256_set1_epi64x(0xFFFFFFFF);
+    let mid = _mm256_add_epi64(
+        _mm256_add_epi64(_mm256_srli_epi64(ll, 32), _mm256_and_si256(lh, low_mask)),
+        _mm256_and_si256(hl, low_mask),
+    );
+    _mm256_add_epi64(
+        _mm256_add_epi64(hh, _mm256_srli_epi64(lh, 32)),
+        _mm256_add_epi64(_mm256_srli_epi64(hl, 32), _mm256_srli_epi64(mid, 32)),
+    )
+}
+
+/// Upper 64 bit of the signed product, the unsigned one corrected for negative factors
+#[target_feature(enable = "avx2")]
+unsafe fn mul_hi_signed(a: __m256i, b: __m256i) -> __m256i {
+    let zero = _mm256_setzero_si256();
+    let a_neg = _mm256_cmpgt_epi64(zero, a);
+    let b_neg = _mm256_cmpgt_epi64(zero, b);
+    let hi = mul_hi(a, b);
+    _mm256_sub_epi64(
+        _mm256_sub_epi64(hi, _mm256_and_si256(a_neg, b)),
+        _mm256_and_si256(b_neg, a),
+    )
+}
@@ -0,0 +1,259 @@
+//! Persistent cache of the seed memory and (optionally) the full dataset,
+//! keyed by the seed hash (and the RandomX variant, see `DiskCache::entry_key`).
+//!
+//! Each entry is a file with the raw content in native byte order followed by
+//! a trailer (magic, length, blake2b checksum of the content). Invalid entries
+//! are deleted on load. The entries of other seeds (stale epochs) are evicted
+//! before the memory of a new seed is built, and if the cache grows above its
+//! size limit, least recently modified first.
+
+extern crate argon2;
+extern crate blake2b_simd;
+
+use self::argon2::block::Block;
+use self::argon2::common::QWORDS_IN_BLOCK;
+use self::blake2b_simd::Params;
+use super::memory::{MemoryRegion, SeedMemory};
+use super::params::{RandomxParams, RX_0};
+
+use std::fs::{self, DirBuilder, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::slice;
+
+const MAGIC: &[u8; 8] = b"MTHRLC01";
+const CHECKSUM_SIZE: usize = 32;
+const TRAILER_SIZE: usize = MAGIC.len() + 8 + CHECKSUM_SIZE;
+
+const SEED_EXTENSION: &str = "seed";
+const DATASET_EXTENSION: &str = "dataset";
+
+#[derive(Clone)]
+pub struct DiskCacheConfig {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub max_size_mb: u64,
+    /// Also cache the full dataset (2GiB), not only the seed memory
+    pub dataset: bool,
+}
+
+#[derive(Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_size: u64,
+    dataset: bool,
+}
+
+impl DiskCache {
+    /// Creates the cache, the directory is created if it does not exist.
+    pub fn new(config: &DiskCacheConfig) -> io::Result<DiskCache> {
+        DirBuilder::new().recursive(true).create(&config.dir)?;
+        Ok(DiskCache {
+            dir: config.dir.clone(),
+            max_size: config.max_size_mb * 1024 * 1024,
+            dataset: config.dataset,
+        })
+    }
+
+    pub fn stores_dataset(&self) -> bool {
+        self.dataset
+    }
+
+    /// The key of the entries for `seed_hash`, rx/0 entries are only keyed by the seed hash.
+    pub fn entry_key(seed_hash: &str, params: &RandomxParams) -> String {
+        if *params == RX_0 {
+            return seed_hash.to_string();
+        }
+        format!("{}-{}", seed_hash, params.name.replace('/', "-"))
+    }
+
+    pub fn load_seed_memory(
+        &self,
+        seed_hash: &str,
+        key: &[u8],
+        params: &RandomxParams,
+        jit: bool,
+    ) -> Option<SeedMemory> {
+        let path = self.entry_path(seed_hash, SEED_EXTENSION)?;
+        let block_count = params.argon_memory as usize;
+        let content = self.load(&path, block_count * QWORDS_IN_BLOCK)?;
+
+        let mut blocks = vec![Block::zero(); block_count].into_boxed_slice();
+        for (block, qwords) in blocks.iter_mut().zip(content.chunks_exact(QWORDS_IN_BLOCK)) {
+            block.as_u8_mut().copy_from_slice(as_bytes(qwords));
+        }
+        info!("seed memory loaded from disk cache {:?}", path);
+        Some(SeedMemory::from_blocks(key, blocks, params, jit))
+    }
+
+    pub fn store_seed_memory(&self, seed_hash: &str, seed_memory: &SeedMemory) -> io::Result<()> {
+        let path = match self.entry_path(seed_hash, SEED_EXTENSION) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let chunks: Vec<&[u8]> = seed_memory.blocks.iter().map(|b| b.as_u8()).collect();
+        self.store(seed_hash, &path, &chunks)
+    }
+
+    /// Loads the dataset with `len` u64 values, the file is memory-mapped.
+    pub fn load_dataset(&self, seed_hash: &str, len: usize) -> Option<MemoryRegion> {
+        if !self.dataset {
+            return None;
+        }
+        let path = self.entry_path(seed_hash, DATASET_EXTENSION)?;
+        let dataset = self.load(&path, len)?;
+        info!("dataset loaded from disk cache {:?}", path);
+        Some(dataset)
+    }
+
+    pub fn store_dataset(&self, seed_hash: &str, dataset: &[u64]) -> io::Result<()> {
+        if !self.dataset {
+            return Ok(());
+        }
+        let path = match self.entry_path(seed_hash, DATASET_EXTENSION) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        self.store(seed_hash, &path, &[as_bytes(dataset)])
+    }
+
+    /// Removes the entries of all seeds except `current_seed_hash` (entry key), called
+    /// with the first seed after startup and on each seed change.
+    pub fn evict_stale(&self, current_seed_hash: &str) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if is_cache_entry(&path) && !is_entry_of(&path, current_seed_hash) {
+                fs::remove_file(&path)?;
+                info!("evicted stale {:?} from disk cache", path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes entries of other seeds (oldest first) until `needed` more bytes fit
+    /// into the size limit. Returns false if they do not fit anyway.
+    pub fn evict(&self, keep_seed_hash: &str, needed: u64) -> io::Result<bool> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if !is_cache_entry(&path) {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            total += meta.len();
+            if !is_entry_of(&path, keep_seed_hash) {
+                entries.push((meta.modified()?, meta.len(), path));
+            }
+        }
+
+        entries.sort();
+        for (_, size, path) in entries {
+            if total + needed <= self.max_size {
+                break;
+            }
+            fs::remove_file(&path)?;
+            info!("evicted {:?} from disk cache", path);
+            total -= size;
+        }
+        Ok(total + needed <= self.max_size)
+    }
+
+    /// None if the seed hash (entry key) is not usable as a file name.
+    fn entry_path(&self, seed_hash: &str, extension: &str) -> Option<PathBuf> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-';
+        if seed_hash.is_empty() || !seed_hash.chars().all(valid) {
+            return None;
+        }
+        let mut path = self.dir.join(seed_hash);
+        path.set_extension(extension);
+        Some(path)
+    }
+
+    fn load(&self, path: &Path, len: usize) -> Option<MemoryRegion> {
+        let file = File::open(path).ok()?;
+        match read_entry(&file, len) {
+            Ok(content) => Some(content),
+            Err(err) => {
+                warn!("invalid disk cache entry {:?} ({}), deleting it", path, err);
+                let _ = fs::remove_file(path);
+                None
+            }
+        }
+    }
+
+    fn store(&self, seed_hash: &str, path: &Path, chunks: &[&[u8]]) -> io::Result<()> {
+        let len: usize = chunks.iter().map(|c| c.len()).sum();
+        if !self.evict(seed_hash, (len + TRAILER_SIZE) as u64)? {
+            warn!("disk cache size limit too small for {:?}, not stored", path);
+            return Ok(());
+        }
+
+        //written to a temporary file first, a crash never leaves a partial entry
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        for chunk in chunks {
+            writer.write_all(chunk)?;
+        }
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(len as u64).to_le_bytes())?;
+        writer.write_all(&checksum(chunks))?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        info!("stored {:?} in disk cache", path);
+        Ok(())
+    }
+}
+
+fn is_cache_entry(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some(SEED_EXTENSION) | Some(DATASET_EXTENSION) | Some("tmp")
+    )
+}
+
+fn is_entry_of(path: &Path, seed_hash: &str) -> bool {
+    path.file_stem().and_then(|s| s.to_str()) == Some(seed_hash)
+}
+
+fn read_entry(file: &File, len: usize) -> io::Result<MemoryRegion> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let size = len * 8;
+    if file.metadata()?.len() != (size + TRAILER_SIZE) as u64 {
+        return Err(invalid("wrong size"));
+    }
+    let mut trailer = [0; TRAILER_SIZE];
+    let mut reader = file;
+    reader.seek(SeekFrom::Start(size as u64))?;
+    reader.read_exact(&mut trailer)?;
+    let (magic, rest) = trailer.split_at(MAGIC.len());
+    let (stored_size, stored_checksum) = rest.split_at(8);
+    if magic != MAGIC || stored_size != (size as u64).to_le_bytes() {
+        return Err(invalid("wrong trailer"));
+    }
+
+    let content = MemoryRegion::map_file(file, len)?;
+    if checksum(&[as_bytes(&content)]) != stored_checksum {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(content)
+}
+
+fn checksum(chunks: &[&[u8]]) -> [u8; CHECKSUM_SIZE] {
+    let mut state = Params::new().hash_length(CHECKSUM_SIZE).to_state();
+    for chunk in chunks {
+        state.update(chunk);
+    }
+    let mut sum = [0; CHECKSUM_SIZE];
+    sum.copy_from_slice(state.finalize().as_bytes());
+    sum
+}
+
+fn as_bytes(qwords: &[u64]) -> &[u8] {
+    unsafe { slice::from_raw_parts(qwords.as_ptr() as *const u8, qwords.len() * 8) }
+}
@@ -0,0 +1,281 @@
+//! C ABI of the reference implementation (`include/randomx.h`), for linking
+//! mithril as `librandomx` replacement (cdylib/staticlib build).
+//!
+//! Invalid arguments (null pointers where the reference requires a value,
+//! dataset ranges out of bounds) abort, like the asserts of the reference
+//! implementation. Allocation failures return null.
+//!
+//! `include/randomx.h` is generated from this file with cbindgen
+//! (`make -C ctest header`, configured in `cbindgen.toml`).
+
+use super::api::{check_dataset_range, RandomxCache, RandomxDataset, RandomxFlags, RandomxVm};
+
+use std::os::raw::{c_int, c_ulong, c_void};
+use std::ptr;
+use std::slice;
+
+pub const RANDOMX_HASH_SIZE: usize = 32;
+pub const RANDOMX_DATASET_ITEM_SIZE: usize = 64;
+
+/// `randomx_flags`, the bits of `RandomxFlags`
+pub type Flags = c_int;
+
+pub const RANDOMX_FLAG_DEFAULT: Flags = 0;
+pub const RANDOMX_FLAG_LARGE_PAGES: Flags = 1;
+pub const RANDOMX_FLAG_HARD_AES: Flags = 2;
+pub const RANDOMX_FLAG_FULL_MEM: Flags = 4;
+pub const RANDOMX_FLAG_JIT: Flags = 8;
+/// Ignored.
+pub const RANDOMX_FLAG_SECURE: Flags = 16;
+/// Ignored.
+pub const RANDOMX_FLAG_ARGON2_SSSE3: Flags = 32;
+/// Ignored.
+pub const RANDOMX_FLAG_ARGON2_AVX2: Flags = 64;
+/// Ignored.
+pub const RANDOMX_FLAG_ARGON2: Flags = 96;
+
+/// `randomx_cache`, allocated with `randomx_alloc_cache` and filled for a key by
+/// `randomx_init_cache`.
+pub struct Cache {
+    cache: Option<RandomxCache>,
+    flags: Flags,
+}
+
+/// `randomx_dataset`
+pub struct Dataset {
+    dataset: RandomxDataset,
+}
+
+/// `randomx_vm`
+pub struct Machine {
+    vm: RandomxVm,
+}
+
+fn to_flags(flags: Flags) -> RandomxFlags {
+    RandomxFlags::from_bits(flags as u32)
+}
+
+/// The recommended flags for this machine (`RANDOMX_FLAG_HARD_AES`, `RANDOMX_FLAG_JIT`).
+#[no_mangle]
+pub extern "C" fn randomx_get_flags() -> Flags {
+    RandomxFlags::recommended().bits() as Flags
+}
+
+/// Allocates a cache, it has to be initialised with `randomx_init_cache` before use.
+/// The cache uses normal pages, of the flags only `RANDOMX_FLAG_JIT` is used.
+#[no_mangle]
+pub extern "C" fn randomx_alloc_cache(flags: Flags) -> *mut Cache {
+    Box::into_raw(Box::new(Cache { cache: None, flags }))
+}
+
+/// Initialises the cache memory for the key (256 MiB, takes a few seconds).
+///
+/// # Safety
+/// `cache` from `randomx_alloc_cache`, `key` valid for `key_size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_init_cache(
+    cache: *mut Cache,
+    key: *const c_void,
+    key_size: usize,
+) {
+    let cache = cache.as_mut().expect("cache");
+    let key = bytes(key, key_size);
+    match &mut cache.cache {
+        Some(c) => c.init(key),
+        None => cache.cache = Some(RandomxCache::with_flags(key, to_flags(cache.flags))),
+    }
+}
+
+/// # Safety
+/// `cache` from `randomx_alloc_cache` or null.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_release_cache(cache: *mut Cache) {
+    if !cache.is_null() {
+        drop(Box::from_raw(cache));
+    }
+}
+
+/// Allocates the dataset (2 GiB), null if `RANDOMX_FLAG_LARGE_PAGES` is set and
+/// no huge pages are available.
+#[no_mangle]
+pub extern "C" fn randomx_alloc_dataset(flags: Flags) -> *mut Dataset {
+    match RandomxDataset::new(to_flags(flags)) {
+        Ok(dataset) => Box::into_raw(Box::new(Dataset { dataset })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn randomx_dataset_item_count() -> c_ulong {
+    RandomxDataset::ITEM_COUNT as c_ulong
+}
+
+/// Initialises the items `start_item..start_item + item_count` of the dataset.
+/// Nothing is initialised if the range is not within the dataset or the cache has
+/// no key.
+///
+/// # Safety
+/// `dataset` from `randomx_alloc_dataset`, `cache` from `randomx_alloc_cache`. Concurrent calls (the usual way of
+/// initialising the dataset with several threads) must use disjoint ranges, and no
+/// VM may hash with the dataset meanwhile.
+#[no_mangle]
+#[allow(clippy::unnecessary_cast)] //c_ulong is only 32 bit on Windows
+pub unsafe extern "C" fn randomx_init_dataset(
+    dataset: *mut Dataset,
+    cache: *mut Cache,
+    start_item: c_ulong,
+    item_count: c_ulong,
+) {
+    let dataset = &dataset.as_ref().expect("dataset").dataset;
+    let (start_item, item_count) = (start_item as u64, item_count as u64);
+    if let Some(cache) = initialised(cache) {
+        if check_dataset_range(start_item, item_count).is_ok() {
+            dataset.init_unsynchronized(cache, start_item, item_count);
+        }
+    }
+}
+
+/// # Safety
+/// `dataset` from `randomx_alloc_dataset`.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_get_dataset_memory(dataset: *mut Dataset) -> *mut c_void {
+    let dataset = &dataset.as_ref().expect("dataset").dataset;
+    dataset.memory().as_ptr() as *mut c_void
+}
+
+/// # Safety
+/// `dataset` from `randomx_alloc_dataset` or null. VMs using the dataset keep
+/// the memory alive until they are destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_release_dataset(dataset: *mut Dataset) {
+    if !dataset.is_null() {
+        drop(Box::from_raw(dataset));
+    }
+}
+
+/// Creates a VM, null if the flags are not supported, or the cache (light mode)
+/// resp. the dataset (`RANDOMX_FLAG_FULL_MEM`) is missing or not initialised.
+///
+/// # Safety
+/// `cache` and `dataset` null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_create_vm(
+    flags: Flags,
+    cache: *mut Cache,
+    dataset: *mut Dataset,
+) -> *mut Machine {
+    let dataset = dataset.as_ref().map(|d| &d.dataset);
+    match RandomxVm::new(to_flags(flags), initialised(cache), dataset) {
+        Ok(vm) => Box::into_raw(Box::new(Machine { vm })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Switches to another cache, ignored for VMs in full memory mode and for caches
+/// without a key.
+///
+/// # Safety
+/// `machine` from `randomx_create_vm`, `cache` from `randomx_alloc_cache`.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_vm_set_cache(machine: *mut Machine, cache: *mut Cache) {
+    let machine = &mut machine.as_mut().expect("machine").vm;
+    if let Some(cache) = initialised(cache) {
+        let _ = machine.set_cache(cache);
+    }
+}
+
+/// Switches to another dataset, ignored for VMs in light mode.
+///
+/// # Safety
+/// `machine` from `randomx_create_vm`, `dataset` from `randomx_alloc_dataset`.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_vm_set_dataset(machine: *mut Machine, dataset: *mut Dataset) {
+    let machine = &mut machine.as_mut().expect("machine").vm;
+    let _ = machine.set_dataset(&dataset.as_ref().expect("dataset").dataset);
+}
+
+/// # Safety
+/// `machine` from `randomx_create_vm` or null.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_destroy_vm(machine: *mut Machine) {
+    if !machine.is_null() {
+        drop(Box::from_raw(machine));
+    }
+}
+
+/// Writes the `RANDOMX_HASH_SIZE` bytes hash of the input to `output`.
+///
+/// # Safety
+/// `machine` from `randomx_create_vm`, `input` valid for `input_size` bytes and
+/// `output` for `RANDOMX_HASH_SIZE` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_calculate_hash(
+    machine: *mut Machine,
+    input: *const c_void,
+    input_size: usize,
+    output: *mut c_void,
+) {
+    let machine = &mut machine.as_mut().expect("machine").vm;
+    let hash = machine.calculate_hash(bytes(input, input_size));
+    ptr::copy_nonoverlapping(hash.as_ptr(), output as *mut u8, hash.len());
+}
+
+/// Pipelined hashing: starts with the first input, each `randomx_calculate_hash_next`
+/// returns the hash of the previous input and starts the next one,
+/// `randomx_calculate_hash_last` returns the hash of the final input.
+///
+/// # Safety
+/// `machine` from `randomx_create_vm`, `input` valid for `input_size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_calculate_hash_first(
+    machine: *mut Machine,
+    input: *const c_void,
+    input_size: usize,
+) {
+    let machine = &mut machine.as_mut().expect("machine").vm;
+    machine.calculate_hash_first(bytes(input, input_size));
+}
+
+/// Without a pending hash (no `randomx_calculate_hash_first`), the series is started
+/// with `next_input` and `output` is left unchanged.
+///
+/// # Safety
+/// Like `randomx_calculate_hash`.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_calculate_hash_next(
+    machine: *mut Machine,
+    next_input: *const c_void,
+    next_input_size: usize,
+    output: *mut c_void,
+) {
+    let machine = &mut machine.as_mut().expect("machine").vm;
+    let next_input = bytes(next_input, next_input_size);
+    match machine.calculate_hash_next(next_input) {
+        Ok(hash) => ptr::copy_nonoverlapping(hash.as_ptr(), output as *mut u8, hash.len()),
+        Err(_) => machine.calculate_hash_first(next_input),
+    }
+}
+
+/// `output` is left unchanged without a pending hash.
+///
+/// # Safety
+/// `machine` from `randomx_create_vm`, `output` valid for `RANDOMX_HASH_SIZE` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn randomx_calculate_hash_last(machine: *mut Machine, output: *mut c_void) {
+    let machine = &mut machine.as_mut().expect("machine").vm;
+    if let Ok(hash) = machine.calculate_hash_last() {
+        ptr::copy_nonoverlapping(hash.as_ptr(), output as *mut u8, hash.len());
+    }
+}
+
+unsafe fn initialised<'a>(cache: *mut Cache) -> Option<&'a RandomxCache> {
+    cache.as_ref().and_then(|c| c.cache.as_ref())
+}
+
+unsafe fn bytes<'a>(data: *const c_void, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data as *const u8, len)
+    }
+}
@@ -1,4 +1,5 @@
 use super::m128::m128i;
+use super::params::RX_0;
 
 #[allow(overflowing_literals)]
 fn keys_1rx4() -> (m128i, m128i, m128i, m128i) {
@@ -50,7 +51,7 @@ pub fn hash_aes_1rx4(input: &[u64]) -> [m128i; 4] {
     [state0, state1, state2, state3]
 }
 
-pub fn fill_aes_1rx4_u64(input: &[m128i; 4], into: &mut Vec<u64>) -> [m128i; 4] {
+pub fn fill_aes_1rx4_u64(input: &[m128i; 4], into: &mut [u64]) -> [m128i; 4] {
     let (key0, key1, key2, key3) = keys_1rx4();
     let mut state0 = input[0];
     let mut state1 = input[1];
@@ -81,6 +82,58 @@ pub fn fill_aes_1rx4_u64(input: &[m128i; 4], into: &mut Vec<u64>) -> [m128i; 4]
     [state0, state1, state2, state3]
 }
 
+/// `hash_aes_1rx4` of `scratchpad` and `fill_aes_1rx4_u64` with `fill_state` into the same
+/// memory in one pass (each block is hashed before it is overwritten). Returns the hash,
+/// `fill_state` is updated like the result of `fill_aes_1rx4_u64`.
+#[allow(overflowing_literals)]
+pub fn hash_and_fill_aes_1rx4(scratchpad: &mut [u64], fill_state: &mut [m128i; 4]) -> [m128i; 4] {
+    debug_assert!(scratchpad.len().is_multiple_of(64));
+
+    let mut hash_state0 = m128i::from_i32(0xd7983aad, 0xcc82db47, 0x9fa856de, 0x92b52c0d);
+    let mut hash_state1 = m128i::from_i32(0xace78057, 0xf59e125a, 0x15c7b798, 0x338d996e);
+    let mut hash_state2 = m128i::from_i32(0xe8a07ce4, 0x5079506b, 0xae62c7d0, 0x6a770017);
+    let mut hash_state3 = m128i::from_i32(0x7e994948, 0x79a10005, 0x07ad828d, 0x630a240c);
+
+    let (key0, key1, key2, key3) = keys_1rx4();
+    let [mut fill_state0, mut fill_state1, mut fill_state2, mut fill_state3] = *fill_state;
+
+    for block in scratchpad.chunks_exact_mut(8) {
+        hash_state0 = hash_state0.aesenc(m128i::from_u64(block[1], block[0]));
+        hash_state1 = hash_state1.aesdec(m128i::from_u64(block[3], block[2]));
+        hash_state2 = hash_state2.aesenc(m128i::from_u64(block[5], block[4]));
+        hash_state3 = hash_state3.aesdec(m128i::from_u64(block[7], block[6]));
+
+        fill_state0 = fill_state0.aesdec(key0);
+        fill_state1 = fill_state1.aesenc(key1);
+        fill_state2 = fill_state2.aesdec(key2);
+        fill_state3 = fill_state3.aesenc(key3);
+        for (i, state) in [fill_state0, fill_state1, fill_state2, fill_state3]
+            .iter()
+            .enumerate()
+        {
+            let (hi, lo) = state.as_i64();
+            block[i * 2] = lo as u64;
+            block[i * 2 + 1] = hi as u64;
+        }
+    }
+    *fill_state = [fill_state0, fill_state1, fill_state2, fill_state3];
+
+    let x_key_0 = m128i::from_i32(0x06890201, 0x90dc56bf, 0x8b24949f, 0xf6fa8389);
+    let x_key_1 = m128i::from_i32(0xed18f99b, 0xee1043c6, 0x51f4e03c, 0x61b263d1);
+
+    hash_state0 = hash_state0.aesenc(x_key_0);
+    hash_state1 = hash_state1.aesdec(x_key_0);
+    hash_state2 = hash_state2.aesenc(x_key_0);
+    hash_state3 = hash_state3.aesdec(x_key_0);
+
+    hash_state0 = hash_state0.aesenc(x_key_1);
+    hash_state1 = hash_state1.aesdec(x_key_1);
+    hash_state2 = hash_state2.aesenc(x_key_1);
+    hash_state3 = hash_state3.aesdec(x_key_1);
+
+    [hash_state0, hash_state1, hash_state2, hash_state3]
+}
+
 fn fill_aes_1rx4_m128i(input: &[m128i; 4], into: &mut Vec<m128i>) -> [m128i; 4] {
     let (key0, key1, key2, key3) = keys_1rx4();
     let mut state0 = input[0];
@@ -111,18 +164,20 @@ pub fn gen_program_aes_1rx4(input: &[m128i; 4], output_size: usize) -> (Vec<m128
     (result, new_seed)
 }
 
-#[allow(overflowing_literals)]
 pub fn gen_program_aes_4rx4(input: &[m128i; 4], output_size: usize) -> Vec<m128i> {
+    gen_program_aes_4rx4_with_keys(input, output_size, &RX_0.program_keys)
+}
+
+/// `gen_program_aes_4rx4` with the generator keys of a variant (`RandomxParams::program_keys`).
+pub fn gen_program_aes_4rx4_with_keys(
+    input: &[m128i; 4],
+    output_size: usize,
+    keys: &[[u32; 4]; 8],
+) -> Vec<m128i> {
     debug_assert!(output_size % 4 == 0);
     let mut result = Vec::with_capacity(output_size);
-    let key0 = m128i::from_i32(0x99e5d23f, 0x2f546d2b, 0xd1833ddb, 0x6421aadd);
-    let key1 = m128i::from_i32(0xa5dfcde5, 0x06f79d53, 0xb6913f55, 0xb20e3450);
-    let key2 = m128i::from_i32(0x171c02bf, 0x0aa4679f, 0x515e7baf, 0x5c3ed904);
-    let key3 = m128i::from_i32(0xd8ded291, 0xcd673785, 0xe78f5d08, 0x85623763);
-    let key4 = m128i::from_i32(0x229effb4, 0x3d518b6d, 0xe3d6a7a6, 0xb5826f73);
-    let key5 = m128i::from_i32(0xb272b7d2, 0xe9024d4e, 0x9c10b3d9, 0xc7566bf3);
-    let key6 = m128i::from_i32(0xf63befa7, 0x2ba9660a, 0xf765a38b, 0xf273c9e7);
-    let key7 = m128i::from_i32(0xc0b0762d, 0x0c06d1fd, 0x915839de, 0x7a7cd609);
+    let [key0, key1, key2, key3, key4, key5, key6, key7] =
+        keys.map(|k| m128i::from_i32(k[0] as i32, k[1] as i32, k[2] as i32, k[3] as i32));
 
     let mut state0 = input[0];
     let mut state1 = input[1];
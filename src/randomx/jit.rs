@@ -0,0 +1,803 @@
+extern crate libc;
+
+use super::common::randomx_reciprocal;
+use super::params::{RandomxParams, RX_0};
+use super::program::{Instr, Mode, Opcode, Program, Store, MAX_FLOAT_REG, MAX_REG};
+use super::rounding::MXCSR_DEFAULT;
+use super::superscalar::{ScInstr, ScOpcode, ScProgram};
+use super::vm::{is_zero_or_power_of_2, Register, VmConfig, DYNAMIC_MANTISSA_MASK};
+use std::ptr;
+
+//Register mapping of the compiled code (System V calling convention)
+//  rdi         = pointer to the Register file
+//  rsi         = pointer to the scratchpad
+//  rax,rcx,rdx = temporaries
+//  r8-r15      = r0-r7
+//  xmm0-3      = f0-3
+//  xmm4-7      = e0-3
+//  xmm8-11     = a0-3
+//  xmm12       = temporary
+//  xmm13       = mantissa mask (fdiv_m)
+//  xmm14       = exponent mask (fdiv_m)
+//  xmm15       = scale mask (fscal_r)
+
+const RAX: u8 = 0;
+const RCX: u8 = 1;
+const RDX: u8 = 2;
+const RBX: u8 = 3;
+const RSP: u8 = 4;
+const RSI: u8 = 6;
+const RDI: u8 = 7;
+
+const XMM_TMP: u8 = 12;
+const XMM_MANTISSA_MASK: u8 = 13;
+const XMM_EXPONENT_MASK: u8 = 14;
+const XMM_SCALE_MASK: u8 = 15;
+
+const REG_F_OFFSET: i32 = 64;
+const REG_E_OFFSET: i32 = 128;
+const REG_A_OFFSET: i32 = 192;
+
+const SCALE_MASK: u64 = 0x80F0000000000000;
+
+const CONST_MANTISSA_OFFSET: usize = 0;
+const CONST_EXPONENT_OFFSET: usize = 16;
+const CONST_SCALE_OFFSET: usize = 32;
+const CODE_OFFSET: usize = 64;
+
+//256 instructions with at most ~30 bytes each + prologue/epilogue
+const CODE_BUFFER_SIZE: usize = 64 * 1024;
+
+#[cfg(target_arch = "x86_64")]
+type JitFn = unsafe extern "sysv64" fn(*mut Register, *mut u64);
+
+#[cfg(target_arch = "x86_64")]
+type SuperscalarFn = unsafe extern "sysv64" fn(*mut u64, *const u64, u64);
+
+/// Writeable/executable memory for the generated code. The memory is
+/// either writeable or executable, never both at the same time.
+struct ExecutableBuffer {
+    ptr: *mut u8,
+    size: usize,
+}
+
+impl ExecutableBuffer {
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    fn new(size: usize) -> Option<ExecutableBuffer> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        Some(ExecutableBuffer {
+            ptr: ptr as *mut u8,
+            size,
+        })
+    }
+
+    #[cfg(not(all(unix, target_arch = "x86_64")))]
+    fn new(_size: usize) -> Option<ExecutableBuffer> {
+        None
+    }
+
+    #[cfg(unix)]
+    fn protect(&mut self, executable: bool) -> bool {
+        let prot = if executable {
+            libc::PROT_READ | libc::PROT_EXEC
+        } else {
+            libc::PROT_READ | libc::PROT_WRITE
+        };
+        unsafe { libc::mprotect(self.ptr as *mut libc::c_void, self.size, prot) == 0 }
+    }
+
+    #[cfg(not(unix))]
+    fn protect(&mut self, _executable: bool) -> bool {
+        false
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ExecutableBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.size);
+        }
+    }
+}
+
+// The buffer is owned exclusively by one JitProgram and only mutated through &mut
+unsafe impl Send for ExecutableBuffer {}
+
+/// A RandomX program compiled to x86-64 machine code. The compiled code
+/// executes the program body once (one of the `program_iterations`),
+/// the scratchpad/dataset handling around it is done by the Vm.
+pub struct JitProgram {
+    buffer: ExecutableBuffer,
+    code: Vec<u8>,
+    instr_offsets: Vec<usize>,
+    /// of the last compiled program, the scratchpad masks and jump condition are immediates
+    params: RandomxParams,
+}
+
+impl JitProgram {
+    /// Returns None if no executable memory is available on this platform.
+    pub fn new() -> Option<JitProgram> {
+        let buffer = ExecutableBuffer::new(CODE_BUFFER_SIZE)?;
+        Some(JitProgram {
+            buffer,
+            code: Vec::with_capacity(CODE_BUFFER_SIZE),
+            instr_offsets: Vec::with_capacity(256),
+            params: RX_0,
+        })
+    }
+
+    /// Compiles the program. Has to be called after `Vm::init_vm` since the
+    /// exponent mask of the program is compiled into the code.
+    pub fn compile(&mut self, prog: &Program, config: &VmConfig, params: &RandomxParams) {
+        self.params = *params;
+        self.code.clear();
+        self.instr_offsets.clear();
+
+        emit_u64(&mut self.code, DYNAMIC_MANTISSA_MASK);
+        emit_u64(&mut self.code, DYNAMIC_MANTISSA_MASK);
+        emit_u64(&mut self.code, config.e_mask[0]);
+        emit_u64(&mut self.code, config.e_mask[1]);
+        emit_u64(&mut self.code, SCALE_MASK);
+        emit_u64(&mut self.code, SCALE_MASK);
+        self.code.resize(CODE_OFFSET, 0xCC);
+
+        self.emit_prologue();
+        for instr in &prog.program {
+            self.instr_offsets.push(self.code.len());
+            self.emit_instr(instr);
+        }
+        self.emit_epilogue();
+
+        assert!(
+            self.code.len() <= self.buffer.size,
+            "jit code buffer too small"
+        );
+        assert!(self.buffer.protect(false), "jit buffer not writeable");
+        unsafe {
+            ptr::copy_nonoverlapping(self.code.as_ptr(), self.buffer.ptr, self.code.len());
+        }
+        assert!(self.buffer.protect(true), "jit buffer not executable");
+    }
+
+    /// Executes the compiled program body once on the given register file and scratchpad.
+    #[cfg(target_arch = "x86_64")]
+    pub fn execute(&self, reg: &mut Register, scratchpad: &mut [u64]) {
+        unsafe {
+            let f: JitFn = std::mem::transmute(self.buffer.ptr.add(CODE_OFFSET));
+            f(reg, scratchpad.as_mut_ptr());
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn execute(&self, _reg: &mut Register, _scratchpad: &mut [u64]) {
+        unreachable!("no jit on this target")
+    }
+
+    fn emit_prologue(&mut self) {
+        let c = &mut self.code;
+        for reg in [12, 13, 14, 15] {
+            push(c, reg);
+        }
+        //keep the stack 16 byte aligned and reserve a slot for ldmxcsr
+        c.extend_from_slice(&[0x48, 0x83, 0xEC, 0x08]); //sub rsp, 8
+
+        for i in 0..MAX_REG {
+            mov_r64_mem(c, r_reg(i), RDI, (i * 8) as i32);
+        }
+        for i in 0..MAX_FLOAT_REG {
+            movapd_load(c, f_reg(i), RDI, REG_F_OFFSET + (i * 16) as i32);
+            movapd_load(c, e_reg(i), RDI, REG_E_OFFSET + (i * 16) as i32);
+            movapd_load(c, a_reg(i), RDI, REG_A_OFFSET + (i * 16) as i32);
+        }
+        movupd_load_rip(c, XMM_MANTISSA_MASK, CONST_MANTISSA_OFFSET);
+        movupd_load_rip(c, XMM_EXPONENT_MASK, CONST_EXPONENT_OFFSET);
+        movupd_load_rip(c, XMM_SCALE_MASK, CONST_SCALE_OFFSET);
+    }
+
+    fn emit_epilogue(&mut self) {
+        let c = &mut self.code;
+        for i in 0..MAX_REG {
+            mov_mem_r64(c, RDI, (i * 8) as i32, r_reg(i));
+        }
+        for i in 0..MAX_FLOAT_REG {
+            movapd_store(c, RDI, REG_F_OFFSET + (i * 16) as i32, f_reg(i));
+            movapd_store(c, RDI, REG_E_OFFSET + (i * 16) as i32, e_reg(i));
+        }
+        c.extend_from_slice(&[0x48, 0x83, 0xC4, 0x08]); //add rsp, 8
+        for reg in [15, 14, 13, 12] {
+            pop(c, reg);
+        }
+        c.push(0xC3); //ret
+    }
+
+    fn emit_instr(&mut self, instr: &Instr) {
+        match instr.op {
+            Opcode::NOP => {}
+            Opcode::IADD_RS => {
+                let dst = store_r(&instr.dst);
+                let src = store_r(&instr.src);
+                let shift = shift_mode(instr);
+                lea_scaled(&mut self.code, dst, src, shift, instr.imm);
+            }
+            Opcode::IADD_M => self.emit_r_mem(instr, &[0x03]),
+            Opcode::ISUB_R => self.emit_r_r_or_imm(instr, 0x29, 5),
+            Opcode::ISUB_M => self.emit_r_mem(instr, &[0x2B]),
+            Opcode::IMUL_R => {
+                let dst = store_r(&instr.dst);
+                if instr.src == Store::NONE {
+                    //imul dst, dst, imm32
+                    rex_rr(&mut self.code, true, dst, dst);
+                    self.code.push(0x69);
+                    self.code.push(modrm(3, dst, dst));
+                    emit_i32(&mut self.code, instr.imm.unwrap());
+                } else {
+                    imul_r64_r64(&mut self.code, dst, store_r(&instr.src));
+                }
+            }
+            Opcode::IMUL_M => self.emit_r_mem(instr, &[0x0F, 0xAF]),
+            Opcode::IMULH_R | Opcode::ISMULH_R => {
+                let dst = store_r(&instr.dst);
+                let src = store_r(&instr.src);
+                let ext = if instr.op == Opcode::IMULH_R { 4 } else { 5 };
+                mov_r64_r64(&mut self.code, RAX, dst);
+                rex_rr(&mut self.code, true, 0, src);
+                self.code.push(0xF7);
+                self.code.push(modrm(3, ext, src));
+                mov_r64_r64(&mut self.code, dst, RDX);
+            }
+            Opcode::IMULH_M | Opcode::ISMULH_M => {
+                let dst = store_r(&instr.dst);
+                let ext = if instr.op == Opcode::IMULH_M { 4 } else { 5 };
+                self.emit_src_address(instr, RCX);
+                mov_r64_r64(&mut self.code, RAX, dst);
+                //(i)mul qword [rsi+rcx]
+                self.code
+                    .extend_from_slice(&[0x48, 0xF7, modrm(0, ext, 4), sib(0, RCX, RSI)]);
+                mov_r64_r64(&mut self.code, dst, RDX);
+            }
+            Opcode::IMUL_RCP => {
+                let imm = instr.imm.unwrap() as u64;
+                if !is_zero_or_power_of_2(imm) {
+                    let dst = store_r(&instr.dst);
+                    mov_r64_imm64(
+                        &mut self.code,
+                        RAX,
+                        randomx_reciprocal(imm & 0x00000000FFFFFFFF),
+                    );
+                    imul_r64_r64(&mut self.code, dst, RAX);
+                }
+            }
+            Opcode::INEG_R => {
+                let dst = store_r(&instr.dst);
+                rex_rr(&mut self.code, true, 0, dst);
+                self.code.push(0xF7);
+                self.code.push(modrm(3, 3, dst));
+            }
+            Opcode::IXOR_R => self.emit_r_r_or_imm(instr, 0x31, 6),
+            Opcode::IXOR_M => self.emit_r_mem(instr, &[0x33]),
+            Opcode::IROR_R => self.emit_rotate(instr, 1),
+            Opcode::IROL_R => self.emit_rotate(instr, 0),
+            Opcode::ISWAP_R => {
+                let dst = store_r(&instr.dst);
+                let src = store_r(&instr.src);
+                rex_rr(&mut self.code, true, src, dst);
+                self.code.push(0x87);
+                self.code.push(modrm(3, src, dst));
+            }
+            Opcode::FSWAP_R => {
+                let dst = store_xmm(&instr.dst);
+                sse_rr(&mut self.code, 0x66, 0xC6, dst, dst);
+                self.code.push(1);
+            }
+            Opcode::FADD_R => self.emit_float_rr(instr, 0x58),
+            Opcode::FSUB_R => self.emit_float_rr(instr, 0x5C),
+            Opcode::FMUL_R => self.emit_float_rr(instr, 0x59),
+            Opcode::FADD_M => self.emit_float_mem(instr, 0x58, false),
+            Opcode::FSUB_M => self.emit_float_mem(instr, 0x5C, false),
+            Opcode::FDIV_M => self.emit_float_mem(instr, 0x5E, true),
+            Opcode::FSCAL_R => {
+                let dst = store_xmm(&instr.dst);
+                sse_rr(&mut self.code, 0x66, 0x57, dst, XMM_SCALE_MASK);
+            }
+            Opcode::FSQRT_R => {
+                let dst = store_xmm(&instr.dst);
+                sse_rr(&mut self.code, 0x66, 0x51, dst, dst);
+            }
+            Opcode::CBRANCH => self.emit_cbranch(instr),
+            Opcode::CFROUND => {
+                let src = store_r(&instr.src);
+                let c = &mut self.code;
+                mov_r64_r64(c, RAX, src);
+                c.extend_from_slice(&[0x48, 0xC1, modrm(3, 1, RAX), instr.imm.unwrap() as u8]); //ror rax, imm8
+                c.extend_from_slice(&[0x83, 0xE0, 0x03]); //and eax, 3
+                c.extend_from_slice(&[0xC1, 0xE0, 0x0D]); //shl eax, 13
+                c.push(0x0D); //or eax, imm32
+                emit_u32(c, MXCSR_DEFAULT);
+                c.extend_from_slice(&[0x89, modrm(0, RAX, 4), sib(0, RSP, RSP)]); //mov [rsp], eax
+                c.extend_from_slice(&[0x0F, 0xAE, modrm(0, 2, 4), sib(0, RSP, RSP)]);
+                //ldmxcsr [rsp]
+            }
+            Opcode::ISTORE => {
+                let src = store_r(&instr.src);
+                let (addr_reg, mask) = match &instr.dst {
+                    Store::L1(r) => (store_r(r), self.params.scratchpad_l1_mask()),
+                    Store::L2(r) => (store_r(r), self.params.scratchpad_l2_mask()),
+                    Store::L3(r) => (store_r(r), self.params.scratchpad_l3_mask()),
+                    _ => panic!("illegal istore destination"),
+                };
+                lea_r32_disp(&mut self.code, RAX, addr_reg, instr.imm.unwrap());
+                and_r32_imm(&mut self.code, RAX, mask as u32);
+                //mov [rsi+rax], src
+                rex_rr(&mut self.code, true, src, 0);
+                self.code.push(0x89);
+                self.code.push(modrm(0, src, 4));
+                self.code.push(sib(0, RAX, RSI));
+            }
+        }
+    }
+
+    /// Computes the scratchpad byte address of the source operand into `tmp` (32 bit).
+    fn emit_src_address(&mut self, instr: &Instr, tmp: u8) {
+        let imm = instr.imm.unwrap();
+        match &instr.src {
+            Store::L1(r) => {
+                lea_r32_disp(&mut self.code, tmp, store_r(r), imm);
+                and_r32_imm(&mut self.code, tmp, self.params.scratchpad_l1_mask() as u32);
+            }
+            Store::L2(r) => {
+                lea_r32_disp(&mut self.code, tmp, store_r(r), imm);
+                and_r32_imm(&mut self.code, tmp, self.params.scratchpad_l2_mask() as u32);
+            }
+            Store::L3(_) => {
+                //mov tmp32, imm32
+                self.code.push(0xB8 + tmp);
+                emit_u32(
+                    &mut self.code,
+                    (imm as u32) & (self.params.scratchpad_l3_mask() as u32),
+                );
+            }
+            _ => panic!("illegal read from scratchpad"),
+        }
+    }
+
+    /// op dst, qword [rsi+rax]
+    fn emit_r_mem(&mut self, instr: &Instr, opcode: &[u8]) {
+        let dst = store_r(&instr.dst);
+        self.emit_src_address(instr, RAX);
+        rex_rr(&mut self.code, true, dst, 0);
+        self.code.extend_from_slice(opcode);
+        self.code.push(modrm(0, dst, 4));
+        self.code.push(sib(0, RAX, RSI));
+    }
+
+    /// op dst, src (opcode in r/m, reg form) or op dst, imm32 (0x81 /ext)
+    fn emit_r_r_or_imm(&mut self, instr: &Instr, opcode: u8, ext: u8) {
+        let dst = store_r(&instr.dst);
+        if instr.src == Store::NONE {
+            rex_rr(&mut self.code, true, 0, dst);
+            self.code.push(0x81);
+            self.code.push(modrm(3, ext, dst));
+            emit_i32(&mut self.code, instr.imm.unwrap());
+        } else {
+            let src = store_r(&instr.src);
+            rex_rr(&mut self.code, true, src, dst);
+            self.code.push(opcode);
+            self.code.push(modrm(3, src, dst));
+        }
+    }
+
+    fn emit_rotate(&mut self, instr: &Instr, ext: u8) {
+        let dst = store_r(&instr.dst);
+        if instr.src == Store::NONE {
+            rex_rr(&mut self.code, true, 0, dst);
+            self.code.push(0xC1);
+            self.code.push(modrm(3, ext, dst));
+            self.code.push(instr.imm.unwrap() as u8);
+        } else {
+            mov_r64_r64(&mut self.code, RCX, store_r(&instr.src));
+            rex_rr(&mut self.code, true, 0, dst);
+            self.code.push(0xD3);
+            self.code.push(modrm(3, ext, dst));
+        }
+    }
+
+    fn emit_float_rr(&mut self, instr: &Instr, opcode: u8) {
+        let dst = store_xmm(&instr.dst);
+        let src = store_xmm(&instr.src);
+        sse_rr(&mut self.code, 0x66, opcode, dst, src);
+    }
+
+    fn emit_float_mem(&mut self, instr: &Instr, opcode: u8, mask: bool) {
+        let dst = store_xmm(&instr.dst);
+        self.emit_src_address(instr, RAX);
+        //cvtdq2pd xmm_tmp, qword [rsi+rax]
+        self.code.push(0xF3);
+        rex_rr(&mut self.code, false, XMM_TMP, 0);
+        self.code
+            .extend_from_slice(&[0x0F, 0xE6, modrm(0, XMM_TMP, 4), sib(0, RAX, RSI)]);
+        if mask {
+            sse_rr(&mut self.code, 0x66, 0x54, XMM_TMP, XMM_MANTISSA_MASK); //andpd
+            sse_rr(&mut self.code, 0x66, 0x56, XMM_TMP, XMM_EXPONENT_MASK); //orpd
+        }
+        sse_rr(&mut self.code, 0x66, opcode, dst, XMM_TMP);
+    }
+
+    fn emit_cbranch(&mut self, instr: &Instr) {
+        let dst = store_r(&instr.dst);
+        let cond = match instr.mode {
+            Mode::Cond(x) => x as u64,
+            _ => panic!("illegal cond mode {}", instr.mode),
+        };
+        let shift = cond + self.params.jump_offset;
+        let mut imm = (instr.imm.unwrap() as u64) | (1 << shift);
+        imm &= !(1 << (shift - 1));
+
+        //add dst, imm32
+        rex_rr(&mut self.code, true, 0, dst);
+        self.code.push(0x81);
+        self.code.push(modrm(3, 0, dst));
+        emit_u32(&mut self.code, imm as u32);
+        //test dst, imm32
+        rex_rr(&mut self.code, true, 0, dst);
+        self.code.push(0xF7);
+        self.code.push(modrm(3, 0, dst));
+        emit_u32(
+            &mut self.code,
+            (self.params.condition_mask() << shift) as u32,
+        );
+        //jz target
+        let target_ix = (instr.target.unwrap() + 1) as usize;
+        let target = self.instr_offsets[target_ix] as i64;
+        let rel = target - (self.code.len() as i64 + 6);
+        self.code.extend_from_slice(&[0x0F, 0x84]);
+        emit_i32(&mut self.code, rel as i32);
+    }
+}
+
+//Register mapping of the compiled superscalar programs
+//  rdi     = pointer to the 8 registers
+//  rsi     = pointer to the cache
+//  rbx     = register value selecting the next cache line (starts with the item number)
+//  rax,rdx = temporaries
+//  r8-r15  = r0-r7
+
+/// The superscalar programs of a seed memory compiled to x86-64 machine code
+/// (`generateSuperscalarHash` of the reference implementation). One call runs
+/// all programs with the cache mixing in between, everything of a dataset item
+/// except the register initialisation.
+pub struct SuperscalarJit {
+    buffer: ExecutableBuffer,
+    cache_lines: u64,
+}
+
+// The code is only written in compile, the compiled code does not modify itself
+unsafe impl Sync for SuperscalarJit {}
+
+impl SuperscalarJit {
+    /// Compiles `programs` for a cache of `cache_lines` (a power of 2) 64 byte lines.
+    /// Returns None if no executable memory is available on this platform.
+    pub fn compile(programs: &[ScProgram], cache_lines: u64) -> Option<SuperscalarJit> {
+        assert!(cache_lines.is_power_of_two() && cache_lines <= 1 << 26);
+        let mut code = Vec::with_capacity(16 * 1024);
+        let c = &mut code;
+        for reg in [RBX, 12, 13, 14, 15] {
+            push(c, reg);
+        }
+        mov_r64_r64(c, RBX, RDX);
+        for i in 0..MAX_REG {
+            mov_r64_mem(c, r_reg(i), RDI, (i * 8) as i32);
+        }
+
+        for prog in programs {
+            for instr in &prog.prog {
+                emit_sc_instr(c, instr);
+            }
+            //rax = (rbx & (cache_lines - 1)) * 64
+            mov_r64_r64(c, RAX, RBX);
+            and_r32_imm(c, RAX, (cache_lines - 1) as u32);
+            c.extend_from_slice(&[0x48, 0xC1, modrm(3, 4, RAX), 6]); //shl rax, 6
+            for i in 0..MAX_REG {
+                //xor r, qword [rsi+rax+8*i]
+                c.push(rex(true, r_reg(i), RAX, RSI));
+                c.extend_from_slice(&[0x33, modrm(1, r_reg(i), 4), sib(0, RAX, RSI)]);
+                c.push((i * 8) as u8);
+            }
+            mov_r64_r64(c, RBX, r_reg(prog.address_reg));
+        }
+
+        for i in 0..MAX_REG {
+            mov_mem_r64(c, RDI, (i * 8) as i32, r_reg(i));
+        }
+        for reg in [15, 14, 13, 12, RBX] {
+            pop(c, reg);
+        }
+        c.push(0xC3); //ret
+
+        let mut buffer = ExecutableBuffer::new(code.len())?;
+        unsafe {
+            ptr::copy_nonoverlapping(code.as_ptr(), buffer.ptr, code.len());
+        }
+        if !buffer.protect(true) {
+            return None;
+        }
+        Some(SuperscalarJit {
+            buffer,
+            cache_lines,
+        })
+    }
+
+    /// Runs the programs on `ds` (initialised for `item_num`) with the cache mixing.
+    #[cfg(target_arch = "x86_64")]
+    pub fn execute(&self, ds: &mut [u64; 8], cache: &[u64], item_num: u64) {
+        assert!(
+            cache.len() as u64 >= self.cache_lines * 8,
+            "cache too small"
+        );
+        unsafe {
+            let f: SuperscalarFn = std::mem::transmute(self.buffer.ptr);
+            f(ds.as_mut_ptr(), cache.as_ptr(), item_num);
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn execute(&self, _ds: &mut [u64; 8], _cache: &[u64], _item_num: u64) {
+        unreachable!("no jit on this target")
+    }
+}
+
+fn emit_sc_instr(c: &mut Vec<u8>, instr: &ScInstr) {
+    let dst = r_reg(instr.dst as usize);
+    match instr.info.op {
+        ScOpcode::ISUB_R | ScOpcode::IXOR_R => {
+            let src = r_reg(instr.src as usize);
+            let opcode = if instr.info.op == ScOpcode::ISUB_R {
+                0x29
+            } else {
+                0x31
+            };
+            rex_rr(c, true, src, dst);
+            c.push(opcode);
+            c.push(modrm(3, src, dst));
+        }
+        ScOpcode::IADD_RS => {
+            let src = r_reg(instr.src as usize);
+            lea_scaled(c, dst, src, instr.mod_shift() as u8, None);
+        }
+        ScOpcode::IMUL_R => imul_r64_r64(c, dst, r_reg(instr.src as usize)),
+        ScOpcode::IROR_C => {
+            rex_rr(c, true, 0, dst);
+            c.extend_from_slice(&[0xC1, modrm(3, 1, dst), instr.imm32 as u8]);
+        }
+        ScOpcode::IADD_C7 | ScOpcode::IADD_C8 | ScOpcode::IADD_C9 => {
+            //add dst, imm32 (sign extended like u64_from_u32_imm)
+            rex_rr(c, true, 0, dst);
+            c.extend_from_slice(&[0x81, modrm(3, 0, dst)]);
+            emit_u32(c, instr.imm32);
+        }
+        ScOpcode::IXOR_C7 | ScOpcode::IXOR_C8 | ScOpcode::IXOR_C9 => {
+            rex_rr(c, true, 0, dst);
+            c.extend_from_slice(&[0x81, modrm(3, 6, dst)]);
+            emit_u32(c, instr.imm32);
+        }
+        ScOpcode::IMULH_R | ScOpcode::ISMULH_R => {
+            let src = r_reg(instr.src as usize);
+            let ext = if instr.info.op == ScOpcode::IMULH_R {
+                4
+            } else {
+                5
+            };
+            mov_r64_r64(c, RAX, dst);
+            rex_rr(c, true, 0, src);
+            c.extend_from_slice(&[0xF7, modrm(3, ext, src)]);
+            mov_r64_r64(c, dst, RDX);
+        }
+        ScOpcode::IMUL_RCP => {
+            mov_r64_imm64(c, RAX, instr.reciprocal);
+            imul_r64_r64(c, dst, RAX);
+        }
+        ScOpcode::INVALID | ScOpcode::COUNT => panic!("invalid opcode {}", instr.info.op),
+    }
+}
+
+fn r_reg(i: usize) -> u8 {
+    8 + i as u8
+}
+
+fn f_reg(i: usize) -> u8 {
+    i as u8
+}
+
+fn e_reg(i: usize) -> u8 {
+    4 + i as u8
+}
+
+fn a_reg(i: usize) -> u8 {
+    8 + i as u8
+}
+
+fn store_r(store: &Store) -> u8 {
+    match store {
+        Store::R(i) => r_reg(*i),
+        _ => panic!("illegal read from register r"),
+    }
+}
+
+fn store_xmm(store: &Store) -> u8 {
+    match store {
+        Store::F(i) => f_reg(*i),
+        Store::E(i) => e_reg(*i),
+        Store::A(i) => a_reg(*i),
+        _ => panic!("illegal read from float register"),
+    }
+}
+
+fn shift_mode(instr: &Instr) -> u8 {
+    match instr.mode {
+        Mode::Shft(x) => x,
+        _ => panic!("illegal shift mode {}", instr.mode),
+    }
+}
+
+//x86 encoding helper
+
+fn modrm(md: u8, reg: u8, rm: u8) -> u8 {
+    (md << 6) | ((reg & 7) << 3) | (rm & 7)
+}
+
+fn sib(scale: u8, index: u8, base: u8) -> u8 {
+    (scale << 6) | ((index & 7) << 3) | (base & 7)
+}
+
+fn rex(w: bool, reg: u8, index: u8, base: u8) -> u8 {
+    0x40 | ((w as u8) << 3) | ((reg >> 3) << 2) | ((index >> 3) << 1) | (base >> 3)
+}
+
+/// Emits a REX prefix for a reg/rm encoding, if needed.
+fn rex_rr(c: &mut Vec<u8>, w: bool, reg: u8, rm: u8) {
+    let prefix = rex(w, reg, 0, rm);
+    if prefix != 0x40 {
+        c.push(prefix);
+    }
+}
+
+fn emit_u32(c: &mut Vec<u8>, v: u32) {
+    c.extend_from_slice(&v.to_le_bytes());
+}
+
+fn emit_i32(c: &mut Vec<u8>, v: i32) {
+    c.extend_from_slice(&v.to_le_bytes());
+}
+
+fn emit_u64(c: &mut Vec<u8>, v: u64) {
+    c.extend_from_slice(&v.to_le_bytes());
+}
+
+fn push(c: &mut Vec<u8>, reg: u8) {
+    rex_rr(c, false, 0, reg);
+    c.push(0x50 + (reg & 7));
+}
+
+fn pop(c: &mut Vec<u8>, reg: u8) {
+    rex_rr(c, false, 0, reg);
+    c.push(0x58 + (reg & 7));
+}
+
+/// mov dst, src (64 bit)
+fn mov_r64_r64(c: &mut Vec<u8>, dst: u8, src: u8) {
+    rex_rr(c, true, src, dst);
+    c.push(0x89);
+    c.push(modrm(3, src, dst));
+}
+
+fn mov_r64_imm64(c: &mut Vec<u8>, dst: u8, imm: u64) {
+    c.push(rex(true, 0, 0, dst));
+    c.push(0xB8 + (dst & 7));
+    emit_u64(c, imm);
+}
+
+/// imul dst, src (64 bit)
+fn imul_r64_r64(c: &mut Vec<u8>, dst: u8, src: u8) {
+    rex_rr(c, true, dst, src);
+    c.extend_from_slice(&[0x0F, 0xAF, modrm(3, dst, src)]);
+}
+
+/// mov dst, qword [base+disp32], base must not be rsp/r12
+fn mov_r64_mem(c: &mut Vec<u8>, dst: u8, base: u8, disp: i32) {
+    rex_rr(c, true, dst, base);
+    c.push(0x8B);
+    c.push(modrm(2, dst, base));
+    emit_i32(c, disp);
+}
+
+/// mov qword [base+disp32], src, base must not be rsp/r12
+fn mov_mem_r64(c: &mut Vec<u8>, base: u8, disp: i32, src: u8) {
+    rex_rr(c, true, src, base);
+    c.push(0x89);
+    c.push(modrm(2, src, base));
+    emit_i32(c, disp);
+}
+
+/// lea dst32, [base+disp32]
+fn lea_r32_disp(c: &mut Vec<u8>, dst: u8, base: u8, disp: i32) {
+    rex_rr(c, false, dst, base);
+    c.push(0x8D);
+    c.push(modrm(2, dst, 4));
+    c.push(sib(0, RSP, base)); //no index
+    emit_i32(c, disp);
+}
+
+/// lea dst, [dst+src*(1<<shift)(+disp32)]
+fn lea_scaled(c: &mut Vec<u8>, dst: u8, src: u8, shift: u8, disp: Option<i32>) {
+    c.push(rex(true, dst, src, dst));
+    c.push(0x8D);
+    match disp {
+        Some(d) => {
+            c.push(modrm(2, dst, 4));
+            c.push(sib(shift, src, dst));
+            emit_i32(c, d);
+        }
+        None if dst & 7 == 5 => {
+            //rbp/r13 as base always needs a displacement
+            c.push(modrm(1, dst, 4));
+            c.push(sib(shift, src, dst));
+            c.push(0);
+        }
+        None => {
+            c.push(modrm(0, dst, 4));
+            c.push(sib(shift, src, dst));
+        }
+    }
+}
+
+/// and dst32, imm32
+fn and_r32_imm(c: &mut Vec<u8>, dst: u8, imm: u32) {
+    rex_rr(c, false, 0, dst);
+    c.push(0x81);
+    c.push(modrm(3, 4, dst));
+    emit_u32(c, imm);
+}
+
+/// <prefix> op xmm_dst, xmm_src
+fn sse_rr(c: &mut Vec<u8>, prefix: u8, opcode: u8, dst: u8, src: u8) {
+    c.push(prefix);
+    rex_rr(c, false, dst, src);
+    c.extend_from_slice(&[0x0F, opcode, modrm(3, dst, src)]);
+}
+
+/// movapd xmm, [base+disp32]
+fn movapd_load(c: &mut Vec<u8>, dst: u8, base: u8, disp: i32) {
+    c.push(0x66);
+    rex_rr(c, false, dst, base);
+    c.extend_from_slice(&[0x0F, 0x28, modrm(2, dst, base)]);
+    emit_i32(c, disp);
+}
+
+/// movapd [base+disp32], xmm
+fn movapd_store(c: &mut Vec<u8>, base: u8, disp: i32, src: u8) {
+    c.push(0x66);
+    rex_rr(c, false, src, base);
+    c.extend_from_slice(&[0x0F, 0x29, modrm(2, src, base)]);
+    emit_i32(c, disp);
+}
+
+/// movupd xmm, [rip+disp32] with disp32 pointing to the absolute buffer offset
+fn movupd_load_rip(c: &mut Vec<u8>, dst: u8, buffer_offset: usize) {
+    c.push(0x66);
+    rex_rr(c, false, dst, 0);
+    c.extend_from_slice(&[0x0F, 0x10, modrm(0, dst, 5)]);
+    let next_ip = c.len() + 4;
+    emit_i32(c, buffer_offset as i32 - next_ip as i32);
+}
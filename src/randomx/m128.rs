@@ -1,3 +1,4 @@
+use super::soft_aes;
 #[cfg(all(
     any(target_arch = "x86", target_arch = "x86_64"),
     target_feature = "sse2"
@@ -5,8 +6,9 @@
 use std::arch::x86_64::{
     __m128d, __m128i, _mm_add_pd, _mm_aesdec_si128, _mm_aesenc_si128, _mm_and_pd, _mm_cmpeq_epi32,
     _mm_cmpeq_pd, _mm_cvtepi32_pd, _mm_div_pd, _mm_extract_epi64, _mm_movemask_epi8,
-    _mm_movemask_pd, _mm_mul_pd, _mm_or_pd, _mm_set_epi32, _mm_set_epi64x, _mm_set_pd,
-    _mm_shuffle_pd, _mm_sqrt_pd, _mm_store_sd, _mm_storeh_pd, _mm_sub_pd, _mm_xor_pd,
+    _mm_movemask_pd, _mm_mul_pd, _mm_or_pd, _mm_prefetch, _mm_set_epi32, _mm_set_epi64x,
+    _mm_set_pd, _mm_shuffle_pd, _mm_sqrt_pd, _mm_store_sd, _mm_storeh_pd, _mm_sub_pd, _mm_xor_pd,
+    _MM_HINT_NTA,
 };
 use std::convert::TryInto;
 use std::fmt;
@@ -34,10 +36,18 @@ impl m128i {
         unsafe { m128i(_mm_set_epi64x(u1 as i64, u0 as i64)) }
     }
     pub fn aesdec(&self, key: m128i) -> m128i {
-        unsafe { m128i(_mm_aesdec_si128(self.0, key.0)) }
+        if soft_aes::hardware_aes() {
+            unsafe { m128i(_mm_aesdec_si128(self.0, key.0)) }
+        } else {
+            m128i::from_u32_lanes(soft_aes::aesdec(self.as_u32_lanes(), key.as_u32_lanes()))
+        }
     }
     pub fn aesenc(&self, key: m128i) -> m128i {
-        unsafe { m128i(_mm_aesenc_si128(self.0, key.0)) }
+        if soft_aes::hardware_aes() {
+            unsafe { m128i(_mm_aesenc_si128(self.0, key.0)) }
+        } else {
+            m128i::from_u32_lanes(soft_aes::aesenc(self.as_u32_lanes(), key.as_u32_lanes()))
+        }
     }
     pub fn as_i64(&self) -> (i64, i64) {
         unsafe {
@@ -47,6 +57,20 @@ impl m128i {
         }
     }
 
+    fn as_u32_lanes(&self) -> [u32; 4] {
+        let (h, l) = self.as_i64();
+        [l as u32, (l >> 32) as u32, h as u32, (h >> 32) as u32]
+    }
+
+    fn from_u32_lanes(lanes: [u32; 4]) -> m128i {
+        m128i::from_i32(
+            lanes[3] as i32,
+            lanes[2] as i32,
+            lanes[1] as i32,
+            lanes[0] as i32,
+        )
+    }
+
     //_mm_cvtepi32_pd
     pub fn lower_to_m128d(&self) -> m128d {
         unsafe { m128d(_mm_cvtepi32_pd(self.0)) }
@@ -214,3 +238,8 @@ impl std::ops::Div for m128d {
         unsafe { m128d(_mm_div_pd(self.0, rhs.0)) }
     }
 }
+
+//_mm_prefetch with non-temporal hint
+pub fn prefetch(ptr: *const u8) {
+    unsafe { _mm_prefetch(ptr as *const i8, _MM_HINT_NTA) }
+}
@@ -0,0 +1,218 @@
+//! Pure Rust implementation of `m128i`/`m128d` for targets without SSE2
+//! (or with the `portable` feature). Mirrors the API of the intrinsics
+//! based implementation, lane 0 is the low lane.
+
+use super::soft_aes;
+use std::convert::TryInto;
+use std::fmt;
+
+#[allow(nonstandard_style)]
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct m128i(pub [u64; 2]);
+
+impl m128i {
+    pub fn zero() -> m128i {
+        m128i([0, 0])
+    }
+    pub fn from_u8(bytes: &[u8]) -> m128i {
+        debug_assert_eq!(bytes.len(), 16);
+
+        let u0 = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let u1 = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+
+        m128i::from_u64(u1, u0)
+    }
+    pub fn from_i32(i3: i32, i2: i32, i1: i32, i0: i32) -> m128i {
+        m128i::from_u32_lanes([i0 as u32, i1 as u32, i2 as u32, i3 as u32])
+    }
+    pub fn from_u64(u1: u64, u0: u64) -> m128i {
+        m128i([u0, u1])
+    }
+    pub fn aesdec(&self, key: m128i) -> m128i {
+        m128i::from_u32_lanes(soft_aes::aesdec(self.as_u32_lanes(), key.as_u32_lanes()))
+    }
+    pub fn aesenc(&self, key: m128i) -> m128i {
+        m128i::from_u32_lanes(soft_aes::aesenc(self.as_u32_lanes(), key.as_u32_lanes()))
+    }
+    pub fn as_i64(&self) -> (i64, i64) {
+        (self.0[1] as i64, self.0[0] as i64)
+    }
+
+    fn as_u32_lanes(&self) -> [u32; 4] {
+        let [l, h] = self.0;
+        [l as u32, (l >> 32) as u32, h as u32, (h >> 32) as u32]
+    }
+
+    fn from_u32_lanes(lanes: [u32; 4]) -> m128i {
+        m128i([
+            u64::from(lanes[0]) | (u64::from(lanes[1]) << 32),
+            u64::from(lanes[2]) | (u64::from(lanes[3]) << 32),
+        ])
+    }
+
+    //_mm_cvtepi32_pd
+    pub fn lower_to_m128d(&self) -> m128d {
+        let lanes = self.as_u32_lanes();
+        m128d([f64::from(lanes[0] as i32), f64::from(lanes[1] as i32)])
+    }
+
+    pub fn as_m128d(&self) -> m128d {
+        m128d::from_u64(self.0[1], self.0[0])
+    }
+}
+
+impl PartialEq for m128i {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for m128i {}
+
+fn format_m128i(m: &m128i, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (low, high) = m.as_i64();
+    f.write_fmt(format_args!("({:x},{:x})", high, low))
+}
+
+impl fmt::LowerHex for m128i {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_m128i(self, f)
+    }
+}
+
+impl fmt::Debug for m128i {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_m128i(self, f)
+    }
+}
+
+//==== m128d
+
+#[allow(nonstandard_style)]
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct m128d(pub [f64; 2]);
+
+impl m128d {
+    pub fn zero() -> m128d {
+        m128d::from_f64(0.0, 0.0)
+    }
+    pub fn from_u64(h: u64, l: u64) -> m128d {
+        m128d::from_f64(f64::from_bits(h), f64::from_bits(l))
+    }
+    pub fn from_f64(h: f64, l: f64) -> m128d {
+        m128d([l, h])
+    }
+    pub fn as_f64(&self) -> (f64, f64) {
+        (self.0[1], self.0[0])
+    }
+
+    pub fn as_u64(&self) -> (u64, u64) {
+        let (f1, f0) = self.as_f64();
+        (f1.to_bits(), f0.to_bits())
+    }
+
+    //_mm_shuffle_pd(a, b, 1)
+    pub fn shuffle_1(&self, other: &m128d) -> m128d {
+        m128d([self.0[1], other.0[0]])
+    }
+
+    //_mm_sqrt_pd
+    pub fn sqrt(&self) -> m128d {
+        m128d([self.0[0].sqrt(), self.0[1].sqrt()])
+    }
+
+    fn map_bits(self, rhs: m128d, op: fn(u64, u64) -> u64) -> m128d {
+        m128d([
+            f64::from_bits(op(self.0[0].to_bits(), rhs.0[0].to_bits())),
+            f64::from_bits(op(self.0[1].to_bits(), rhs.0[1].to_bits())),
+        ])
+    }
+}
+
+// same semantic as _mm_cmpeq_pd: NaN is never equal, 0.0 == -0.0
+impl PartialEq for m128d {
+    #[allow(clippy::float_cmp)]
+    fn eq(&self, other: &Self) -> bool {
+        self.0[0] == other.0[0] && self.0[1] == other.0[1]
+    }
+}
+impl Eq for m128d {}
+
+impl std::ops::Add for m128d {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        m128d([self.0[0] + other.0[0], self.0[1] + other.0[1]])
+    }
+}
+
+impl std::ops::Sub for m128d {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        m128d([self.0[0] - other.0[0], self.0[1] - other.0[1]])
+    }
+}
+
+fn format_m128d(m: &m128d, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (low, high) = m.as_f64();
+    f.write_fmt(format_args!("({},{})", low, high))
+}
+
+impl fmt::LowerHex for m128d {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (low, high) = self.as_f64();
+        f.write_fmt(format_args!("({:x},{:x})", high.to_bits(), low.to_bits()))
+    }
+}
+
+impl fmt::Debug for m128d {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_m128d(self, f)
+    }
+}
+
+impl std::ops::BitXor for m128d {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        self.map_bits(rhs, |a, b| a ^ b)
+    }
+}
+
+impl std::ops::BitAnd for m128d {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.map_bits(rhs, |a, b| a & b)
+    }
+}
+
+impl std::ops::BitOr for m128d {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.map_bits(rhs, |a, b| a | b)
+    }
+}
+
+impl std::ops::Mul for m128d {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        m128d([self.0[0] * rhs.0[0], self.0[1] * rhs.0[1]])
+    }
+}
+
+impl std::ops::Div for m128d {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        m128d([self.0[0] / rhs.0[0], self.0[1] / rhs.0[1]])
+    }
+}
+
+/// Prefetch hint, no-op without intrinsics.
+pub fn prefetch(_ptr: *const u8) {}
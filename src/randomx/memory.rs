@@ -1,25 +1,44 @@
 extern crate argon2;
+extern crate crossbeam_channel;
 
-use std::arch::x86_64::{_mm_prefetch, _MM_HINT_NTA};
-use std::sync::{Arc, RwLock};
+use std::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+#[cfg(not(unix))]
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::{Deref, DerefMut};
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::ptr::{self, NonNull};
+use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
 use std::time::Instant;
 
 use self::argon2::block::Block;
+use self::crossbeam_channel::{unbounded, Receiver, Sender};
 
 use super::super::byte_string;
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
+use super::dataset_avx2;
+use super::disk_cache::DiskCache;
+use super::jit::SuperscalarJit;
+use super::m128::prefetch;
+use super::params::{RandomxParams, RX_0};
 use super::superscalar::{Blake2Generator, ScProgram};
 
-const RANDOMX_ARGON_LANES: u32 = 1;
-const RANDOMX_ARGON_MEMORY: u32 = 262144;
-const RANDOMX_ARGON_SALT: &[u8; 8] = b"RandomX\x03";
-const RANDOMX_ARGON_ITERATIONS: u32 = 3;
-const RANDOMX_CACHE_ACCESSES: usize = 8;
-
 const ARGON2_SYNC_POINTS: u32 = 4;
 const ARGON_BLOCK_SIZE: u32 = 1024;
 
 pub const CACHE_LINE_SIZE: u64 = 64;
-pub const DATASET_ITEM_COUNT: usize = (2147483648 + 33554368) / 64; //34.078.719
+
+#[cfg(target_os = "linux")]
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+//number of items a thread initialises before reporting progress
+const DATASET_INIT_BATCH: usize = 1 << 16;
 
 const SUPERSCALAR_MUL_0: u64 = 6364136223846793005;
 const SUPERSCALAR_ADD_1: u64 = 9298411001130361340;
@@ -34,6 +53,11 @@ const SUPERSCALAR_ADD_7: u64 = 9549104520008361294;
 pub struct SeedMemory {
     pub blocks: Box<[Block]>,
     pub programs: Vec<ScProgram<'static>>,
+    /// Parameters of the RandomX variant the memory was built for
+    pub params: RandomxParams,
+    /// `programs` compiled, the dataset items are computed with the interpreter if None
+    /// (not compiled or compiling failed)
+    pub jit: Option<SuperscalarJit>,
 }
 
 impl SeedMemory {
@@ -41,53 +65,98 @@ impl SeedMemory {
         SeedMemory {
             blocks: Box::new([]),
             programs: Vec::with_capacity(0),
+            params: RX_0,
+            jit: None,
         }
     }
 
-    /// Creates a new initialised seed memory.
+    /// Creates a new initialised seed memory (rx/0) with compiled superscalar programs.
     pub fn new_initialised(key: &[u8]) -> SeedMemory {
-        let mut mem = argon2::memory::Memory::new(RANDOMX_ARGON_LANES, RANDOMX_ARGON_MEMORY);
-        let context = &create_argon_context(key);
+        SeedMemory::with_params(key, &RX_0, true)
+    }
+
+    /// Creates a new initialised seed memory for the RandomX variant `params`, the
+    /// superscalar programs are compiled if `jit` is set.
+    pub fn with_params(key: &[u8], params: &RandomxParams, jit: bool) -> SeedMemory {
+        let mut mem = argon2::memory::Memory::new(params.argon_lanes, params.argon_memory);
+        let context = &create_argon_context(key, params);
         argon2::core::initialize(context, &mut mem);
         argon2::core::fill_memory_blocks(context, &mut mem);
 
-        let mut programs = Vec::with_capacity(RANDOMX_CACHE_ACCESSES);
+        SeedMemory::from_blocks(key, mem.blocks, params, jit)
+    }
+
+    /// Creates the seed memory from already computed Argon2 `blocks` for `key`.
+    pub fn from_blocks(
+        key: &[u8],
+        blocks: Box<[Block]>,
+        params: &RandomxParams,
+        jit: bool,
+    ) -> SeedMemory {
+        let mut programs = Vec::with_capacity(params.cache_accesses);
         let mut gen = Blake2Generator::new(key, 0);
-        for _ in 0..RANDOMX_CACHE_ACCESSES {
+        for _ in 0..params.cache_accesses {
             programs.push(ScProgram::generate(&mut gen));
         }
 
+        let cache_lines = params.argon_memory as u64 * ARGON_BLOCK_SIZE as u64 / CACHE_LINE_SIZE;
+        let jit = if jit {
+            let compiled = SuperscalarJit::compile(&programs, cache_lines);
+            if compiled.is_none() {
+                warn!("could not compile the superscalar programs, dataset items are interpreted");
+            }
+            compiled
+        } else {
+            None
+        };
+
         SeedMemory {
-            blocks: mem.blocks,
+            blocks,
             programs,
+            params: *params,
+            jit,
+        }
+    }
+
+    /// The Argon2 blocks as one slice
+    pub fn cache(&self) -> &[u64] {
+        assert_eq!(std::mem::size_of::<Block>(), ARGON_BLOCK_SIZE as usize);
+        unsafe {
+            slice::from_raw_parts(
+                self.blocks.as_ptr() as *const u64,
+                self.blocks.len() * ARGON_BLOCK_SIZE as usize / 8,
+            )
         }
     }
 }
 
-fn create_argon_context(key: &[u8]) -> argon2::context::Context {
-    let segment_length = RANDOMX_ARGON_MEMORY / (RANDOMX_ARGON_LANES * ARGON2_SYNC_POINTS);
+fn create_argon_context<'a>(
+    key: &'a [u8],
+    params: &'a RandomxParams,
+) -> argon2::context::Context<'a> {
+    let segment_length = params.argon_memory / (params.argon_lanes * ARGON2_SYNC_POINTS);
     let config = argon2::config::Config {
         ad: &[],
         hash_length: 0,
-        lanes: RANDOMX_ARGON_LANES,
-        mem_cost: RANDOMX_ARGON_MEMORY,
+        lanes: params.argon_lanes,
+        mem_cost: params.argon_memory,
         secret: &[],
-        time_cost: RANDOMX_ARGON_ITERATIONS,
+        time_cost: params.argon_iterations,
         variant: argon2::Variant::Argon2d,
         version: argon2::Version::Version13,
     };
     argon2::context::Context {
         config,
-        memory_blocks: RANDOMX_ARGON_MEMORY,
+        memory_blocks: params.argon_memory,
         pwd: key,
-        salt: RANDOMX_ARGON_SALT,
+        salt: params.argon_salt,
         lane_length: segment_length * ARGON2_SYNC_POINTS,
         segment_length,
     }
 }
 
 fn mix_block_value(seed_mem: &SeedMemory, reg_value: u64, r: usize) -> u64 {
-    let mask = (((RANDOMX_ARGON_MEMORY * ARGON_BLOCK_SIZE) as u64) / CACHE_LINE_SIZE) - 1;
+    let mask = (((seed_mem.params.argon_memory * ARGON_BLOCK_SIZE) as u64) / CACHE_LINE_SIZE) - 1;
     let byte_offset = ((reg_value & mask) * CACHE_LINE_SIZE) + (8 * r as u64);
 
     let block_ix = byte_offset / ARGON_BLOCK_SIZE as u64;
@@ -95,10 +164,9 @@ fn mix_block_value(seed_mem: &SeedMemory, reg_value: u64, r: usize) -> u64 {
     seed_mem.blocks[block_ix as usize][block_v_ix as usize]
 }
 
-pub fn init_dataset_item(seed_mem: &SeedMemory, item_num: u64) -> [u64; 8] {
+/// Registers of the dataset item `item_num` before the superscalar programs run.
+pub fn init_dataset_registers(item_num: u64) -> [u64; 8] {
     let mut ds = [0; 8];
-
-    let mut reg_value = item_num;
     ds[0] = (item_num + 1).wrapping_mul(SUPERSCALAR_MUL_0);
     ds[1] = ds[0] ^ SUPERSCALAR_ADD_1;
     ds[2] = ds[0] ^ SUPERSCALAR_ADD_2;
@@ -107,6 +175,17 @@ pub fn init_dataset_item(seed_mem: &SeedMemory, item_num: u64) -> [u64; 8] {
     ds[5] = ds[0] ^ SUPERSCALAR_ADD_5;
     ds[6] = ds[0] ^ SUPERSCALAR_ADD_6;
     ds[7] = ds[0] ^ SUPERSCALAR_ADD_7;
+    ds
+}
+
+pub fn init_dataset_item(seed_mem: &SeedMemory, item_num: u64) -> [u64; 8] {
+    let mut ds = init_dataset_registers(item_num);
+    let mut reg_value = item_num;
+
+    if let Some(jit) = &seed_mem.jit {
+        jit.execute(&mut ds, seed_mem.cache(), item_num);
+        return ds;
+    }
 
     for prog in &seed_mem.programs {
         prog.execute(&mut ds);
@@ -120,38 +199,617 @@ pub fn init_dataset_item(seed_mem: &SeedMemory, item_num: u64) -> [u64; 8] {
     ds
 }
 
+/// Computes the `count` dataset items starting with `start` into `out` (8 `u64` per item).
+/// Without compiled superscalar programs four items are computed at a time if AVX2 is available.
+pub fn init_dataset_items(seed_mem: &SeedMemory, start: u64, count: usize, out: &mut [u64]) {
+    assert!(out.len() >= count * 8, "out too small for {} items", count);
+    #[allow(unused_mut)]
+    let mut done = 0;
+    #[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
+    if use_avx2(seed_mem) {
+        done = count - count % dataset_avx2::LANES;
+        unsafe { dataset_avx2::init_dataset_items_x4(seed_mem, start, &mut out[..done * 8]) };
+    }
+    for (i, item) in out[done * 8..count * 8].chunks_exact_mut(8).enumerate() {
+        item.copy_from_slice(&init_dataset_item(seed_mem, start + (done + i) as u64));
+    }
+}
+
+/// The compiled superscalar programs are faster than four interpreted AVX2 lanes
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
+fn use_avx2(seed_mem: &SeedMemory) -> bool {
+    seed_mem.jit.is_none() && dataset_avx2::available()
+}
+
+/// Fills `items` (8 `u64` per item, starting with the dataset item `first_item`)
+/// spread over `threads` threads.
+pub fn init_dataset_range(seed_mem: &SeedMemory, items: &mut [u64], first_item: u64, threads: u64) {
+    let total = (items.len() / 8) as u64;
+    if total == 0 {
+        return;
+    }
+    let chunk_items = (total as usize).div_ceil(threads.max(1) as usize);
+    let done = AtomicU64::new(0);
+
+    thread::scope(|scope| {
+        for (chunk_ix, chunk) in items.chunks_mut(chunk_items * 8).enumerate() {
+            let done = &done;
+            let first_item = first_item + (chunk_ix * chunk_items) as u64;
+            scope.spawn(move || {
+                for (batch_ix, batch) in chunk.chunks_mut(DATASET_INIT_BATCH * 8).enumerate() {
+                    let batch_start = first_item + (batch_ix * DATASET_INIT_BATCH) as u64;
+                    let batch_items = (batch.len() / 8) as u64;
+                    init_dataset_items(seed_mem, batch_start, batch_items as usize, batch);
+                    let before = done.fetch_add(batch_items, Ordering::Relaxed);
+                    let after = before + batch_items;
+                    if before * 10 / total != after * 10 / total {
+                        info!("dataset init {}%", after * 100 / total);
+                    }
+                }
+            });
+        }
+    });
+}
+
+static HUGE_PAGES: AtomicBool = AtomicBool::new(false);
+
+/// Enables huge pages for all following `MemoryRegion` allocations (dataset and scratchpads).
+pub fn set_huge_pages(enabled: bool) {
+    HUGE_PAGES.store(enabled, Ordering::Relaxed);
+}
+
+pub fn huge_pages() -> bool {
+    HUGE_PAGES.load(Ordering::Relaxed)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageKind {
+    /// explicit huge pages (`MAP_HUGETLB`), need reserved huge pages
+    Huge,
+    /// transparent huge pages requested via `madvise`
+    Transparent,
+    Normal,
+    /// memory-mapped file
+    File,
+}
+
+impl fmt::Display for PageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PageKind::Huge => f.write_str("huge pages"),
+            PageKind::Transparent => f.write_str("transparent huge pages"),
+            PageKind::Normal => f.write_str("normal pages"),
+            PageKind::File => f.write_str("a memory-mapped file"),
+        }
+    }
+}
+
+/// Zero initialised, cache line aligned `u64` memory. Backed by huge pages if
+/// enabled (`set_huge_pages`) and available, normal pages otherwise.
+pub struct MemoryRegion {
+    ptr: NonNull<u64>,
+    len: usize,
+    /// size of the mapping, 0 if allocated on the heap
+    mapped_size: usize,
+    kind: PageKind,
+}
+
+// plain memory, exclusively owned
+unsafe impl Send for MemoryRegion {}
+unsafe impl Sync for MemoryRegion {}
+
+impl MemoryRegion {
+    /// Allocates `len` u64 values.
+    pub fn new(len: usize) -> MemoryRegion {
+        MemoryRegion::allocate(len, huge_pages())
+    }
+
+    /// Allocates `len` u64 values, tries huge pages first if `huge_pages` is set
+    /// (independent of `set_huge_pages`).
+    pub fn allocate(len: usize, huge_pages: bool) -> MemoryRegion {
+        if len == 0 {
+            return MemoryRegion {
+                ptr: NonNull::dangling(),
+                len,
+                mapped_size: 0,
+                kind: PageKind::Normal,
+            };
+        }
+        if huge_pages {
+            if let Some(region) = MemoryRegion::map(len) {
+                return region;
+            }
+        }
+        let layout = MemoryRegion::layout(len);
+        let ptr = unsafe { alloc_zeroed(layout) } as *mut u64;
+        match NonNull::new(ptr) {
+            Some(ptr) => MemoryRegion {
+                ptr,
+                len,
+                mapped_size: 0,
+                kind: PageKind::Normal,
+            },
+            None => handle_alloc_error(layout),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn map(len: usize) -> Option<MemoryRegion> {
+        let size = (len * 8).div_ceil(HUGE_PAGE_SIZE) * HUGE_PAGE_SIZE;
+        let prot = libc::PROT_READ | libc::PROT_WRITE;
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                prot,
+                flags | libc::MAP_HUGETLB,
+                -1,
+                0,
+            )
+        };
+        if ptr != libc::MAP_FAILED {
+            return MemoryRegion::mapped(ptr, len, size, PageKind::Huge);
+        }
+
+        //no (or not enough) huge pages reserved, try transparent huge pages
+        let ptr = unsafe { libc::mmap(ptr::null_mut(), size, prot, flags, -1, 0) };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        let kind = if unsafe { libc::madvise(ptr, size, libc::MADV_HUGEPAGE) } == 0 {
+            PageKind::Transparent
+        } else {
+            PageKind::Normal
+        };
+        MemoryRegion::mapped(ptr, len, size, kind)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn map(_len: usize) -> Option<MemoryRegion> {
+        None
+    }
+
+    /// Maps the first `len` u64 values of `file` (copy on write).
+    #[cfg(unix)]
+    pub fn map_file(file: &File, len: usize) -> io::Result<MemoryRegion> {
+        if len == 0 {
+            return Ok(MemoryRegion::new(0));
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len * 8,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(MemoryRegion {
+            ptr: NonNull::new(ptr as *mut u64).expect("mapping"),
+            len,
+            mapped_size: len * 8,
+            kind: PageKind::File,
+        })
+    }
+
+    /// Reads the first `len` u64 values of `file`, no mmap available.
+    #[cfg(not(unix))]
+    pub fn map_file(file: &File, len: usize) -> io::Result<MemoryRegion> {
+        let mut region = MemoryRegion::new(len);
+        let bytes = unsafe { slice::from_raw_parts_mut(region.as_mut_ptr() as *mut u8, len * 8) };
+        let mut reader = file;
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(bytes)?;
+        Ok(region)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn mapped(
+        ptr: *mut libc::c_void,
+        len: usize,
+        mapped_size: usize,
+        kind: PageKind,
+    ) -> Option<MemoryRegion> {
+        Some(MemoryRegion {
+            ptr: NonNull::new(ptr as *mut u64)?,
+            len,
+            mapped_size,
+            kind,
+        })
+    }
+
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(len * 8, CACHE_LINE_SIZE as usize).expect("memory layout")
+    }
+
+    pub fn page_kind(&self) -> PageKind {
+        self.kind
+    }
+
+    /// Pointer for writes through a shared reference, the caller has to rule out
+    /// concurrent access to the written values.
+    pub(crate) fn as_mut_ptr(&self) -> *mut u64 {
+        self.ptr.as_ptr()
+    }
+
+    fn as_atomic(&self) -> &[AtomicU64] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr() as *const AtomicU64, self.len) }
+    }
+}
+
+impl Deref for MemoryRegion {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for MemoryRegion {
+    fn deref_mut(&mut self) -> &mut [u64] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for MemoryRegion {
+    fn drop(&mut self) {
+        if self.mapped_size != 0 {
+            #[cfg(unix)]
+            unsafe {
+                libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.mapped_size);
+            }
+        } else if self.len != 0 {
+            unsafe { dealloc(self.ptr.as_ptr() as *mut u8, MemoryRegion::layout(self.len)) }
+        }
+    }
+}
+
+enum Dataset {
+    /// light mode, items are computed on every access
+    None,
+    /// items are computed on first access, `initialised` has one bit per item.
+    /// The memory is only accessed atomically.
+    Lazy {
+        memory: MemoryRegion,
+        initialised: Box<[AtomicU64]>,
+    },
+    /// all items initialised, read only
+    Complete(MemoryRegion),
+}
+
+/// Which memory is built for mining (`worker.memory_mode`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryMode {
+    /// seed memory and dataset, fast hashing
+    Full,
+    /// only the seed memory (256MiB), every dataset access computes the item (slow)
+    Light,
+    /// full if the available memory suffices, light otherwise
+    Auto,
+}
+
+impl MemoryMode {
+    /// `Full` or `Light`, `Auto` is decided with the `available` memory in bytes
+    /// (full if unknown).
+    pub fn resolve(
+        self,
+        params: &RandomxParams,
+        threads: u64,
+        available: Option<u64>,
+    ) -> MemoryMode {
+        match self {
+            MemoryMode::Auto => {
+                if available.is_none_or(|a| a >= full_memory_required(params, threads)) {
+                    MemoryMode::Full
+                } else {
+                    MemoryMode::Light
+                }
+            }
+            mode => mode,
+        }
+    }
+}
+
+impl fmt::Display for MemoryMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            MemoryMode::Full => "full",
+            MemoryMode::Light => "light",
+            MemoryMode::Auto => "auto",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Bytes needed for mining in full mode with `threads` threads. During a seed
+/// change the allocator holds two memories (seed memory and dataset each).
+pub fn full_memory_required(params: &RandomxParams, threads: u64) -> u64 {
+    let seed_memory = params.argon_memory as u64 * ARGON_BLOCK_SIZE as u64;
+    let dataset = params.dataset_item_count() as u64 * CACHE_LINE_SIZE;
+    2 * (seed_memory + dataset) + threads * params.scratchpad_l3_size
+}
+
+/// `MemAvailable` of `/proc/meminfo` in bytes, None if unknown.
+pub fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    parse_mem_available(&meminfo)
+}
+
+/// The `MemAvailable` line of the `/proc/meminfo` content in bytes (the file has kB).
+pub fn parse_mem_available(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let mut fields = line["MemAvailable:".len()..].split_whitespace();
+    let kb: u64 = fields.next()?.parse().ok()?;
+    match fields.next() {
+        Some("kB") => Some(kb * 1024),
+        _ => None,
+    }
+}
+
+/// Builds the `VmMemory` for a seed hash on a background thread. At most two
+/// memories are kept: the current one and the one being built (or built ahead
+/// of time for the next seed).
 #[derive(Clone)]
 pub struct VmMemoryAllocator {
     pub vm_memory_seed: String,
     pub vm_memory: Arc<VmMemory>,
+    /// Build the complete dataset instead of initialising items on first access
+    pub eager: bool,
+    /// Build light memories without a dataset (`MemoryMode::Light`)
+    pub light: bool,
+    /// Compile the superscalar programs of the seed memories (`worker.jit`)
+    pub jit: bool,
+    /// Threads for the eager dataset init, None: one per worker thread
+    pub init_threads: Option<u64>,
+    pub disk_cache: Option<DiskCache>,
+    /// RandomX variant of the built memories
+    pub params: RandomxParams,
+    /// Finished background builds, has to be passed to `finished`
+    pub built_rcvr: Receiver<(String, VmMemory)>,
+    built_sndr: Sender<(String, VmMemory)>,
+    building: Option<String>,
+    /// requested while another build was running
+    queued: Option<String>,
+    /// requested by `memory_for`, becomes the current memory once built
+    waiting: Option<String>,
+    /// built ahead of time, not used yet
+    next: Option<(String, Arc<VmMemory>)>,
 }
 
 impl VmMemoryAllocator {
     pub fn initial() -> VmMemoryAllocator {
+        let (built_sndr, built_rcvr) = unbounded();
         VmMemoryAllocator {
             vm_memory_seed: "".to_string(),
             vm_memory: Arc::new(VmMemory::no_memory()),
+            eager: false,
+            light: false,
+            jit: true,
+            init_threads: None,
+            disk_cache: None,
+            params: RX_0,
+            built_rcvr,
+            built_sndr,
+            building: None,
+            queued: None,
+            waiting: None,
+            next: None,
+        }
+    }
+
+    pub fn with_eager_init(init_threads: Option<u64>) -> VmMemoryAllocator {
+        VmMemoryAllocator {
+            eager: true,
+            init_threads,
+            ..VmMemoryAllocator::initial()
+        }
+    }
+
+    /// Switches the RandomX variant. The memories of the previous variant are dropped,
+    /// a build still running for it is discarded once `finished`.
+    pub fn set_params(&mut self, params: RandomxParams) {
+        if params == self.params {
+            return;
         }
+        info!("switching to {}", params.name);
+        self.params = params;
+        self.vm_memory_seed = "".to_string();
+        self.vm_memory = Arc::new(VmMemory::no_memory());
+        self.queued = None;
+        self.waiting = None;
+        self.next = None;
     }
 
-    pub fn reallocate(&mut self, seed: String) {
+    /// Returns the memory for `seed` if it is ready. Otherwise it is built in the
+    /// background and becomes the current memory once `finished`.
+    pub fn memory_for(&mut self, seed: &str, worker_threads: u64) -> Option<Arc<VmMemory>> {
         if seed != self.vm_memory_seed {
-            let mem_init_start = Instant::now();
-            self.vm_memory = Arc::new(VmMemory::full(&byte_string::string_to_u8_array(&seed)));
-            self.vm_memory_seed = seed;
+            match self.next.take() {
+                Some((next_seed, memory)) if next_seed == seed => {
+                    self.vm_memory_seed = next_seed;
+                    self.vm_memory = memory;
+                }
+                next => {
+                    self.next = next;
+                    self.waiting = Some(seed.to_string());
+                    self.request(seed, worker_threads);
+                    return None;
+                }
+            }
+        }
+        self.waiting = None;
+        Some(self.vm_memory.clone())
+    }
+
+    /// Builds the memory for a seed that will be used by upcoming jobs.
+    pub fn prepare(&mut self, seed: &str, worker_threads: u64) {
+        let prepared = matches!(&self.next, Some((next_seed, _)) if next_seed == seed);
+        if seed != self.vm_memory_seed && !prepared {
+            self.request(seed, worker_threads);
+        }
+    }
+
+    /// Takes over a memory received from `built_rcvr`.
+    pub fn finished(&mut self, seed: String, memory: VmMemory, worker_threads: u64) {
+        self.building = None;
+        if *memory.params() != self.params {
             info!(
-                "memory init took {}ms with seed_hash: {}",
-                mem_init_start.elapsed().as_millis(),
-                self.vm_memory_seed,
+                "memory for seed_hash {} discarded, built for {}",
+                seed,
+                memory.params().name
             );
+            if let Some(waiting) = self.waiting.clone() {
+                self.request(&waiting, worker_threads);
+            }
+        } else {
+            info!("memory for seed_hash {} ready", seed);
+            let memory = Arc::new(memory);
+            if self.waiting.as_ref() == Some(&seed) {
+                self.waiting = None;
+                self.vm_memory_seed = seed;
+                self.vm_memory = memory;
+            } else {
+                self.next = Some((seed, memory));
+            }
+        }
+        if let Some(queued) = self.queued.take() {
+            self.request(&queued, worker_threads);
+        }
+    }
+
+    fn request(&mut self, seed: &str, worker_threads: u64) {
+        if self.building.as_deref() == Some(seed) {
+            return;
+        }
+        if self.building.is_some() {
+            self.queued = Some(seed.to_string());
+            return;
+        }
+        //free the prepared memory early, never more than two at the same time
+        self.next = None;
+        self.building = Some(seed.to_string());
+
+        let seed = seed.to_string();
+        let light = self.light;
+        let eager = self.eager;
+        let jit = self.jit;
+        let threads = self.init_threads.unwrap_or(worker_threads);
+        let disk_cache = self.disk_cache.clone();
+        let params = self.params;
+        let built_sndr = self.built_sndr.clone();
+        thread::Builder::new()
+            .name("memory init".to_string())
+            .spawn(move || {
+                let mem_init_start = Instant::now();
+                let memory = build_memory(
+                    &seed,
+                    light,
+                    eager,
+                    jit,
+                    threads,
+                    disk_cache.as_ref(),
+                    &params,
+                );
+                info!(
+                    "memory init took {}ms with seed_hash: {}",
+                    mem_init_start.elapsed().as_millis(),
+                    seed,
+                );
+                if built_sndr.send((seed, memory)).is_err() {
+                    error!("memory init finished, but the allocator was dropped");
+                }
+            })
+            .expect("memory init thread");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_memory(
+    seed: &str,
+    light: bool,
+    eager: bool,
+    jit: bool,
+    threads: u64,
+    disk_cache: Option<&DiskCache>,
+    params: &RandomxParams,
+) -> VmMemory {
+    let key = byte_string::string_to_u8_array(seed);
+    let entry_key = DiskCache::entry_key(seed, params);
+    let cache = match disk_cache {
+        Some(cache) => cache,
+        None => {
+            let seed_memory = SeedMemory::with_params(&key, params, jit);
+            return vm_memory(seed_memory, light, eager, threads);
+        }
+    };
+
+    if let Err(err) = cache.evict_stale(&entry_key) {
+        warn!("evicting stale disk cache entries failed: {}", err);
+    }
+    let seed_memory = match cache.load_seed_memory(&entry_key, &key, params, jit) {
+        Some(seed_memory) => seed_memory,
+        None => {
+            let seed_memory = SeedMemory::with_params(&key, params, jit);
+            if let Err(err) = cache.store_seed_memory(&entry_key, &seed_memory) {
+                warn!("storing seed memory in disk cache failed: {}", err);
+            }
+            seed_memory
         }
+    };
+
+    if light || !cache.stores_dataset() {
+        return vm_memory(seed_memory, light, eager, threads);
+    }
+    let dataset = match cache.load_dataset(&entry_key, params.dataset_item_count() * 8) {
+        Some(dataset) => dataset,
+        None => {
+            let dataset = build_dataset(&seed_memory, threads);
+            if let Err(err) = cache.store_dataset(&entry_key, &dataset) {
+                warn!("storing dataset in disk cache failed: {}", err);
+            }
+            dataset
+        }
+    };
+    VmMemory::complete(seed_memory, dataset)
+}
+
+fn vm_memory(seed_memory: SeedMemory, light: bool, eager: bool, threads: u64) -> VmMemory {
+    if light {
+        VmMemory::light_from(seed_memory)
+    } else if eager {
+        let dataset = build_dataset(&seed_memory, threads);
+        VmMemory::complete(seed_memory, dataset)
+    } else {
+        VmMemory::lazy(seed_memory)
     }
 }
 
+/// Allocates and initialises the complete dataset with `threads` threads.
+pub fn build_dataset(seed_mem: &SeedMemory, threads: u64) -> MemoryRegion {
+    let mut mem = MemoryRegion::new(seed_mem.params.dataset_item_count() * 8);
+    info!("dataset allocated with {}", mem.page_kind());
+
+    let init_start = Instant::now();
+    init_dataset_range(seed_mem, &mut mem, 0, threads);
+    info!(
+        "dataset init with {} threads took {}ms",
+        threads,
+        init_start.elapsed().as_millis()
+    );
+    mem
+}
+
 pub struct VmMemory {
     pub seed_memory: SeedMemory,
-    pub dataset_memory: RwLock<Vec<Option<[u64; 8]>>>,
-    pub cache: bool,
+    dataset: Dataset,
 }
 
 impl VmMemory {
@@ -159,69 +817,136 @@ impl VmMemory {
     pub fn no_memory() -> VmMemory {
         VmMemory {
             seed_memory: SeedMemory::no_memory(),
-            cache: false,
-            dataset_memory: RwLock::new(Vec::with_capacity(0)),
+            dataset: Dataset::None,
         }
     }
 
     pub fn light(key: &[u8]) -> VmMemory {
+        VmMemory::light_with_params(key, &RX_0, true)
+    }
+
+    /// Light mode for the RandomX variant `params`, see `SeedMemory::with_params` for `jit`.
+    pub fn light_with_params(key: &[u8], params: &RandomxParams, jit: bool) -> VmMemory {
+        VmMemory::light_from(SeedMemory::with_params(key, params, jit))
+    }
+
+    /// Light mode with an already built seed memory.
+    pub fn light_from(seed_memory: SeedMemory) -> VmMemory {
         VmMemory {
-            seed_memory: SeedMemory::new_initialised(key),
-            cache: false,
-            dataset_memory: RwLock::new(Vec::with_capacity(0)),
+            seed_memory,
+            dataset: Dataset::None,
         }
     }
+
+    /// No dataset, items are computed on every access.
+    pub fn is_light(&self) -> bool {
+        matches!(self.dataset, Dataset::None)
+    }
+
     pub fn full(key: &[u8]) -> VmMemory {
-        let seed_mem = SeedMemory::new_initialised(key);
-        let mem = vec![None; DATASET_ITEM_COUNT];
+        VmMemory::lazy(SeedMemory::new_initialised(key))
+    }
+
+    /// Full memory, dataset items are initialised on first access.
+    pub fn lazy(seed_memory: SeedMemory) -> VmMemory {
+        let item_count = seed_memory.params.dataset_item_count();
+        let initialised = (0..item_count.div_ceil(64))
+            .map(|_| AtomicU64::new(0))
+            .collect();
+        let memory = MemoryRegion::new(item_count * 8);
+        info!("dataset allocated with {}", memory.page_kind());
         VmMemory {
-            seed_memory: seed_mem,
-            cache: true,
-            dataset_memory: RwLock::new(mem),
+            seed_memory,
+            dataset: Dataset::Lazy {
+                memory,
+                initialised,
+            },
+        }
+    }
+
+    /// Like `full`, but all dataset items are initialised up front with `threads` threads.
+    pub fn full_eager(key: &[u8], threads: u64) -> VmMemory {
+        let seed_memory = SeedMemory::new_initialised(key);
+        let dataset = build_dataset(&seed_memory, threads);
+        VmMemory::complete(seed_memory, dataset)
+    }
+
+    /// Full memory with the complete (already initialised) `dataset`.
+    pub fn complete(seed_memory: SeedMemory, dataset: MemoryRegion) -> VmMemory {
+        let item_count = seed_memory.params.dataset_item_count();
+        assert_eq!(dataset.len(), item_count * 8, "dataset size");
+        VmMemory {
+            seed_memory,
+            dataset: Dataset::Complete(dataset),
+        }
+    }
+
+    pub fn params(&self) -> &RandomxParams {
+        &self.seed_memory.params
+    }
+
+    /// The complete dataset, None in light mode or if items are initialised on first access.
+    pub(crate) fn dataset_mut(&mut self) -> Option<&mut MemoryRegion> {
+        match &mut self.dataset {
+            Dataset::Complete(memory) => Some(memory),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn dataset(&self) -> Option<&MemoryRegion> {
+        match &self.dataset {
+            Dataset::Complete(memory) => Some(memory),
+            _ => None,
         }
     }
 
     pub fn dataset_prefetch(&self, offset: u64) {
         let item_num = offset / CACHE_LINE_SIZE;
-        if self.cache {
-            let mem = self.dataset_memory.read().unwrap();
-            let rl_cached = &mem[item_num as usize];
-            if let Some(rl) = rl_cached {
-                unsafe {
-                    let raw: *const i8 = std::mem::transmute(rl);
-                    _mm_prefetch(raw, _MM_HINT_NTA);
-                }
-            }
-        }
+        let memory = match &self.dataset {
+            Dataset::Complete(memory) | Dataset::Lazy { memory, .. } => memory,
+            Dataset::None => return,
+        };
+        prefetch(memory.as_atomic()[item_num as usize * 8..].as_ptr() as *const u8);
     }
 
     pub fn dataset_read(&self, offset: u64, reg: &mut [u64; 8]) {
         let item_num = offset / CACHE_LINE_SIZE;
+        let ix = item_num as usize * 8;
 
-        if self.cache {
-            {
-                let mem = self.dataset_memory.read().unwrap();
-                let rl_cached = &mem[item_num as usize];
-                if let Some(rl) = rl_cached {
+        match &self.dataset {
+            Dataset::Complete(memory) => {
+                let rl = &memory[ix..ix + 8];
+                for i in 0..8 {
+                    reg[i] ^= rl[i];
+                }
+            }
+            Dataset::Lazy {
+                memory,
+                initialised,
+            } => {
+                let rl = &memory.as_atomic()[ix..ix + 8];
+                let flags = &initialised[item_num as usize / 64];
+                let bit = 1 << (item_num % 64);
+                if flags.load(Ordering::Acquire) & bit != 0 {
+                    for i in 0..8 {
+                        reg[i] ^= rl[i].load(Ordering::Relaxed);
+                    }
+                } else {
+                    //concurrent inits of the same item write the same values
+                    let item = init_dataset_item(&self.seed_memory, item_num);
                     for i in 0..8 {
-                        reg[i] ^= rl[i];
+                        rl[i].store(item[i], Ordering::Relaxed);
+                        reg[i] ^= item[i];
                     }
-                    return;
+                    flags.fetch_or(bit, Ordering::Release);
                 }
             }
-            {
+            Dataset::None => {
                 let rl = init_dataset_item(&self.seed_memory, item_num);
-                let mut mem_mut = self.dataset_memory.write().unwrap();
-                mem_mut[item_num as usize] = Some(rl);
                 for i in 0..8 {
                     reg[i] ^= rl[i];
                 }
             }
-        } else {
-            let rl = init_dataset_item(&self.seed_memory, item_num);
-            for i in 0..8 {
-                reg[i] ^= rl[i];
-            }
         }
     }
 }
@@ -1,7 +1,25 @@
+mod api;
+pub mod bytecode;
 pub mod common;
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
+pub mod dataset_avx2;
+pub mod disk_cache;
+pub mod ffi;
 pub mod hash;
+pub mod jit;
+#[cfg_attr(
+    any(feature = "portable", not(target_arch = "x86_64")),
+    path = "m128_portable.rs"
+)]
 pub mod m128;
 pub mod memory;
+pub mod params;
 pub mod program;
+pub mod rounding;
+pub mod soft_aes;
+pub mod soft_float;
 pub mod superscalar;
+pub mod verify;
 pub mod vm;
+
+pub use self::api::{RandomxCache, RandomxDataset, RandomxError, RandomxFlags, RandomxVm};
@@ -0,0 +1,295 @@
+//! Parameters of the RandomX variants, the `configuration.h` of the reference
+//! implementation. The cache (Argon2) parameters, the dataset size, the
+//! scratchpad sizes, the program shape and the instruction frequencies differ
+//! between the coins using RandomX.
+//!
+//! rx/wow also changes the keys of the program generator (`program_keys`), rx/xla
+//! is RandomXL, the variant of Scala.
+
+/// Number of instructions with a frequency (all `Opcode`s except `NOP`)
+pub const INSTRUCTION_COUNT: usize = 29;
+
+const DATASET_ITEM_SIZE: u64 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RandomxParams {
+    /// algorithm name as used by pools and proxies
+    pub name: &'static str,
+    /// Argon2 memory in KiB (1KiB blocks)
+    pub argon_memory: u32,
+    pub argon_iterations: u32,
+    pub argon_lanes: u32,
+    pub argon_salt: &'static [u8],
+    /// number of superscalar programs per dataset item
+    pub cache_accesses: usize,
+    /// dataset size in bytes
+    pub dataset_base_size: u64,
+    pub dataset_extra_size: u64,
+    /// scratchpad level sizes in bytes
+    pub scratchpad_l1_size: u64,
+    pub scratchpad_l2_size: u64,
+    pub scratchpad_l3_size: u64,
+    /// instructions per program
+    pub program_size: usize,
+    pub program_iterations: usize,
+    /// chained programs per hash
+    pub program_count: usize,
+    pub jump_bits: u64,
+    pub jump_offset: u64,
+    /// Instruction frequencies (out of 256) in the order of the `Opcode` variants,
+    /// the remaining opcodes are `NOP`s
+    pub frequencies: [u16; INSTRUCTION_COUNT],
+    /// AES keys of the program generator (`fillAes4Rx4`), highest word first
+    pub program_keys: [[u32; 4]; 8],
+}
+
+pub const RX_0: RandomxParams = RandomxParams {
+    name: "rx/0",
+    argon_memory: 262144,
+    argon_iterations: 3,
+    argon_lanes: 1,
+    argon_salt: b"RandomX\x03",
+    cache_accesses: 8,
+    dataset_base_size: 2147483648,
+    dataset_extra_size: 33554368,
+    scratchpad_l1_size: 16384,
+    scratchpad_l2_size: 262144,
+    scratchpad_l3_size: 2097152,
+    program_size: 256,
+    program_iterations: 2048,
+    program_count: 8,
+    jump_bits: 8,
+    jump_offset: 8,
+    frequencies: [
+        16, //IADD_RS
+        7,  //IADD_M
+        16, //ISUB_R
+        7,  //ISUB_M
+        16, //IMUL_R
+        4,  //IMUL_M
+        4,  //IMULH_R
+        1,  //IMULH_M
+        4,  //ISMULH_R
+        1,  //ISMULH_M
+        8,  //IMUL_RCP
+        2,  //INEG_R
+        15, //IXOR_R
+        5,  //IXOR_M
+        8,  //IROR_R
+        2,  //IROL_R
+        4,  //ISWAP_R
+        4,  //FSWAP_R
+        16, //FADD_R
+        5,  //FADD_M
+        16, //FSUB_R
+        5,  //FSUB_M
+        6,  //FSCAL_R
+        32, //FMUL_R
+        4,  //FDIV_M
+        6,  //FSQRT_R
+        25, //CBRANCH
+        1,  //CFROUND
+        16, //ISTORE
+    ],
+    program_keys: [
+        [0x99e5d23f, 0x2f546d2b, 0xd1833ddb, 0x6421aadd],
+        [0xa5dfcde5, 0x06f79d53, 0xb6913f55, 0xb20e3450],
+        [0x171c02bf, 0x0aa4679f, 0x515e7baf, 0x5c3ed904],
+        [0xd8ded291, 0xcd673785, 0xe78f5d08, 0x85623763],
+        [0x229effb4, 0x3d518b6d, 0xe3d6a7a6, 0xb5826f73],
+        [0xb272b7d2, 0xe9024d4e, 0x9c10b3d9, 0xc7566bf3],
+        [0xf63befa7, 0x2ba9660a, 0xf765a38b, 0xf273c9e7],
+        [0xc0b0762d, 0x0c06d1fd, 0x915839de, 0x7a7cd609],
+    ],
+};
+
+/// RandomARQ (ArQmA)
+pub const RX_ARQ: RandomxParams = RandomxParams {
+    name: "rx/arq",
+    argon_iterations: 1,
+    argon_salt: b"RandomARQ\x01",
+    scratchpad_l2_size: 131072,
+    scratchpad_l3_size: 262144,
+    program_iterations: 1024,
+    program_count: 4,
+    ..RX_0
+};
+
+/// The generator keys of RandomX before the audit, both halves use the same keys
+const WOW_PROGRAM_KEY0: [u32; 4] = [0xcf359e95, 0x141f82b7, 0x7ffbe4a6, 0xf890465d];
+const WOW_PROGRAM_KEY1: [u32; 4] = [0x6741ffdc, 0xbd5c5ac3, 0xfee8278a, 0x6a55c450];
+const WOW_PROGRAM_KEY2: [u32; 4] = [0x3d324aac, 0xa7279ad2, 0xd524fde4, 0x114c47a4];
+const WOW_PROGRAM_KEY3: [u32; 4] = [0x76f6db08, 0x42d3dbd9, 0x99a9aeff, 0x810c3a2a];
+
+/// RandomWOW (Wownero)
+pub const RX_WOW: RandomxParams = RandomxParams {
+    name: "rx/wow",
+    argon_salt: b"RandomWOW\x01",
+    scratchpad_l2_size: 131072,
+    scratchpad_l3_size: 1048576,
+    program_iterations: 1024,
+    program_count: 16,
+    frequencies: [
+        25, //IADD_RS
+        7,  //IADD_M
+        16, //ISUB_R
+        7,  //ISUB_M
+        16, //IMUL_R
+        4,  //IMUL_M
+        4,  //IMULH_R
+        1,  //IMULH_M
+        4,  //ISMULH_R
+        1,  //ISMULH_M
+        8,  //IMUL_RCP
+        2,  //INEG_R
+        15, //IXOR_R
+        5,  //IXOR_M
+        10, //IROR_R
+        0,  //IROL_R
+        4,  //ISWAP_R
+        8,  //FSWAP_R
+        20, //FADD_R
+        5,  //FADD_M
+        20, //FSUB_R
+        5,  //FSUB_M
+        6,  //FSCAL_R
+        20, //FMUL_R
+        4,  //FDIV_M
+        6,  //FSQRT_R
+        16, //CBRANCH
+        1,  //CFROUND
+        16, //ISTORE
+    ],
+    program_keys: [
+        WOW_PROGRAM_KEY0,
+        WOW_PROGRAM_KEY1,
+        WOW_PROGRAM_KEY2,
+        WOW_PROGRAM_KEY3,
+        WOW_PROGRAM_KEY0,
+        WOW_PROGRAM_KEY1,
+        WOW_PROGRAM_KEY2,
+        WOW_PROGRAM_KEY3,
+    ],
+    ..RX_0
+};
+
+/// RandomXL (Scala)
+pub const RX_XLA: RandomxParams = RandomxParams {
+    name: "rx/xla",
+    argon_memory: 131072,
+    argon_iterations: 2,
+    argon_salt: b"DefyXScala\x13",
+    cache_accesses: 2,
+    dataset_base_size: 33554432,
+    scratchpad_l1_size: 65536,
+    scratchpad_l2_size: 131072,
+    scratchpad_l3_size: 262144,
+    program_size: 64,
+    program_iterations: 1024,
+    program_count: 4,
+    frequencies: [
+        25, //IADD_RS
+        7,  //IADD_M
+        16, //ISUB_R
+        7,  //ISUB_M
+        16, //IMUL_R
+        4,  //IMUL_M
+        4,  //IMULH_R
+        1,  //IMULH_M
+        4,  //ISMULH_R
+        1,  //ISMULH_M
+        8,  //IMUL_RCP
+        2,  //INEG_R
+        15, //IXOR_R
+        5,  //IXOR_M
+        8,  //IROR_R
+        2,  //IROL_R
+        4,  //ISWAP_R
+        4,  //FSWAP_R
+        16, //FADD_R
+        5,  //FADD_M
+        16, //FSUB_R
+        5,  //FSUB_M
+        6,  //FSCAL_R
+        32, //FMUL_R
+        4,  //FDIV_M
+        6,  //FSQRT_R
+        16, //CBRANCH
+        1,  //CFROUND
+        16, //ISTORE
+    ],
+    ..RX_0
+};
+
+pub const PRESETS: [RandomxParams; 4] = [RX_0, RX_WOW, RX_ARQ, RX_XLA];
+
+impl Default for RandomxParams {
+    fn default() -> Self {
+        RX_0
+    }
+}
+
+impl RandomxParams {
+    /// The preset with the algorithm `name` (e.g. "rx/0").
+    pub fn by_name(name: &str) -> Option<RandomxParams> {
+        PRESETS.iter().find(|p| p.name == name).copied()
+    }
+
+    /// 8 byte aligned address mask of the scratchpad levels
+    pub const fn scratchpad_l1_mask(&self) -> u64 {
+        (self.scratchpad_l1_size - 1) & !7
+    }
+
+    pub const fn scratchpad_l2_mask(&self) -> u64 {
+        (self.scratchpad_l2_size - 1) & !7
+    }
+
+    pub const fn scratchpad_l3_mask(&self) -> u64 {
+        (self.scratchpad_l3_size - 1) & !7
+    }
+
+    /// 64 byte aligned address mask of the whole scratchpad (spAddr of the program loop)
+    pub const fn scratchpad_l3_mask64(&self) -> u64 {
+        (self.scratchpad_l3_size - 1) & !(DATASET_ITEM_SIZE - 1)
+    }
+
+    /// Scratchpad size in `u64`
+    pub const fn scratchpad_len(&self) -> usize {
+        (self.scratchpad_l3_size / 8) as usize
+    }
+
+    pub const fn condition_mask(&self) -> u64 {
+        (1 << self.jump_bits) - 1
+    }
+
+    pub const fn dataset_item_count(&self) -> usize {
+        ((self.dataset_base_size + self.dataset_extra_size) / DATASET_ITEM_SIZE) as usize
+    }
+
+    pub const fn dataset_extra_items(&self) -> u64 {
+        self.dataset_extra_size / DATASET_ITEM_SIZE
+    }
+
+    /// Item aligned mask of the dataset base (mx/ma registers)
+    pub const fn cache_line_align_mask(&self) -> u64 {
+        (self.dataset_base_size - 1) & !(DATASET_ITEM_SIZE - 1)
+    }
+
+    /// Number of `m128i` generated for a program: 128 bytes of entropy and
+    /// 8 bytes per instruction.
+    pub const fn program_bytes_len(&self) -> usize {
+        8 + self.program_size / 2
+    }
+
+    /// Exclusive upper bound of the opcode byte for each instruction, in the
+    /// order of `frequencies`.
+    pub fn opcode_limits(&self) -> [i64; INSTRUCTION_COUNT] {
+        let mut limits = [0; INSTRUCTION_COUNT];
+        let mut limit = 0;
+        for (l, freq) in limits.iter_mut().zip(self.frequencies.iter()) {
+            limit += *freq as i64;
+            *l = limit;
+        }
+        limits
+    }
+}
@@ -1,5 +1,6 @@
 use super::m128::m128i;
-use super::vm::{is_zero_or_power_of_2, Vm, SCRATCHPAD_L3_MASK};
+use super::params::{RandomxParams, INSTRUCTION_COUNT};
+use super::vm::is_zero_or_power_of_2;
 use std::fmt;
 use strum::Display;
 
@@ -7,41 +8,42 @@ pub const MAX_FLOAT_REG: usize = 4;
 pub const MAX_REG: usize = 8;
 pub const REG_NEEDS_DISPLACEMENT_IX: usize = 5;
 pub const REG_NEEDS_DISPLACEMENT: Store = Store::R(REG_NEEDS_DISPLACEMENT_IX);
-const STORE_L3_CONDITION: u8 = 14;
+pub const STORE_L3_CONDITION: u8 = 14;
 
+/// In the order of `RandomxParams::frequencies`
 #[allow(nonstandard_style)]
-#[derive(Display, Debug, PartialEq)]
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Opcode {
-    NOP = 0,
-    IADD_RS = 0x10,
-    IADD_M = 0x17,
-    ISUB_R = 0x27,
-    ISUB_M = 0x2e,
-    IMUL_R = 0x3e,
-    IMUL_M = 0x42,
-    IMULH_R = 0x46,
-    IMULH_M = 0x47,
-    ISMULH_R = 0x4b,
-    ISMULH_M = 0x4c,
-    IMUL_RCP = 0x54,
-    INEG_R = 0x56,
-    IXOR_R = 0x65,
-    IXOR_M = 0x6a,
-    IROR_R = 0x72,
-    IROL_R = 0x74,
-    ISWAP_R = 0x78,
-    FSWAP_R = 0x7c,
-    FADD_R = 0x8c,
-    FADD_M = 0x91,
-    FSUB_R = 0xa1,
-    FSUB_M = 0xa6,
-    FSCAL_R = 0xac,
-    FMUL_R = 0xcc,
-    FDIV_M = 0xd0,
-    FSQRT_R = 0xd6,
-    CBRANCH = 0xef,
-    CFROUND = 0xf0,
-    ISTORE = 0x100,
+    IADD_RS,
+    IADD_M,
+    ISUB_R,
+    ISUB_M,
+    IMUL_R,
+    IMUL_M,
+    IMULH_R,
+    IMULH_M,
+    ISMULH_R,
+    ISMULH_M,
+    IMUL_RCP,
+    INEG_R,
+    IXOR_R,
+    IXOR_M,
+    IROR_R,
+    IROL_R,
+    ISWAP_R,
+    FSWAP_R,
+    FADD_R,
+    FADD_M,
+    FSUB_R,
+    FSUB_M,
+    FSCAL_R,
+    FMUL_R,
+    FDIV_M,
+    FSQRT_R,
+    CBRANCH,
+    CFROUND,
+    ISTORE,
+    NOP,
 }
 
 #[derive(Display, PartialEq)]
@@ -85,17 +87,9 @@ pub struct Instr {
     pub unsigned_imm: bool,
     pub mode: Mode,
     pub target: Option<i32>,
-    pub effect: fn(&mut Vm, &Instr),
 }
 
-fn new_instr(
-    op: Opcode,
-    dst: Store,
-    src: Store,
-    imm: i32,
-    mode: Mode,
-    effect: fn(&mut Vm, &Instr),
-) -> Instr {
+fn new_instr(op: Opcode, dst: Store, src: Store, imm: i32, mode: Mode) -> Instr {
     if src == dst {
         return Instr {
             op,
@@ -105,7 +99,6 @@ fn new_instr(
             unsigned_imm: false,
             mode,
             target: None,
-            effect,
         };
     }
     Instr {
@@ -116,17 +109,10 @@ fn new_instr(
         unsigned_imm: false,
         mode,
         target: None,
-        effect,
     }
 }
 
-fn new_imm_instr(
-    op: Opcode,
-    dst: Store,
-    imm: i32,
-    mode: Mode,
-    effect: fn(&mut Vm, &Instr),
-) -> Instr {
+fn new_imm_instr(op: Opcode, dst: Store, imm: i32, mode: Mode) -> Instr {
     Instr {
         op,
         dst,
@@ -135,7 +121,6 @@ fn new_imm_instr(
         unsigned_imm: false,
         mode,
         target: None,
-        effect,
     }
 }
 
@@ -145,7 +130,7 @@ pub fn new_lcache_instr(
     src: usize,
     imm: i32,
     modi: u8,
-    effect: fn(&mut Vm, &Instr),
+    l3_mask: u64,
 ) -> Instr {
     let src_reg = r_reg(src);
     if src_reg == dst_reg {
@@ -153,11 +138,10 @@ pub fn new_lcache_instr(
             op,
             dst: dst_reg,
             src: Store::L3(Box::new(Store::Imm)),
-            imm: Some(imm & (SCRATCHPAD_L3_MASK as i32)),
+            imm: Some(imm & (l3_mask as i32)),
             unsigned_imm: false,
             mode: Mode::None,
             target: None,
-            effect,
         };
     }
     let lx = l12_cache(src, modi);
@@ -169,13 +153,6 @@ pub fn new_lcache_instr(
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect,
-    }
-}
-
-impl Instr {
-    pub fn execute(&self, vm: &mut Vm) {
-        (self.effect)(vm, self);
     }
 }
 
@@ -249,7 +226,11 @@ pub struct Program {
 }
 
 impl Program {
-    pub fn from_bytes(bytes: Vec<m128i>) -> Program {
+    /// Decodes the generated program `bytes` (`RandomxParams::program_bytes_len`) with
+    /// the instruction frequencies of `params`.
+    pub fn from_bytes(bytes: Vec<m128i>, params: &RandomxParams) -> Program {
+        let limits = params.opcode_limits();
+        let l3_mask = params.scratchpad_l3_mask();
         let mut entropy = Vec::with_capacity(16);
         let mut program = Vec::with_capacity((bytes.len() - 8) * 2);
         let mut register_usage = [-1; MAX_REG];
@@ -262,8 +243,9 @@ impl Program {
 
         for (i, byte) in bytes.iter().enumerate().skip(8) {
             let (op2, op1) = byte.as_i64();
-            let instr1 = decode_instruction(op1, ((i - 8) * 2) as i32, &mut register_usage);
-            let instr2 = decode_instruction(op2, (((i - 8) * 2) + 1) as i32, &mut register_usage);
+            let ix = ((i - 8) * 2) as i32;
+            let instr1 = decode_instruction(op1, ix, &mut register_usage, &limits, l3_mask);
+            let instr2 = decode_instruction(op2, ix + 1, &mut register_usage, &limits, l3_mask);
             program.push(instr1);
             program.push(instr2);
         }
@@ -286,13 +268,21 @@ impl fmt::Display for Program {
 }
 
 #[allow(overflowing_literals)]
-pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG]) -> Instr {
+/// `limits` are the `RandomxParams::opcode_limits`, `l3_mask` the
+/// `RandomxParams::scratchpad_l3_mask`.
+pub fn decode_instruction(
+    bytes: i64,
+    i: i32,
+    register_usage: &mut [i32; MAX_REG],
+    limits: &[i64; INSTRUCTION_COUNT],
+    l3_mask: u64,
+) -> Instr {
     let op = bytes & 0xFF;
     let dst = ((bytes & 0xFF00) >> 8) as usize;
     let src = ((bytes & 0xFF0000) >> 16) as usize;
     let modi = ((bytes & 0xFF000000) >> 24) as u8;
     let imm = ((bytes & 0xFFFFFFFF00000000) >> 32) as i32;
-    if op < Opcode::IADD_RS as i64 {
+    if op < limits[Opcode::IADD_RS as usize] {
         let dst_reg = r_reg(dst);
         let imm_val;
         if dst_reg == REG_NEEDS_DISPLACEMENT {
@@ -309,44 +299,29 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
             unsigned_imm: false,
             mode: mod_shft(modi),
             target: None,
-            effect: Vm::exec_iadd_rs,
         };
     }
-    if op < Opcode::IADD_M as i64 {
+    if op < limits[Opcode::IADD_M as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_lcache_instr(Opcode::IADD_M, r_reg(dst), src, imm, modi, Vm::exec_iadd_m);
+        return new_lcache_instr(Opcode::IADD_M, r_reg(dst), src, imm, modi, l3_mask);
     }
-    if op < Opcode::ISUB_R as i64 {
+    if op < limits[Opcode::ISUB_R as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_instr(
-            Opcode::ISUB_R,
-            r_reg(dst),
-            r_reg(src),
-            imm,
-            Mode::None,
-            Vm::exec_isub_r,
-        );
-    }
-    if op < Opcode::ISUB_M as i64 {
+        return new_instr(Opcode::ISUB_R, r_reg(dst), r_reg(src), imm, Mode::None);
+    }
+    if op < limits[Opcode::ISUB_M as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_lcache_instr(Opcode::ISUB_M, r_reg(dst), src, imm, modi, Vm::exec_isub_m);
+        return new_lcache_instr(Opcode::ISUB_M, r_reg(dst), src, imm, modi, l3_mask);
     }
-    if op < Opcode::IMUL_R as i64 {
+    if op < limits[Opcode::IMUL_R as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_instr(
-            Opcode::IMUL_R,
-            r_reg(dst),
-            r_reg(src),
-            imm,
-            Mode::None,
-            Vm::exec_imul_r,
-        );
-    }
-    if op < Opcode::IMUL_M as i64 {
+        return new_instr(Opcode::IMUL_R, r_reg(dst), r_reg(src), imm, Mode::None);
+    }
+    if op < limits[Opcode::IMUL_M as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_lcache_instr(Opcode::IMUL_M, r_reg(dst), src, imm, modi, Vm::exec_imul_m);
+        return new_lcache_instr(Opcode::IMUL_M, r_reg(dst), src, imm, modi, l3_mask);
     }
-    if op < Opcode::IMULH_R as i64 {
+    if op < limits[Opcode::IMULH_R as usize] {
         register_usage[dst % MAX_REG] = i;
         return Instr {
             op: Opcode::IMULH_R,
@@ -356,21 +331,13 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
             unsigned_imm: false,
             mode: Mode::None,
             target: None,
-            effect: Vm::exec_imulh_r,
         };
     }
-    if op < Opcode::IMULH_M as i64 {
+    if op < limits[Opcode::IMULH_M as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_lcache_instr(
-            Opcode::IMULH_M,
-            r_reg(dst),
-            src,
-            imm,
-            modi,
-            Vm::exec_imulh_m,
-        );
-    }
-    if op < Opcode::ISMULH_R as i64 {
+        return new_lcache_instr(Opcode::IMULH_M, r_reg(dst), src, imm, modi, l3_mask);
+    }
+    if op < limits[Opcode::ISMULH_R as usize] {
         register_usage[dst % MAX_REG] = i;
         return Instr {
             op: Opcode::ISMULH_R,
@@ -380,83 +347,41 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
             unsigned_imm: false,
             mode: Mode::None,
             target: None,
-            effect: Vm::exec_ismulh_r,
         };
     }
-    if op < Opcode::ISMULH_M as i64 {
+    if op < limits[Opcode::ISMULH_M as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_lcache_instr(
-            Opcode::ISMULH_M,
-            r_reg(dst),
-            src,
-            imm,
-            modi,
-            Vm::exec_ismulh_m,
-        );
-    }
-    if op < Opcode::IMUL_RCP as i64 {
+        return new_lcache_instr(Opcode::ISMULH_M, r_reg(dst), src, imm, modi, l3_mask);
+    }
+    if op < limits[Opcode::IMUL_RCP as usize] {
         if !is_zero_or_power_of_2(imm as u64) {
             register_usage[dst % MAX_REG] = i;
         }
-        let mut instr = new_imm_instr(
-            Opcode::IMUL_RCP,
-            r_reg(dst),
-            imm,
-            Mode::None,
-            Vm::exec_imul_rcp,
-        );
+        let mut instr = new_imm_instr(Opcode::IMUL_RCP, r_reg(dst), imm, Mode::None);
         instr.unsigned_imm = true;
         return instr;
     }
-    if op < Opcode::INEG_R as i64 {
+    if op < limits[Opcode::INEG_R as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_instr(
-            Opcode::INEG_R,
-            r_reg(dst),
-            Store::NONE,
-            imm,
-            Mode::None,
-            Vm::exec_ineg_r,
-        );
-    }
-    if op < Opcode::IXOR_R as i64 {
+        return new_instr(Opcode::INEG_R, r_reg(dst), Store::NONE, imm, Mode::None);
+    }
+    if op < limits[Opcode::IXOR_R as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_instr(
-            Opcode::IXOR_R,
-            r_reg(dst),
-            r_reg(src),
-            imm,
-            Mode::None,
-            Vm::exec_ixor_r,
-        );
-    }
-    if op < Opcode::IXOR_M as i64 {
+        return new_instr(Opcode::IXOR_R, r_reg(dst), r_reg(src), imm, Mode::None);
+    }
+    if op < limits[Opcode::IXOR_M as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_lcache_instr(Opcode::IXOR_M, r_reg(dst), src, imm, modi, Vm::exec_ixor_m);
+        return new_lcache_instr(Opcode::IXOR_M, r_reg(dst), src, imm, modi, l3_mask);
     }
-    if op < Opcode::IROR_R as i64 {
+    if op < limits[Opcode::IROR_R as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_instr(
-            Opcode::IROR_R,
-            r_reg(dst),
-            r_reg(src),
-            imm & 63,
-            Mode::None,
-            Vm::exec_iror_r,
-        );
-    }
-    if op < Opcode::IROL_R as i64 {
+        return new_instr(Opcode::IROR_R, r_reg(dst), r_reg(src), imm & 63, Mode::None);
+    }
+    if op < limits[Opcode::IROL_R as usize] {
         register_usage[dst % MAX_REG] = i;
-        return new_instr(
-            Opcode::IROL_R,
-            r_reg(dst),
-            r_reg(src),
-            imm & 63,
-            Mode::None,
-            Vm::exec_irol_r,
-        );
-    }
-    if op < Opcode::ISWAP_R as i64 {
+        return new_instr(Opcode::IROL_R, r_reg(dst), r_reg(src), imm & 63, Mode::None);
+    }
+    if op < limits[Opcode::ISWAP_R as usize] {
         let dst_r = dst % MAX_REG;
         let src_r = src % MAX_REG;
         if src_r != dst_r {
@@ -470,13 +395,12 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
                 unsigned_imm: false,
                 mode: Mode::None,
                 target: None,
-                effect: Vm::exec_iswap_r,
             };
         } else {
-            return new_instr(Opcode::NOP, Store::NONE, Store::NONE, imm, Mode::None, nop);
+            return new_instr(Opcode::NOP, Store::NONE, Store::NONE, imm, Mode::None);
         }
     }
-    if op < Opcode::FSWAP_R as i64 {
+    if op < limits[Opcode::FSWAP_R as usize] {
         let dst_ix = dst % MAX_REG;
         if dst_ix >= MAX_FLOAT_REG {
             return new_instr(
@@ -485,7 +409,6 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
                 Store::NONE,
                 imm,
                 Mode::None,
-                Vm::exec_fswap_r,
             );
         } else {
             return new_instr(
@@ -494,70 +417,34 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
                 Store::NONE,
                 imm,
                 Mode::None,
-                Vm::exec_fswap_r,
             );
         }
     }
-    if op < Opcode::FADD_R as i64 {
-        return new_instr(
-            Opcode::FADD_R,
-            f_reg(dst),
-            a_reg(src),
-            imm,
-            Mode::None,
-            Vm::exec_fadd_r,
-        );
-    }
-    if op < Opcode::FADD_M as i64 {
-        return new_lcache_instr(Opcode::FADD_M, f_reg(dst), src, imm, modi, Vm::exec_fadd_m);
-    }
-    if op < Opcode::FSUB_R as i64 {
-        return new_instr(
-            Opcode::FSUB_R,
-            f_reg(dst),
-            a_reg(src),
-            imm,
-            Mode::None,
-            Vm::exec_fsub_r,
-        );
-    }
-    if op < Opcode::FSUB_M as i64 {
-        return new_lcache_instr(Opcode::FSUB_M, f_reg(dst), src, imm, modi, Vm::exec_fsub_m);
-    }
-    if op < Opcode::FSCAL_R as i64 {
-        return new_instr(
-            Opcode::FSCAL_R,
-            f_reg(dst),
-            Store::NONE,
-            imm,
-            Mode::None,
-            Vm::exec_fscal_r,
-        );
-    }
-    if op < Opcode::FMUL_R as i64 {
-        return new_instr(
-            Opcode::FMUL_R,
-            e_reg(dst),
-            a_reg(src),
-            imm,
-            Mode::None,
-            Vm::exec_fmul_r,
-        );
-    }
-    if op < Opcode::FDIV_M as i64 {
-        return new_lcache_instr(Opcode::FDIV_M, e_reg(dst), src, imm, modi, Vm::exec_fdiv_m);
-    }
-    if op < Opcode::FSQRT_R as i64 {
-        return new_instr(
-            Opcode::FSQRT_R,
-            e_reg(dst),
-            Store::NONE,
-            imm,
-            Mode::None,
-            Vm::exec_fsqrt_r,
-        );
-    }
-    if op < Opcode::CBRANCH as i64 {
+    if op < limits[Opcode::FADD_R as usize] {
+        return new_instr(Opcode::FADD_R, f_reg(dst), a_reg(src), imm, Mode::None);
+    }
+    if op < limits[Opcode::FADD_M as usize] {
+        return new_lcache_instr(Opcode::FADD_M, f_reg(dst), src, imm, modi, l3_mask);
+    }
+    if op < limits[Opcode::FSUB_R as usize] {
+        return new_instr(Opcode::FSUB_R, f_reg(dst), a_reg(src), imm, Mode::None);
+    }
+    if op < limits[Opcode::FSUB_M as usize] {
+        return new_lcache_instr(Opcode::FSUB_M, f_reg(dst), src, imm, modi, l3_mask);
+    }
+    if op < limits[Opcode::FSCAL_R as usize] {
+        return new_instr(Opcode::FSCAL_R, f_reg(dst), Store::NONE, imm, Mode::None);
+    }
+    if op < limits[Opcode::FMUL_R as usize] {
+        return new_instr(Opcode::FMUL_R, e_reg(dst), a_reg(src), imm, Mode::None);
+    }
+    if op < limits[Opcode::FDIV_M as usize] {
+        return new_lcache_instr(Opcode::FDIV_M, e_reg(dst), src, imm, modi, l3_mask);
+    }
+    if op < limits[Opcode::FSQRT_R as usize] {
+        return new_instr(Opcode::FSQRT_R, e_reg(dst), Store::NONE, imm, Mode::None);
+    }
+    if op < limits[Opcode::CBRANCH as usize] {
         let target = register_usage[dst % MAX_REG];
         for usage in register_usage.iter_mut().take(MAX_REG) {
             *usage = i;
@@ -570,10 +457,9 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
             unsigned_imm: false,
             mode: mod_cond(modi),
             target: Some(target),
-            effect: Vm::exec_cbranch,
         };
     }
-    if op < Opcode::CFROUND as i64 {
+    if op < limits[Opcode::CFROUND as usize] {
         return Instr {
             op: Opcode::CFROUND,
             dst: Store::NONE,
@@ -582,10 +468,9 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
             unsigned_imm: false,
             mode: Mode::None,
             target: None,
-            effect: Vm::exec_cfround,
         };
     }
-    if op < Opcode::ISTORE as i64 {
+    if op < limits[Opcode::ISTORE as usize] {
         return Instr {
             op: Opcode::ISTORE,
             dst: l_cache(dst, modi),
@@ -594,10 +479,9 @@ pub fn decode_instruction(bytes: i64, i: i32, register_usage: &mut [i32; MAX_REG
             unsigned_imm: false,
             mode: Mode::None,
             target: None,
-            effect: Vm::exec_istore,
         };
     }
-    new_instr(Opcode::NOP, Store::NONE, Store::NONE, imm, Mode::None, nop)
+    new_instr(Opcode::NOP, Store::NONE, Store::NONE, imm, Mode::None)
 }
 
 pub fn r_reg(dst: usize) -> Store {
@@ -691,5 +575,3 @@ fn mod_cond(modi: u8) -> Mode {
 fn mod_shft(modi: u8) -> Mode {
     Mode::Shft((modi >> 2) % 4)
 }
-
-pub fn nop(_state: &mut Vm, _instr: &Instr) {}
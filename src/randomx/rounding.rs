@@ -0,0 +1,128 @@
+//! Control of the floating point rounding mode (the RandomX `fprc` register).
+//!
+//! Modes are numbered as in RandomX: 0 = to nearest, 1 = down (towards -inf),
+//! 2 = up (towards +inf), 3 = towards zero.
+
+pub const ROUND_TO_NEAREST: u32 = 0;
+pub const ROUND_DOWN: u32 = 1;
+pub const ROUND_UP: u32 = 2;
+pub const ROUND_TO_ZERO: u32 = 3;
+
+/// MXCSR with all exceptions masked, round to nearest.
+pub const MXCSR_DEFAULT: u32 = 0x9FC0;
+
+pub fn reset_rounding_mode() {
+    set_rounding_mode(ROUND_TO_NEAREST);
+}
+
+/// Saves the floating point control state of the current thread (MXCSR/FPCR)
+/// and restores it when dropped, also if the thread unwinds.
+pub struct FpStateGuard {
+    state: u64,
+}
+
+impl FpStateGuard {
+    pub fn save() -> FpStateGuard {
+        FpStateGuard { state: fp_state() }
+    }
+}
+
+impl Drop for FpStateGuard {
+    fn drop(&mut self) {
+        set_fp_state(self.state);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn set_rounding_mode(mode: u32) {
+    let mxcsr = MXCSR_DEFAULT | ((mode & 3) << 13);
+    unsafe {
+        std::arch::asm!("ldmxcsr [{}]", in(reg) &mxcsr, options(nostack, readonly));
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn get_rounding_mode() -> u32 {
+    (fp_state() as u32 >> 13) & 3
+}
+
+/// The whole floating point control register (MXCSR/FPCR) of the current thread
+#[cfg(target_arch = "x86_64")]
+pub fn fp_state() -> u64 {
+    let mut mxcsr: u32 = 0;
+    unsafe {
+        std::arch::asm!("stmxcsr [{}]", in(reg) &mut mxcsr, options(nostack));
+    }
+    mxcsr as u64
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn set_fp_state(state: u64) {
+    let mxcsr = state as u32;
+    unsafe {
+        std::arch::asm!("ldmxcsr [{}]", in(reg) &mxcsr, options(nostack, readonly));
+    }
+}
+
+//FPCR.RMode (bits 22-23): 0 = nearest, 1 = +inf, 2 = -inf, 3 = zero
+#[cfg(target_arch = "aarch64")]
+const FPCR_RMODE: [u64; 4] = [0, 2, 1, 3];
+
+#[cfg(target_arch = "aarch64")]
+pub fn set_rounding_mode(mode: u32) {
+    unsafe {
+        let mut fpcr: u64;
+        std::arch::asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack));
+        fpcr = (fpcr & !(3 << 22)) | (FPCR_RMODE[(mode & 3) as usize] << 22);
+        std::arch::asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack));
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn get_rounding_mode() -> u32 {
+    FPCR_RMODE[((fp_state() >> 22) & 3) as usize] as u32
+}
+
+/// The whole floating point control register (MXCSR/FPCR) of the current thread
+#[cfg(target_arch = "aarch64")]
+pub fn fp_state() -> u64 {
+    let fpcr: u64;
+    unsafe {
+        std::arch::asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack));
+    }
+    fpcr
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn set_fp_state(state: u64) {
+    unsafe {
+        std::arch::asm!("msr fpcr, {}", in(reg) state, options(nomem, nostack));
+    }
+}
+
+// No known way to switch the rounding mode, only the mode is remembered.
+// Floating point results are only correct for round to nearest on these targets.
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+thread_local! {
+    static ROUNDING_MODE: std::cell::Cell<u32> = std::cell::Cell::new(ROUND_TO_NEAREST);
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn set_rounding_mode(mode: u32) {
+    ROUNDING_MODE.with(|m| m.set(mode & 3));
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn get_rounding_mode() -> u32 {
+    ROUNDING_MODE.with(|m| m.get())
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn fp_state() -> u64 {
+    get_rounding_mode() as u64
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn set_fp_state(state: u64) {
+    set_rounding_mode(state as u32);
+}
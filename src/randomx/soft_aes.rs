@@ -0,0 +1,212 @@
+//! Table based software implementation of the single AES round used by
+//! RandomX (aesenc/aesdec), for CPUs without AES-NI.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+const AES_UNDETECTED: u8 = 0;
+const AES_HARDWARE: u8 = 1;
+const AES_SOFTWARE: u8 = 2;
+
+static AES_MODE: AtomicU8 = AtomicU8::new(AES_UNDETECTED);
+
+thread_local! {
+    /// mode of an `AesModeGuard` on this thread, `AES_UNDETECTED` if there is none
+    static THREAD_AES_MODE: Cell<u8> = const { Cell::new(AES_UNDETECTED) };
+}
+
+/// Returns true if the AES-NI instructions are used. Detected on the
+/// first call unless `set_hardware_aes` was called before, an `AesModeGuard`
+/// overrides it for its thread.
+pub fn hardware_aes() -> bool {
+    match THREAD_AES_MODE.with(|mode| mode.get()) {
+        AES_HARDWARE => return true,
+        AES_SOFTWARE => return false,
+        _ => {}
+    }
+    match AES_MODE.load(Ordering::Relaxed) {
+        AES_HARDWARE => true,
+        AES_SOFTWARE => false,
+        _ => {
+            let detected = detect_hardware_aes();
+            set_hardware_aes(detected);
+            detected
+        }
+    }
+}
+
+/// Overrides the CPU detection. Enabling hardware AES on a CPU
+/// without AES-NI will crash with an illegal instruction.
+pub fn set_hardware_aes(enabled: bool) {
+    let mode = if enabled { AES_HARDWARE } else { AES_SOFTWARE };
+    AES_MODE.store(mode, Ordering::Relaxed);
+}
+
+/// Selects hardware or software AES for the current thread only, the previous
+/// selection of the thread is restored when dropped (also if the thread unwinds).
+pub struct AesModeGuard {
+    previous: u8,
+}
+
+impl AesModeGuard {
+    /// Enabling hardware AES on a CPU without AES-NI will crash with an illegal instruction.
+    pub fn set(hardware: bool) -> AesModeGuard {
+        let mode = if hardware { AES_HARDWARE } else { AES_SOFTWARE };
+        AesModeGuard {
+            previous: THREAD_AES_MODE.with(|m| m.replace(mode)),
+        }
+    }
+}
+
+impl Drop for AesModeGuard {
+    fn drop(&mut self) {
+        THREAD_AES_MODE.with(|m| m.set(self.previous));
+    }
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(feature = "portable")
+))]
+pub fn detect_hardware_aes() -> bool {
+    is_x86_feature_detected!("aes")
+}
+
+#[cfg(not(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(feature = "portable")
+)))]
+pub fn detect_hardware_aes() -> bool {
+    false
+}
+
+/// One AES encryption round (ShiftRows, SubBytes, MixColumns, AddRoundKey), same as `_mm_aesenc_si128`.
+/// The state is given as 32 bit lanes, lane 0 holds the lowest bytes.
+pub fn aesenc(s: [u32; 4], key: [u32; 4]) -> [u32; 4] {
+    [
+        enc_column(s[0], s[1], s[2], s[3]) ^ key[0],
+        enc_column(s[1], s[2], s[3], s[0]) ^ key[1],
+        enc_column(s[2], s[3], s[0], s[1]) ^ key[2],
+        enc_column(s[3], s[0], s[1], s[2]) ^ key[3],
+    ]
+}
+
+/// One AES decryption round (InvShiftRows, InvSubBytes, InvMixColumns, AddRoundKey),
+/// same as `_mm_aesdec_si128`.
+pub fn aesdec(s: [u32; 4], key: [u32; 4]) -> [u32; 4] {
+    [
+        dec_column(s[0], s[3], s[2], s[1]) ^ key[0],
+        dec_column(s[1], s[0], s[3], s[2]) ^ key[1],
+        dec_column(s[2], s[1], s[0], s[3]) ^ key[2],
+        dec_column(s[3], s[2], s[1], s[0]) ^ key[3],
+    ]
+}
+
+#[inline(always)]
+fn enc_column(c0: u32, c1: u32, c2: u32, c3: u32) -> u32 {
+    ENC_TABLE[0][(c0 & 0xFF) as usize]
+        ^ ENC_TABLE[1][((c1 >> 8) & 0xFF) as usize]
+        ^ ENC_TABLE[2][((c2 >> 16) & 0xFF) as usize]
+        ^ ENC_TABLE[3][(c3 >> 24) as usize]
+}
+
+#[inline(always)]
+fn dec_column(c0: u32, c1: u32, c2: u32, c3: u32) -> u32 {
+    DEC_TABLE[0][(c0 & 0xFF) as usize]
+        ^ DEC_TABLE[1][((c1 >> 8) & 0xFF) as usize]
+        ^ DEC_TABLE[2][((c2 >> 16) & 0xFF) as usize]
+        ^ DEC_TABLE[3][(c3 >> 24) as usize]
+}
+
+//tables (generated at compile time)
+
+const SBOX: [u8; 256] = sbox();
+static ENC_TABLE: [[u32; 256]; 4] = enc_table(&SBOX);
+static DEC_TABLE: [[u32; 256]; 4] = dec_table(&inv_sbox(&SBOX));
+
+const fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            p ^= a;
+        }
+        let hi = a & 0x80;
+        a <<= 1;
+        if hi != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    p
+}
+
+//multiplicative inverse in GF(2^8): a^254
+const fn ginv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut base = a;
+    let mut exp = 254;
+    while exp != 0 {
+        if exp & 1 != 0 {
+            result = gmul(result, base);
+        }
+        base = gmul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+const fn sbox() -> [u8; 256] {
+    let mut sbox = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let b = ginv(i as u8);
+        sbox[i] =
+            b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^ b.rotate_left(4) ^ 0x63;
+        i += 1;
+    }
+    sbox
+}
+
+const fn inv_sbox(sbox: &[u8; 256]) -> [u8; 256] {
+    let mut inv = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+const fn rotate_table(table: &mut [[u32; 256]; 4]) {
+    let mut i = 0;
+    while i < 256 {
+        table[1][i] = table[0][i].rotate_left(8);
+        table[2][i] = table[0][i].rotate_left(16);
+        table[3][i] = table[0][i].rotate_left(24);
+        i += 1;
+    }
+}
+
+const fn enc_table(sbox: &[u8; 256]) -> [[u32; 256]; 4] {
+    let mut table = [[0; 256]; 4];
+    let mut i = 0;
+    while i < 256 {
+        let s = sbox[i];
+        table[0][i] = u32::from_le_bytes([gmul(s, 2), s, s, gmul(s, 3)]);
+        i += 1;
+    }
+    rotate_table(&mut table);
+    table
+}
+
+const fn dec_table(inv_sbox: &[u8; 256]) -> [[u32; 256]; 4] {
+    let mut table = [[0; 256]; 4];
+    let mut i = 0;
+    while i < 256 {
+        let s = inv_sbox[i];
+        table[0][i] = u32::from_le_bytes([gmul(s, 0xe), gmul(s, 0x9), gmul(s, 0xd), gmul(s, 0xb)]);
+        i += 1;
+    }
+    rotate_table(&mut table);
+    table
+}
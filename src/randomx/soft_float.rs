@@ -0,0 +1,435 @@
+//! Correctly rounded IEEE 754 double precision arithmetic in software for
+//! the four RandomX rounding modes. Only integer operations are used, so
+//! the results neither depend on nor change the floating point state of
+//! the thread. Special values follow the SSE2 conventions (first NaN
+//! operand is propagated, invalid operations return the default NaN).
+//! Subnormals are handled as in IEEE 754 and not flushed to zero as with
+//! MXCSR_DEFAULT, this makes no difference for RandomX which never produces
+//! subnormal values.
+//!
+//! The add/sub/mul algorithms follow Berkeley SoftFloat 3.
+
+use super::m128::m128d;
+use super::rounding::{ROUND_DOWN, ROUND_TO_NEAREST, ROUND_UP};
+
+const SIGN_BIT: u64 = 0x8000000000000000;
+const QUIET_BIT: u64 = 0x0008000000000000;
+const DEFAULT_NAN: u64 = 0xFFF8000000000000;
+const FRAC_MASK: u64 = 0x000FFFFFFFFFFFFF;
+const HIDDEN_BIT: u64 = 0x0010000000000000;
+const EXP_MAX: i32 = 0x7FF;
+
+pub fn add(a: f64, b: f64, mode: u32) -> f64 {
+    let (ui_a, ui_b) = (a.to_bits(), b.to_bits());
+    let sign_a = sign(ui_a);
+    let bits = if sign_a == sign(ui_b) {
+        add_mags(ui_a, ui_b, sign_a, mode)
+    } else {
+        sub_mags(ui_a, ui_b, sign_a, mode)
+    };
+    f64::from_bits(bits)
+}
+
+pub fn sub(a: f64, b: f64, mode: u32) -> f64 {
+    let (ui_a, ui_b) = (a.to_bits(), b.to_bits());
+    let sign_a = sign(ui_a);
+    let bits = if sign_a == sign(ui_b) {
+        sub_mags(ui_a, ui_b, sign_a, mode)
+    } else {
+        add_mags(ui_a, ui_b, sign_a, mode)
+    };
+    f64::from_bits(bits)
+}
+
+pub fn mul(a: f64, b: f64, mode: u32) -> f64 {
+    let (ui_a, ui_b) = (a.to_bits(), b.to_bits());
+    let (mut exp_a, mut sig_a) = (exp(ui_a), frac(ui_a));
+    let (mut exp_b, mut sig_b) = (exp(ui_b), frac(ui_b));
+    let sign_z = sign(ui_a) ^ sign(ui_b);
+
+    if exp_a == EXP_MAX || exp_b == EXP_MAX {
+        if is_nan(ui_a) || is_nan(ui_b) {
+            return f64::from_bits(propagate_nan(ui_a, ui_b));
+        }
+        //inf * 0 is invalid
+        if (exp_a == 0 && sig_a == 0) || (exp_b == 0 && sig_b == 0) {
+            return f64::from_bits(DEFAULT_NAN);
+        }
+        return f64::from_bits(pack(sign_z, EXP_MAX, 0));
+    }
+    if exp_a == 0 {
+        if sig_a == 0 {
+            return f64::from_bits(pack(sign_z, 0, 0));
+        }
+        (exp_a, sig_a) = norm_subnormal(sig_a);
+    }
+    if exp_b == 0 {
+        if sig_b == 0 {
+            return f64::from_bits(pack(sign_z, 0, 0));
+        }
+        (exp_b, sig_b) = norm_subnormal(sig_b);
+    }
+
+    let mut exp_z = exp_a + exp_b - 0x3FF;
+    let sig_a = (sig_a | HIDDEN_BIT) << 10;
+    let sig_b = (sig_b | HIDDEN_BIT) << 11;
+    let sig128 = (sig_a as u128) * (sig_b as u128);
+    let mut sig_z = ((sig128 >> 64) as u64) | ((sig128 as u64 != 0) as u64);
+    if sig_z < 0x4000000000000000 {
+        exp_z -= 1;
+        sig_z <<= 1;
+    }
+    f64::from_bits(round_pack(sign_z, exp_z, sig_z, mode))
+}
+
+pub fn div(a: f64, b: f64, mode: u32) -> f64 {
+    let (ui_a, ui_b) = (a.to_bits(), b.to_bits());
+    let (mut exp_a, mut sig_a) = (exp(ui_a), frac(ui_a));
+    let (mut exp_b, mut sig_b) = (exp(ui_b), frac(ui_b));
+    let sign_z = sign(ui_a) ^ sign(ui_b);
+
+    if is_nan(ui_a) || is_nan(ui_b) {
+        return f64::from_bits(propagate_nan(ui_a, ui_b));
+    }
+    if exp_a == EXP_MAX {
+        if exp_b == EXP_MAX {
+            return f64::from_bits(DEFAULT_NAN);
+        }
+        return f64::from_bits(pack(sign_z, EXP_MAX, 0));
+    }
+    if exp_b == EXP_MAX {
+        return f64::from_bits(pack(sign_z, 0, 0));
+    }
+    if exp_b == 0 {
+        if sig_b == 0 {
+            if exp_a == 0 && sig_a == 0 {
+                return f64::from_bits(DEFAULT_NAN);
+            }
+            return f64::from_bits(pack(sign_z, EXP_MAX, 0));
+        }
+        (exp_b, sig_b) = norm_subnormal(sig_b);
+    }
+    if exp_a == 0 {
+        if sig_a == 0 {
+            return f64::from_bits(pack(sign_z, 0, 0));
+        }
+        (exp_a, sig_a) = norm_subnormal(sig_a);
+    }
+
+    let mut exp_z = exp_a - exp_b + 0x3FE;
+    let mut sig_a = sig_a | HIDDEN_BIT;
+    let sig_b = sig_b | HIDDEN_BIT;
+    if sig_a < sig_b {
+        exp_z -= 1;
+        sig_a <<= 1;
+    }
+    //quotient with the leading bit at position 62
+    let num = (sig_a as u128) << 62;
+    let q = (num / sig_b as u128) as u64;
+    let rem = num % sig_b as u128;
+    let sig_z = q | ((rem != 0) as u64);
+    f64::from_bits(round_pack(sign_z, exp_z, sig_z, mode))
+}
+
+pub fn sqrt(a: f64, mode: u32) -> f64 {
+    let ui_a = a.to_bits();
+    let (mut exp_a, mut sig_a) = (exp(ui_a), frac(ui_a));
+
+    if is_nan(ui_a) {
+        return f64::from_bits(ui_a | QUIET_BIT);
+    }
+    if exp_a == 0 && sig_a == 0 {
+        return a; //sqrt(-0) = -0
+    }
+    if sign(ui_a) {
+        return f64::from_bits(DEFAULT_NAN);
+    }
+    if exp_a == EXP_MAX {
+        return a;
+    }
+    if exp_a == 0 {
+        (exp_a, sig_a) = norm_subnormal(sig_a);
+    }
+
+    let mut e = exp_a - 0x3FF;
+    let mut m = (sig_a | HIDDEN_BIT) as u128;
+    if e & 1 != 0 {
+        m <<= 1;
+        e -= 1;
+    }
+    //sqrt(m * 2^72) = sqrt(m / 2^52) * 2^62
+    let n = m << 72;
+    let s = isqrt(n);
+    let sig_z = (s as u64) | ((s * s != n) as u64);
+    f64::from_bits(round_pack(false, (e >> 1) + 0x3FE, sig_z, mode))
+}
+
+//m128d lane wise versions
+
+pub fn add_pd(a: m128d, b: m128d, mode: u32) -> m128d {
+    lane_wise(a, b, mode, add)
+}
+
+pub fn sub_pd(a: m128d, b: m128d, mode: u32) -> m128d {
+    lane_wise(a, b, mode, sub)
+}
+
+pub fn mul_pd(a: m128d, b: m128d, mode: u32) -> m128d {
+    lane_wise(a, b, mode, mul)
+}
+
+pub fn div_pd(a: m128d, b: m128d, mode: u32) -> m128d {
+    lane_wise(a, b, mode, div)
+}
+
+pub fn sqrt_pd(a: m128d, mode: u32) -> m128d {
+    let (h, l) = a.as_f64();
+    m128d::from_f64(sqrt(h, mode), sqrt(l, mode))
+}
+
+fn lane_wise(a: m128d, b: m128d, mode: u32, op: fn(f64, f64, u32) -> f64) -> m128d {
+    let (ah, al) = a.as_f64();
+    let (bh, bl) = b.as_f64();
+    m128d::from_f64(op(ah, bh, mode), op(al, bl, mode))
+}
+
+//helper
+
+fn sign(ui: u64) -> bool {
+    ui & SIGN_BIT != 0
+}
+
+fn exp(ui: u64) -> i32 {
+    ((ui >> 52) & 0x7FF) as i32
+}
+
+fn frac(ui: u64) -> u64 {
+    ui & FRAC_MASK
+}
+
+fn is_nan(ui: u64) -> bool {
+    exp(ui) == EXP_MAX && frac(ui) != 0
+}
+
+fn propagate_nan(ui_a: u64, ui_b: u64) -> u64 {
+    if is_nan(ui_a) {
+        ui_a | QUIET_BIT
+    } else {
+        ui_b | QUIET_BIT
+    }
+}
+
+// sig is added, so a set hidden bit increments the exponent
+fn pack(sign: bool, exp: i32, sig: u64) -> u64 {
+    ((sign as u64) << 63)
+        .wrapping_add((exp as u64) << 52)
+        .wrapping_add(sig)
+}
+
+fn norm_subnormal(sig: u64) -> (i32, u64) {
+    let shift = sig.leading_zeros() as i32 - 11;
+    (1 - shift, sig << shift)
+}
+
+fn shift_right_jam(a: u64, dist: u32) -> u64 {
+    if dist < 63 {
+        (a >> dist) | (((a << ((64 - dist) & 63)) != 0 && dist != 0) as u64)
+    } else {
+        (a != 0) as u64
+    }
+}
+
+fn isqrt(n: u128) -> u128 {
+    let mut rem = n;
+    let mut root: u128 = 0;
+    let mut bit: u128 = 1 << 126;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/// Rounds and packs `sig * 2^(exp - 0x3FE - 62)`, sig has its leading bit at position 62
+/// and 10 extra bits below the mantissa (the lowest one is sticky).
+fn round_pack(sign: bool, mut exp: i32, mut sig: u64, mode: u32) -> u64 {
+    let round_near_even = mode == ROUND_TO_NEAREST;
+    let round_increment: u64 = if round_near_even {
+        0x200
+    } else if (sign && mode == ROUND_DOWN) || (!sign && mode == ROUND_UP) {
+        0x3FF
+    } else {
+        0
+    };
+    let mut round_bits = sig & 0x3FF;
+    if exp < 0 {
+        sig = shift_right_jam(sig, (-exp) as u32);
+        exp = 0;
+        round_bits = sig & 0x3FF;
+    } else if exp >= 0x7FD && (exp > 0x7FD || sig.wrapping_add(round_increment) >= SIGN_BIT) {
+        //overflow: infinity or the largest finite number (if rounding away from infinity)
+        return pack(sign, EXP_MAX, 0) - (round_increment == 0) as u64;
+    }
+    sig = (sig + round_increment) >> 10;
+    if round_near_even && round_bits == 0x200 {
+        sig &= !1;
+    }
+    if sig == 0 {
+        exp = 0;
+    }
+    pack(sign, exp, sig)
+}
+
+fn add_mags(ui_a: u64, ui_b: u64, sign_z: bool, mode: u32) -> u64 {
+    let (exp_a, mut sig_a) = (exp(ui_a), frac(ui_a));
+    let (exp_b, mut sig_b) = (exp(ui_b), frac(ui_b));
+    let exp_diff = exp_a - exp_b;
+
+    let exp_z;
+    let mut sig_z;
+    if exp_diff == 0 {
+        if exp_a == 0 {
+            return ui_a + sig_b;
+        }
+        if exp_a == EXP_MAX {
+            if sig_a != 0 || sig_b != 0 {
+                return propagate_nan(ui_a, ui_b);
+            }
+            return ui_a;
+        }
+        exp_z = exp_a;
+        sig_z = (HIDDEN_BIT * 2 + sig_a + sig_b) << 9;
+    } else {
+        sig_a <<= 9;
+        sig_b <<= 9;
+        let mut e;
+        if exp_diff < 0 {
+            if exp_b == EXP_MAX {
+                if sig_b != 0 {
+                    return propagate_nan(ui_a, ui_b);
+                }
+                return pack(sign_z, EXP_MAX, 0);
+            }
+            e = exp_b;
+            if exp_a != 0 {
+                sig_a += 0x2000000000000000;
+            } else {
+                sig_a <<= 1;
+            }
+            sig_a = shift_right_jam(sig_a, (-exp_diff) as u32);
+        } else {
+            if exp_a == EXP_MAX {
+                if sig_a != 0 {
+                    return propagate_nan(ui_a, ui_b);
+                }
+                return ui_a;
+            }
+            e = exp_a;
+            if exp_b != 0 {
+                sig_b += 0x2000000000000000;
+            } else {
+                sig_b <<= 1;
+            }
+            sig_b = shift_right_jam(sig_b, exp_diff as u32);
+        }
+        sig_z = 0x2000000000000000 + sig_a + sig_b;
+        if sig_z < 0x4000000000000000 {
+            e -= 1;
+            sig_z <<= 1;
+        }
+        exp_z = e;
+    }
+    round_pack(sign_z, exp_z, sig_z, mode)
+}
+
+fn sub_mags(ui_a: u64, ui_b: u64, mut sign_z: bool, mode: u32) -> u64 {
+    let (mut exp_a, mut sig_a) = (exp(ui_a), frac(ui_a));
+    let (exp_b, mut sig_b) = (exp(ui_b), frac(ui_b));
+    let exp_diff = exp_a - exp_b;
+
+    if exp_diff == 0 {
+        if exp_a == EXP_MAX {
+            if sig_a != 0 || sig_b != 0 {
+                return propagate_nan(ui_a, ui_b);
+            }
+            return DEFAULT_NAN; //inf - inf
+        }
+        let mut sig_diff = sig_a as i64 - sig_b as i64;
+        if sig_diff == 0 {
+            return pack(mode == ROUND_DOWN, 0, 0);
+        }
+        if exp_a != 0 {
+            exp_a -= 1;
+        }
+        if sig_diff < 0 {
+            sign_z = !sign_z;
+            sig_diff = -sig_diff;
+        }
+        let mut shift = sig_diff.leading_zeros() as i32 - 11;
+        let mut exp_z = exp_a - shift;
+        if exp_z < 0 {
+            shift = exp_a;
+            exp_z = 0;
+        }
+        return pack(sign_z, exp_z, (sig_diff as u64) << shift);
+    }
+
+    sig_a <<= 10;
+    sig_b <<= 10;
+    let exp_z;
+    let sig_z;
+    if exp_diff < 0 {
+        sign_z = !sign_z;
+        if exp_b == EXP_MAX {
+            if sig_b != 0 {
+                return propagate_nan(ui_a, ui_b);
+            }
+            return pack(sign_z, EXP_MAX, 0);
+        }
+        sig_a += if exp_a != 0 {
+            0x4000000000000000
+        } else {
+            sig_a
+        };
+        sig_a = shift_right_jam(sig_a, (-exp_diff) as u32);
+        sig_b |= 0x4000000000000000;
+        exp_z = exp_b;
+        sig_z = sig_b - sig_a;
+    } else {
+        if exp_a == EXP_MAX {
+            if sig_a != 0 {
+                return propagate_nan(ui_a, ui_b);
+            }
+            return ui_a;
+        }
+        sig_b += if exp_b != 0 {
+            0x4000000000000000
+        } else {
+            sig_b
+        };
+        sig_b = shift_right_jam(sig_b, exp_diff as u32);
+        sig_a |= 0x4000000000000000;
+        exp_z = exp_a;
+        sig_z = sig_a - sig_b;
+    }
+    norm_round_pack(sign_z, exp_z - 1, sig_z, mode)
+}
+
+fn norm_round_pack(sign: bool, exp: i32, sig: u64, mode: u32) -> u64 {
+    let shift = sig.leading_zeros() as i32 - 1;
+    let exp = exp - shift;
+    if shift >= 10 && (exp as u32) < 0x7FD {
+        let e = if sig != 0 { exp } else { 0 };
+        pack(sign, e, sig << (shift - 10))
+    } else {
+        round_pack(sign, exp, sig << shift, mode)
+    }
+}
@@ -68,6 +68,8 @@ pub struct ScInstr<'a> {
 	pub src: i32,
 	pub mod_v: u8,
 	pub imm32: u32,
+	/// `randomx_reciprocal(imm32)` for IMUL_RCP, computed once on generation
+	pub reciprocal: u64,
 	pub op_group: ScOpcode,
 	pub op_group_par: i32,
 	pub can_reuse: bool,
@@ -82,6 +84,7 @@ impl ScInstr<'_> {
 			src: -1,
 			mod_v: 0,
 			imm32: 0,
+			reciprocal: 0,
 			op_group: ScOpcode::INVALID,
 			can_reuse: false,
 			group_par_is_source: false,
@@ -222,6 +225,7 @@ impl ScInstr<'_> {
 				src: -1,
 				mod_v: 0,
 				imm32: 0,
+				reciprocal: 0,
 				op_group: ScOpcode::IADD_RS,
 				can_reuse: false,
 				group_par_is_source: true,
@@ -233,6 +237,7 @@ impl ScInstr<'_> {
 				src: -1,
 				mod_v: 0,
 				imm32: 0,
+				reciprocal: 0,
 				op_group: ScOpcode::IXOR_R,
 				can_reuse: false,
 				group_par_is_source: true,
@@ -244,6 +249,7 @@ impl ScInstr<'_> {
 				src: -1,
 				mod_v: gen.get_byte(),
 				imm32: 0,
+				reciprocal: 0,
 				op_group: ScOpcode::IADD_RS,
 				can_reuse: false,
 				group_par_is_source: true,
@@ -255,6 +261,7 @@ impl ScInstr<'_> {
 				src: -1,
 				mod_v: 0,
 				imm32: 0,
+				reciprocal: 0,
 				op_group: ScOpcode::IMUL_R,
 				can_reuse: false,
 				group_par_is_source: true,
@@ -272,6 +279,7 @@ impl ScInstr<'_> {
 					src: -1,
 					mod_v: 0,
 					imm32: imm32 as u32,
+					reciprocal: 0,
 					op_group: ScOpcode::IROR_C,
 					can_reuse: false,
 					group_par_is_source: true,
@@ -284,6 +292,7 @@ impl ScInstr<'_> {
 				src: -1,
 				mod_v: 0,
 				imm32: gen.get_u32(),
+				reciprocal: 0,
 				op_group: ScOpcode::IADD_C7,
 				can_reuse: false,
 				group_par_is_source: false,
@@ -295,6 +304,7 @@ impl ScInstr<'_> {
 				src: -1,
 				mod_v: 0,
 				imm32: gen.get_u32(),
+				reciprocal: 0,
 				op_group: ScOpcode::IXOR_C7,
 				can_reuse: false,
 				group_par_is_source: false,
@@ -306,6 +316,7 @@ impl ScInstr<'_> {
 				src: -1,
 				mod_v: 0,
 				imm32: 0,
+				reciprocal: 0,
 				op_group: ScOpcode::IMULH_R,
 				group_par_is_source: true,
 				can_reuse: false,
@@ -317,6 +328,7 @@ impl ScInstr<'_> {
 				src: -1,
 				mod_v: 0,
 				imm32: 0,
+				reciprocal: 0,
 				op_group: ScOpcode::ISMULH_R,
 				group_par_is_source: true,
 				can_reuse: false,
@@ -334,6 +346,7 @@ impl ScInstr<'_> {
 					src: -1,
 					mod_v: 0,
 					imm32,
+					reciprocal: randomx_reciprocal(imm32 as u64),
 					op_group: ScOpcode::IMUL_RCP,
 					can_reuse: false,
 					group_par_is_source: true,
@@ -855,7 +868,7 @@ impl ScProgram<'_> {
 				ScOpcode::IMULH_R => ds[dst] = mulh(ds[dst], ds[src]),
 				ScOpcode::ISMULH_R => ds[dst] = smulh(ds[dst], ds[src]),
 				ScOpcode::IMUL_RCP => {
-					ds[dst] = ds[dst].wrapping_mul(randomx_reciprocal(instr.imm32 as u64))
+					ds[dst] = ds[dst].wrapping_mul(instr.reciprocal)
 				}
 				ScOpcode::COUNT => panic!("COUNT execution tried"),
 				ScOpcode::INVALID => panic!("INVALLID execution tried"),
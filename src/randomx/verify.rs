@@ -0,0 +1,199 @@
+//! Recomputes the hash of a submitted share in light mode, independent of the
+//! mining path: the dataset items are computed by the superscalar interpreter,
+//! never by the compiled programs that built the mining dataset. The seed memory
+//! of the last seed hash is kept, so verifying shares of the same seed only costs
+//! the hash itself.
+
+use super::memory::VmMemory;
+use super::params::{RandomxParams, RX_0};
+use super::vm::{new_jit_vm, new_vm, Vm};
+use crate::byte_string;
+use crate::stratum::stratum_data::Share;
+use crate::worker::target::Target;
+use crate::worker::worker_pool::with_nonce;
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Hex chars of the blob up to the end of the nonce
+const BLOB_MIN_LEN: usize = 86;
+const NONCE_LEN: usize = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    InvalidSeedHash(String),
+    InvalidBlob(String),
+    InvalidNonce(String),
+    InvalidTarget(String),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::InvalidSeedHash(v) => write!(f, "invalid seed hash {:?}", v),
+            VerifyError::InvalidBlob(v) => write!(f, "invalid blob {:?}", v),
+            VerifyError::InvalidNonce(v) => write!(f, "invalid nonce {:?}", v),
+            VerifyError::InvalidTarget(v) => write!(f, "invalid target {:?}", v),
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShareVerification {
+    /// the recomputed hash (hex)
+    pub hash: String,
+    /// the recomputed hash is the submitted one
+    pub hash_matches: bool,
+    /// the recomputed hash is below the job target
+    pub meets_target: bool,
+}
+
+impl ShareVerification {
+    pub fn is_valid(&self) -> bool {
+        self.hash_matches && self.meets_target
+    }
+}
+
+/// seed hash, params and light mode memory built for them
+type SeedEntry = (String, RandomxParams, Arc<VmMemory>);
+
+/// Light mode seed memory of the last seed hash, can be shared by the verifiers
+/// of several threads (clones share the memory).
+#[derive(Clone, Default)]
+pub struct VerifierMemory {
+    current: Arc<Mutex<Option<SeedEntry>>>,
+    /// held while a seed memory is built, `current` stays available meanwhile
+    building: Arc<Mutex<()>>,
+}
+
+impl VerifierMemory {
+    /// The memory for `seed_hash` and `params`. Building a new one takes a few
+    /// seconds, only threads waiting for the same memory are blocked meanwhile.
+    pub fn get(&self, seed_hash: &str, params: &RandomxParams) -> Arc<VmMemory> {
+        if let Some(memory) = self.current(seed_hash, params) {
+            return memory;
+        }
+        let _building = self.building.lock().unwrap_or_else(|e| e.into_inner());
+        //built by another thread while waiting
+        if let Some(memory) = self.current(seed_hash, params) {
+            return memory;
+        }
+        info!(
+            "building seed memory for share verification, seed {}",
+            seed_hash
+        );
+        let key = byte_string::string_to_u8_array(seed_hash);
+        let memory = Arc::new(VmMemory::light_with_params(&key, params, false));
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        *current = Some((seed_hash.to_string(), *params, memory.clone()));
+        memory
+    }
+
+    fn current(&self, seed_hash: &str, params: &RandomxParams) -> Option<Arc<VmMemory>> {
+        let current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        match &*current {
+            Some((seed, p, memory)) if seed == seed_hash && p == params => Some(memory.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps the VM of the last seed hash and variant, it is only rebuilt for shares of
+/// another seed. The seed memory can be shared with other verifiers (`with_memory`).
+pub struct ShareVerifier {
+    memory: VerifierMemory,
+    params: RandomxParams,
+    jit: bool,
+    /// seed hash and params of the VM
+    vm: Option<(String, RandomxParams, Vm)>,
+}
+
+impl Default for ShareVerifier {
+    fn default() -> Self {
+        ShareVerifier::new()
+    }
+}
+
+impl ShareVerifier {
+    pub fn new() -> ShareVerifier {
+        ShareVerifier::with_memory(VerifierMemory::default(), true)
+    }
+
+    /// Runs the programs in the interpreter, independent of the JIT used for mining.
+    pub fn interpreter() -> ShareVerifier {
+        ShareVerifier::with_memory(VerifierMemory::default(), false)
+    }
+
+    /// A verifier using the seed memories of `memory`, the programs are compiled if
+    /// `jit` is set.
+    pub fn with_memory(memory: VerifierMemory, jit: bool) -> ShareVerifier {
+        ShareVerifier {
+            memory,
+            params: RX_0,
+            jit,
+            vm: None,
+        }
+    }
+
+    /// Switches the RandomX variant (rx/0 by default) of the following verifications.
+    pub fn set_params(&mut self, params: RandomxParams) {
+        self.params = params;
+    }
+
+    /// Verifies `share` for the job with `seed_hash`, `blob` and `target` (all hex, as
+    /// in `StratumAction::Job`). The seed memory and the VM are only rebuilt if
+    /// `seed_hash` or the variant differ from the previous call.
+    pub fn verify(
+        &mut self,
+        seed_hash: &str,
+        blob: &str,
+        target: &str,
+        share: &Share,
+    ) -> Result<ShareVerification, VerifyError> {
+        if seed_hash.is_empty() || !is_hex(seed_hash) {
+            return Err(VerifyError::InvalidSeedHash(seed_hash.to_string()));
+        }
+        if blob.len() < BLOB_MIN_LEN || !is_hex(blob) {
+            return Err(VerifyError::InvalidBlob(blob.to_string()));
+        }
+        if share.nonce.len() != NONCE_LEN || !is_hex(&share.nonce) {
+            return Err(VerifyError::InvalidNonce(share.nonce.clone()));
+        }
+        let share_target =
+            Target::from_hex(target).map_err(|_| VerifyError::InvalidTarget(target.to_string()))?;
+
+        let input = byte_string::string_to_u8_array(&with_nonce(blob, &share.nonce));
+        let hash = self.vm(seed_hash).calculate_hash(&input);
+        let meets_target = share_target.is_met_by(hash.as_bytes());
+        let hash = hash.to_hex().to_string();
+
+        Ok(ShareVerification {
+            hash_matches: hash.eq_ignore_ascii_case(&share.hash),
+            meets_target,
+            hash,
+        })
+    }
+
+    fn vm(&mut self, seed_hash: &str) -> &mut Vm {
+        let params = self.params;
+        let current = matches!(&self.vm, Some((seed, p, _)) if seed == seed_hash && *p == params);
+        if !current {
+            let memory = self.memory.get(seed_hash, &self.params);
+            let vm = if self.jit {
+                new_jit_vm(memory)
+            } else {
+                new_vm(memory)
+            };
+            self.vm = Some((seed_hash.to_string(), self.params, vm));
+        }
+        let (_, _, vm) = self.vm.as_mut().expect("verifier vm");
+        vm
+    }
+}
+
+fn is_hex(s: &str) -> bool {
+    s.len().is_multiple_of(2) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
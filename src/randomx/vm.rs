@@ -1,35 +1,24 @@
 extern crate blake2b_simd;
 
 use self::blake2b_simd::{blake2b, Hash, Params};
-use super::common::{mulh, randomx_reciprocal, smulh, u64_from_i32_imm};
-use super::hash::{fill_aes_1rx4_u64, gen_program_aes_4rx4, hash_aes_1rx4};
+use super::bytecode::{ByteInstr, Bytecode};
+use super::common::{mulh, smulh};
+use super::hash::{
+    fill_aes_1rx4_u64, gen_program_aes_4rx4_with_keys, hash_aes_1rx4, hash_and_fill_aes_1rx4,
+};
+use super::jit::JitProgram;
 use super::m128::{m128d, m128i};
-use super::memory::{VmMemory, CACHE_LINE_SIZE};
-use super::program::{Instr, Mode, Program, Store, MAX_FLOAT_REG, MAX_REG};
-use std::arch::x86_64::{_mm_getcsr, _mm_setcsr};
-use std::convert::TryInto;
+use super::memory::{MemoryRegion, VmMemory, CACHE_LINE_SIZE};
+use super::params::RandomxParams;
+use super::program::{Opcode, Program, MAX_FLOAT_REG, MAX_REG};
+use super::rounding::{self, FpStateGuard};
+use super::soft_float;
+use std::error::Error;
+use std::fmt;
 use std::sync::Arc;
 
-pub const SCRATCHPAD_L1_MASK: u64 = 0x3ff8;
-pub const SCRATCHPAD_L2_MASK: u64 = 0x3fff8;
-pub const SCRATCHPAD_L3_MASK: u64 = 0x1ffff8;
-const SCRATCHPAD_L3_MASK_U32: u32 = 0x1fffc0;
-
-const SCRATCHPAD_SIZE: usize = 262144;
-const MXCSR_DEFAULT: u32 = 0x9FC0;
-const CONDITION_OFFSET: u64 = 8;
-const CONDITION_MASK: u64 = (1 << CONDITION_OFFSET) - 1;
-
-const RANDOMX_PROGRAM_COUNT: usize = 8;
-const RANDOMX_PROGRAM_SIZE: i32 = 256;
-const RANDOMX_PROGRAM_ITERATIONS: usize = 2048;
-const RANDOMX_DATASET_BASE_SIZE: usize = 2147483648;
-const RANDOMX_DATASET_ITEM_SIZE: usize = 64;
-const RANDOMX_DATASET_EXTRA_SIZE: usize = 33554368;
 const RANDOMX_HASH_SIZE: usize = 32;
 
-const DATASET_EXTRA_ITEMS: usize = RANDOMX_DATASET_EXTRA_SIZE / RANDOMX_DATASET_ITEM_SIZE;
-
 const MANTISSA_SIZE: u64 = 52;
 const MANTISSA_MASK: u64 = (1 << MANTISSA_SIZE) - 1;
 const EXPONENT_SIZE: u64 = 11;
@@ -38,16 +27,27 @@ const EXPONENT_MASK: u64 = (1 << EXPONENT_SIZE) - 1;
 const EXPONENT_BITS: u64 = 0x300;
 const DYNAMIC_EXPONENT_BITS: u64 = 4;
 const STATIC_EXPONENT_BITS: u64 = 4;
-const DYNAMIC_MANTISSA_MASK: u64 = (1 << (MANTISSA_SIZE + DYNAMIC_EXPONENT_BITS)) - 1;
+pub const DYNAMIC_MANTISSA_MASK: u64 = (1 << (MANTISSA_SIZE + DYNAMIC_EXPONENT_BITS)) - 1;
+
+/// `calculate_hash_next` or `calculate_hash_last` without a series started by
+/// `calculate_hash_first`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoPendingHash;
+
+impl fmt::Display for NoPendingHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("no pending hash, calculate_hash_first not called")
+    }
+}
 
-const CACHE_LINE_ALIGN_MASK: u64 =
-    ((RANDOMX_DATASET_BASE_SIZE - 1) & !(RANDOMX_DATASET_ITEM_SIZE - 1)) as u64;
+impl Error for NoPendingHash {}
 
 pub struct MemoryRegister {
     pub mx: usize,
     pub ma: usize,
 }
 
+#[repr(C)]
 pub struct Register {
     pub r: [u64; MAX_REG as usize],
     pub f: [m128d; MAX_FLOAT_REG as usize],
@@ -116,36 +116,53 @@ pub struct VmConfig {
 pub struct Vm {
     pub mem_reg: MemoryRegister,
     pub reg: Register,
-    pub scratchpad: Vec<u64>,
-    pub pc: i32,
+    pub scratchpad: MemoryRegion,
     pub config: VmConfig,
     pub mem: Arc<VmMemory>,
+    /// Parameters of the RandomX variant, the ones of `mem`
+    pub params: RandomxParams,
     pub dataset_offset: u64,
+    /// Compiled mode if set, the program is interpreted otherwise
+    pub jit: Option<JitProgram>,
+    /// Current rounding mode (fprc register)
+    pub fprc: u32,
+    /// Emulate the rounding modes in software instead of using the FPU rounding control,
+    /// the compiled mode is not used if set
+    pub soft_float: bool,
+    /// Program seed of the pending input of a pipelined series, its scratchpad is filled
+    pipeline_seed: Option<[m128i; 4]>,
+    /// Program of the current round in the interpreter, reused between rounds
+    bytecode: Bytecode,
 }
 
 impl Vm {
     pub fn init_vm(&mut self, prog: &Program) {
+        self.init_vm_entropy(&prog.entropy);
+    }
+
+    /// Initialises the registers and the configuration from the program `entropy`.
+    pub fn init_vm_entropy(&mut self, entropy: &[u64]) {
         self.reg.a[0] = m128d::from_u64(
-            small_positive_float_bit(prog.entropy[1]),
-            small_positive_float_bit(prog.entropy[0]),
+            small_positive_float_bit(entropy[1]),
+            small_positive_float_bit(entropy[0]),
         );
         self.reg.a[1] = m128d::from_u64(
-            small_positive_float_bit(prog.entropy[3]),
-            small_positive_float_bit(prog.entropy[2]),
+            small_positive_float_bit(entropy[3]),
+            small_positive_float_bit(entropy[2]),
         );
         self.reg.a[2] = m128d::from_u64(
-            small_positive_float_bit(prog.entropy[5]),
-            small_positive_float_bit(prog.entropy[4]),
+            small_positive_float_bit(entropy[5]),
+            small_positive_float_bit(entropy[4]),
         );
         self.reg.a[3] = m128d::from_u64(
-            small_positive_float_bit(prog.entropy[7]),
-            small_positive_float_bit(prog.entropy[6]),
+            small_positive_float_bit(entropy[7]),
+            small_positive_float_bit(entropy[6]),
         );
 
-        self.mem_reg.ma = ((prog.entropy[8] & CACHE_LINE_ALIGN_MASK) as u32) as usize;
-        self.mem_reg.mx = (prog.entropy[10] as u32) as usize;
+        self.mem_reg.ma = ((entropy[8] & self.params.cache_line_align_mask()) as u32) as usize;
+        self.mem_reg.mx = (entropy[10] as u32) as usize;
 
-        let mut address_reg = prog.entropy[12] as usize;
+        let mut address_reg = entropy[12] as usize;
         self.config.read_reg[0] = address_reg & 1;
         address_reg >>= 1;
         self.config.read_reg[1] = 2 + (address_reg & 1);
@@ -155,10 +172,10 @@ impl Vm {
         self.config.read_reg[3] = 6 + (address_reg & 1);
 
         self.dataset_offset =
-            (prog.entropy[13] % (DATASET_EXTRA_ITEMS as u64 + 1)) * CACHE_LINE_SIZE;
+            (entropy[13] % (self.params.dataset_extra_items() + 1)) * CACHE_LINE_SIZE;
 
-        self.config.e_mask[0] = float_mask(prog.entropy[14]);
-        self.config.e_mask[1] = float_mask(prog.entropy[15]);
+        self.config.e_mask[0] = float_mask(entropy[14]);
+        self.config.e_mask[1] = float_mask(entropy[15]);
 
         for i in 0..MAX_REG {
             self.reg.r[i] = 0;
@@ -169,21 +186,65 @@ impl Vm {
         fill_aes_1rx4_u64(seed, &mut self.scratchpad)
     }
 
+    /// Restores the floating point state of the calling thread on return.
+    /// Aborts a pipelined series started with `calculate_hash_first`.
     pub fn calculate_hash(&mut self, input: &[u8]) -> Hash {
-        let hash = blake2b(input);
-        let seed = hash_to_m128i_array(&hash);
+        let _fp_state = FpStateGuard::save();
+        self.pipeline_seed = None;
+        let seed = hash_to_m128i_array(&blake2b(input));
+        let program_seed = self.init_scratchpad(&seed);
+
+        self.run_programs(program_seed);
+        let final_hash = hash_aes_1rx4(&self.scratchpad);
+        self.finish_hash(final_hash)
+    }
+
+    /// Starts a pipelined series of hashes (`randomx_calculate_hash_first`), the hash of
+    /// `input` is returned by the following `calculate_hash_next` or `calculate_hash_last`.
+    pub fn calculate_hash_first(&mut self, input: &[u8]) {
+        let seed = hash_to_m128i_array(&blake2b(input));
+        self.pipeline_seed = Some(self.init_scratchpad(&seed));
+    }
+
+    /// Returns the hash of the previous input and starts `next_input`. The scratchpad of
+    /// `next_input` is filled in the same pass as the final scratchpad hash of the previous
+    /// input. Fails if no series was started with `calculate_hash_first`.
+    pub fn calculate_hash_next(&mut self, next_input: &[u8]) -> Result<Hash, NoPendingHash> {
+        let program_seed = self.pipeline_seed.take().ok_or(NoPendingHash)?;
+        let _fp_state = FpStateGuard::save();
+
+        self.run_programs(program_seed);
+        let mut fill_state = hash_to_m128i_array(&blake2b(next_input));
+        let final_hash = hash_and_fill_aes_1rx4(&mut self.scratchpad, &mut fill_state);
+        self.pipeline_seed = Some(fill_state);
+        Ok(self.finish_hash(final_hash))
+    }
 
-        let mut tmp_hash = self.init_scratchpad(&seed);
+    /// Returns the hash of the previous input and ends the series.
+    /// Fails if no series was started with `calculate_hash_first`.
+    pub fn calculate_hash_last(&mut self) -> Result<Hash, NoPendingHash> {
+        let program_seed = self.pipeline_seed.take().ok_or(NoPendingHash)?;
+        let _fp_state = FpStateGuard::save();
+
+        self.run_programs(program_seed);
+        let final_hash = hash_aes_1rx4(&self.scratchpad);
+        Ok(self.finish_hash(final_hash))
+    }
+
+    /// Runs the `program_count` chained programs on the filled scratchpad.
+    fn run_programs(&mut self, program_seed: [m128i; 4]) {
         self.reset_rounding_mode();
 
-        for _ in 0..(RANDOMX_PROGRAM_COUNT - 1) {
+        let mut tmp_hash = program_seed;
+        for _ in 0..(self.params.program_count - 1) {
             self.run(&tmp_hash);
             let blake_result = blake2b(&self.reg.to_bytes());
             tmp_hash = hash_to_m128i_array(&blake_result);
         }
-
         self.run(&tmp_hash);
-        let final_hash = hash_aes_1rx4(&self.scratchpad);
+    }
+
+    fn finish_hash(&mut self, final_hash: [m128i; 4]) -> Hash {
         self.reg.a[0] = final_hash[0].as_m128d();
         self.reg.a[1] = final_hash[1].as_m128d();
         self.reg.a[2] = final_hash[2].as_m128d();
@@ -194,23 +255,43 @@ impl Vm {
         params.hash(&self.reg.to_bytes())
     }
 
-    /// Runs one round
+    /// Runs one round. The rounding mode is taken from and written back to `fprc`,
+    /// the floating point state of the calling thread is restored on return.
     pub fn run(&mut self, seed: &[m128i; 4]) {
-        let prog = Program::from_bytes(gen_program_aes_4rx4(seed, 136));
+        let _fp_state = FpStateGuard::save();
+        self.set_rounding_mode(self.fprc);
 
-        self.init_vm(&prog);
+        let bytes = gen_program_aes_4rx4_with_keys(
+            seed,
+            self.params.program_bytes_len(),
+            &self.params.program_keys,
+        );
+        let compiled = self.jit.is_some() && !self.soft_float;
+        let mut bytecode = std::mem::take(&mut self.bytecode);
+        if compiled {
+            let prog = Program::from_bytes(bytes, &self.params);
+            self.init_vm(&prog);
+            if let Some(jit) = &mut self.jit {
+                jit.compile(&prog, &self.config, &self.params);
+            }
+        } else {
+            bytecode.decode(&bytes, &self.params);
+            self.init_vm_entropy(&bytecode.entropy);
+        }
 
+        let sp_addr_mask = self.params.scratchpad_l3_mask64() as u32;
+        let dataset_mask = self.params.cache_line_align_mask() as usize;
         let mut sp_addr_0: u32 = self.mem_reg.mx as u32;
         let mut sp_addr_1: u32 = self.mem_reg.ma as u32;
 
-        for _ in 0..RANDOMX_PROGRAM_ITERATIONS {
+        for _ in 0..self.params.program_iterations {
             let sp_mix = self.reg.r[self.config.read_reg[0]] ^ self.reg.r[self.config.read_reg[1]];
 
             sp_addr_0 ^= sp_mix as u32;
-            sp_addr_0 &= SCRATCHPAD_L3_MASK_U32;
+            sp_addr_0 &= sp_addr_mask;
             sp_addr_0 /= 8;
             sp_addr_1 ^= (sp_mix >> 32) as u32;
-            sp_addr_1 &= SCRATCHPAD_L3_MASK_U32;
+            sp_addr_1 &= sp_addr_mask;
             sp_addr_1 /= 8;
 
             for i in 0..MAX_REG {
@@ -227,16 +308,14 @@ impl Vm {
                 );
             }
 
-            self.pc = 0;
-            while self.pc < RANDOMX_PROGRAM_SIZE {
-                let instr = &prog.program[self.pc as usize];
-                instr.execute(self);
-                self.pc += 1;
+            match &self.jit {
+                Some(jit) if compiled => jit.execute(&mut self.reg, &mut self.scratchpad),
+                _ => self.execute_bytecode(&bytecode.code),
             }
 
             self.mem_reg.mx ^= (self.reg.r[self.config.read_reg[2]]
                 ^ self.reg.r[self.config.read_reg[3]]) as usize;
-            self.mem_reg.mx &= CACHE_LINE_ALIGN_MASK as usize;
+            self.mem_reg.mx &= dataset_mask;
             self.mem.dataset_prefetch(self.mem_reg.mx as u64);
             self.mem.dataset_read(
                 self.dataset_offset + self.mem_reg.ma as u64,
@@ -261,310 +340,202 @@ impl Vm {
             sp_addr_0 = 0;
             sp_addr_1 = 0;
         }
-    }
-
-    pub fn reset_rounding_mode(&mut self) {
-        unsafe {
-            _mm_setcsr(MXCSR_DEFAULT);
-        }
-    }
-
-    pub fn set_rounding_mode(&mut self, mode: u32) {
-        unsafe { _mm_setcsr(MXCSR_DEFAULT | (mode << 13)) }
-    }
-
-    pub fn get_rounding_mode(&self) -> u32 {
-        unsafe { (_mm_getcsr() >> 13) & 3 }
-    }
-
-    //f...
-
-    pub fn exec_fswap_r(&mut self, instr: &Instr) {
-        let v_dst = self.read_float_reg(&instr.dst);
-        self.write_float_reg(&instr.dst, v_dst.shuffle_1(&v_dst));
-    }
-
-    pub fn exec_fadd_r(&mut self, instr: &Instr) {
-        let v_src = self.read_a(&instr.src);
-        let v_dst = self.read_f(&instr.dst);
-        self.write_f(&instr.dst, v_src + v_dst);
-    }
-
-    pub fn exec_fadd_m(&mut self, instr: &Instr) {
-        let v = self.scratchpad[self.scratchpad_src_ix(instr)];
-        let v_src = m128i::from_u64(0, v).lower_to_m128d();
-        let v_dst = self.read_f(&instr.dst);
-        self.write_f(&instr.dst, v_dst + v_src);
-    }
-
-    pub fn exec_fsub_r(&mut self, instr: &Instr) {
-        let v_src = self.read_a(&instr.src);
-        let v_dst = self.read_f(&instr.dst);
-        self.write_f(&instr.dst, v_dst - v_src);
-    }
-
-    pub fn exec_fsub_m(&mut self, instr: &Instr) {
-        let v = self.scratchpad[self.scratchpad_src_ix(instr)];
-        let v_src = m128i::from_u64(0, v).lower_to_m128d();
-        let v_dst = self.read_f(&instr.dst);
-        self.write_f(&instr.dst, v_dst - v_src);
-    }
-
-    pub fn exec_fscal_r(&mut self, instr: &Instr) {
-        let v_dst = self.read_f(&instr.dst);
-        let mask = m128d::from_u64(0x80F0000000000000, 0x80F0000000000000);
-        self.write_f(&instr.dst, v_dst ^ mask);
-    }
-
-    pub fn exec_fmul_r(&mut self, instr: &Instr) {
-        let v_src = self.read_a(&instr.src);
-        let v_dst = self.read_e(&instr.dst);
-        self.write_e(&instr.dst, v_src * v_dst);
-    }
-
-    pub fn exec_fsqrt_r(&mut self, instr: &Instr) {
-        let v_dst = self.read_e(&instr.dst);
-        self.write_e(&instr.dst, v_dst.sqrt());
-    }
-
-    pub fn exec_fdiv_m(&mut self, instr: &Instr) {
-        let v = self.scratchpad[self.scratchpad_src_ix(instr)];
-        let v_src = self.mask_register_exponent_mantissa(m128i::from_u64(0, v).lower_to_m128d());
-        let v_dst = self.read_e(&instr.dst);
-        self.write_e(&instr.dst, v_dst / v_src);
-    }
-
-    //i...
-
-    pub fn exec_iadd_m(&mut self, instr: &Instr) {
-        let ix = self.scratchpad_src_ix(instr);
-        self.write_r(
-            &instr.dst,
-            self.read_r(&instr.dst).wrapping_add(self.scratchpad[ix]),
-        );
-    }
-
-    pub fn exec_isub_m(&mut self, instr: &Instr) {
-        let ix = self.scratchpad_src_ix(instr);
-        self.write_r(
-            &instr.dst,
-            self.read_r(&instr.dst).wrapping_sub(self.scratchpad[ix]),
-        );
-    }
-
-    pub fn exec_imul_m(&mut self, instr: &Instr) {
-        let ix = self.scratchpad_src_ix(instr);
-        self.write_r(
-            &instr.dst,
-            self.read_r(&instr.dst).wrapping_mul(self.scratchpad[ix]),
-        );
-    }
-    pub fn exec_iadd_rs(&mut self, instr: &Instr) {
-        let mut v = self.read_r(&instr.src) << shift_mode(instr);
-        if let Some(imm) = instr.imm {
-            v = v.wrapping_add(u64_from_i32_imm(imm));
+        self.bytecode = bytecode;
+        self.fprc = self.get_rounding_mode();
+    }
+
+    /// Interprets a program decoded by `Bytecode::decode` once.
+    pub fn execute_bytecode(&mut self, code: &[ByteInstr]) {
+        let mut pc = 0;
+        while pc < code.len() {
+            let instr = &code[pc];
+            pc += 1;
+            let dst = instr.dst as usize;
+            let src = instr.src as usize;
+            match instr.op {
+                Opcode::IADD_RS => {
+                    let v = (self.reg.r[src] << instr.shift).wrapping_add(instr.imm);
+                    self.reg.r[dst] = self.reg.r[dst].wrapping_add(v);
+                }
+                Opcode::IADD_M => {
+                    let v = self.scratchpad[self.bytecode_src_ix(instr)];
+                    self.reg.r[dst] = self.reg.r[dst].wrapping_add(v);
+                }
+                Opcode::ISUB_R => {
+                    let v = (self.reg.r[src] & instr.src_mask) | instr.imm;
+                    self.reg.r[dst] = self.reg.r[dst].wrapping_sub(v);
+                }
+                Opcode::ISUB_M => {
+                    let v = self.scratchpad[self.bytecode_src_ix(instr)];
+                    self.reg.r[dst] = self.reg.r[dst].wrapping_sub(v);
+                }
+                Opcode::IMUL_R => {
+                    let v = (self.reg.r[src] & instr.src_mask) | instr.imm;
+                    self.reg.r[dst] = self.reg.r[dst].wrapping_mul(v);
+                }
+                Opcode::IMUL_M => {
+                    let v = self.scratchpad[self.bytecode_src_ix(instr)];
+                    self.reg.r[dst] = self.reg.r[dst].wrapping_mul(v);
+                }
+                Opcode::IMULH_R => {
+                    self.reg.r[dst] = mulh(self.reg.r[src], self.reg.r[dst]);
+                }
+                Opcode::IMULH_M => {
+                    let v = self.scratchpad[self.bytecode_src_ix(instr)];
+                    self.reg.r[dst] = mulh(v, self.reg.r[dst]);
+                }
+                Opcode::ISMULH_R => {
+                    self.reg.r[dst] = smulh(self.reg.r[src], self.reg.r[dst]);
+                }
+                Opcode::ISMULH_M => {
+                    let v = self.scratchpad[self.bytecode_src_ix(instr)];
+                    self.reg.r[dst] = smulh(v, self.reg.r[dst]);
+                }
+                Opcode::IMUL_RCP => {
+                    self.reg.r[dst] = self.reg.r[dst].wrapping_mul(instr.imm);
+                }
+                Opcode::INEG_R => {
+                    self.reg.r[dst] = self.reg.r[dst].wrapping_neg();
+                }
+                Opcode::IXOR_R => {
+                    self.reg.r[dst] ^= (self.reg.r[src] & instr.src_mask) | instr.imm;
+                }
+                Opcode::IXOR_M => {
+                    self.reg.r[dst] ^= self.scratchpad[self.bytecode_src_ix(instr)];
+                }
+                Opcode::IROR_R => {
+                    let v = (self.reg.r[src] & instr.src_mask) | instr.imm;
+                    self.reg.r[dst] = self.reg.r[dst].rotate_right((v & 0xFFFFFF) as u32);
+                }
+                Opcode::IROL_R => {
+                    let v = (self.reg.r[src] & instr.src_mask) | instr.imm;
+                    self.reg.r[dst] = self.reg.r[dst].rotate_left((v & 0xFFFFFF) as u32);
+                }
+                Opcode::ISWAP_R => {
+                    self.reg.r.swap(dst, src);
+                }
+                Opcode::FSWAP_R => {
+                    let v = if src == 0 {
+                        &mut self.reg.f[dst]
+                    } else {
+                        &mut self.reg.e[dst]
+                    };
+                    *v = v.shuffle_1(&*v);
+                }
+                Opcode::FADD_R => {
+                    self.reg.f[dst] = self.fp_add(self.reg.a[src], self.reg.f[dst]);
+                }
+                Opcode::FADD_M => {
+                    let v = self.scratchpad[self.bytecode_src_ix(instr)];
+                    let v_src = m128i::from_u64(0, v).lower_to_m128d();
+                    self.reg.f[dst] = self.fp_add(self.reg.f[dst], v_src);
+                }
+                Opcode::FSUB_R => {
+                    self.reg.f[dst] = self.fp_sub(self.reg.f[dst], self.reg.a[src]);
+                }
+                Opcode::FSUB_M => {
+                    let v = self.scratchpad[self.bytecode_src_ix(instr)];
+                    let v_src = m128i::from_u64(0, v).lower_to_m128d();
+                    self.reg.f[dst] = self.fp_sub(self.reg.f[dst], v_src);
+                }
+                Opcode::FSCAL_R => {
+                    let mask = m128d::from_u64(0x80F0000000000000, 0x80F0000000000000);
+                    self.reg.f[dst] = self.reg.f[dst] ^ mask;
+                }
+                Opcode::FMUL_R => {
+                    self.reg.e[dst] = self.fp_mul(self.reg.a[src], self.reg.e[dst]);
+                }
+                Opcode::FDIV_M => {
+                    let v = self.scratchpad[self.bytecode_src_ix(instr)];
+                    let v_src = self
+                        .mask_register_exponent_mantissa(m128i::from_u64(0, v).lower_to_m128d());
+                    self.reg.e[dst] = self.fp_div(self.reg.e[dst], v_src);
+                }
+                Opcode::FSQRT_R => {
+                    self.reg.e[dst] = self.fp_sqrt(self.reg.e[dst]);
+                }
+                Opcode::CBRANCH => {
+                    let v = self.reg.r[dst].wrapping_add(instr.imm);
+                    self.reg.r[dst] = v;
+                    if v & instr.mask == 0 {
+                        pc = instr.target as usize;
+                    }
+                }
+                Opcode::CFROUND => {
+                    let mode = self.reg.r[src].rotate_right(instr.imm as u32) % 4;
+                    self.set_rounding_mode(mode as u32);
+                }
+                Opcode::ISTORE => {
+                    let addr = self.reg.r[dst].wrapping_add(instr.imm) & instr.mask;
+                    self.scratchpad[(addr / 8) as usize] = self.reg.r[src];
+                }
+                Opcode::NOP => {}
+            }
         }
-        self.write_r(&instr.dst, self.read_r(&instr.dst).wrapping_add(v));
-    }
-    pub fn exec_isub_r(&mut self, instr: &Instr) {
-        let v = self.imm_or_r(instr);
-        self.write_r(&instr.dst, self.read_r(&instr.dst).wrapping_sub(v));
-    }
-
-    pub fn exec_imul_r(&mut self, instr: &Instr) {
-        let v = self.imm_or_r(instr);
-        self.write_r(&instr.dst, self.read_r(&instr.dst).wrapping_mul(v));
-    }
-
-    pub fn exec_imul_rcp(&mut self, instr: &Instr) {
-        if !is_zero_or_power_of_2(instr.imm.unwrap() as u64) {
-            let v = randomx_reciprocal((instr.imm.unwrap() as u64) & 0x00000000FFFFFFFF);
-            self.write_r(&instr.dst, self.read_r(&instr.dst).wrapping_mul(v));
-        } //else: nop
-    }
-
-    pub fn exec_imulh_r(&mut self, instr: &Instr) {
-        let v_src = self.read_r(&instr.src);
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, mulh(v_src, v_dst));
     }
 
-    pub fn exec_imulh_m(&mut self, instr: &Instr) {
-        let v_dst = self.read_r(&instr.dst);
-        let v_src = self.scratchpad[self.scratchpad_src_ix(instr)];
-        self.write_r(&instr.dst, mulh(v_src, v_dst));
+    pub fn is_jit(&self) -> bool {
+        self.jit.is_some()
     }
 
-    pub fn exec_ismulh_r(&mut self, instr: &Instr) {
-        let v_src = self.read_r(&instr.src);
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, smulh(v_src, v_dst));
-    }
-
-    pub fn exec_ismulh_m(&mut self, instr: &Instr) {
-        let v_src = self.scratchpad[self.scratchpad_src_ix(instr)];
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, smulh(v_src, v_dst));
-    }
-
-    pub fn exec_ineg_r(&mut self, instr: &Instr) {
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, (!v_dst).wrapping_add(1));
-    }
-
-    pub fn exec_ixor_r(&mut self, instr: &Instr) {
-        let v_src = self.imm_or_r(instr);
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, v_dst ^ v_src);
-    }
-
-    pub fn exec_ixor_m(&mut self, instr: &Instr) {
-        let v_src = self.scratchpad[self.scratchpad_src_ix(instr)];
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, v_dst ^ v_src);
-    }
-
-    pub fn exec_iror_r(&mut self, instr: &Instr) {
-        let v_src = (self.imm_or_r(instr) & 0xFFFFFF) as u32;
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, v_dst.rotate_right(v_src));
-    }
-
-    pub fn exec_irol_r(&mut self, instr: &Instr) {
-        let v_src = (self.imm_or_r(instr) & 0xFFFFFF) as u32;
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, v_dst.rotate_left(v_src));
-    }
-
-    pub fn exec_iswap_r(&mut self, instr: &Instr) {
-        let v_src = self.read_r(&instr.src);
-        let v_dst = self.read_r(&instr.dst);
-        self.write_r(&instr.dst, v_src);
-        self.write_r(&instr.src, v_dst);
-    }
-
-    pub fn exec_istore(&mut self, instr: &Instr) {
-        let ix = self.scratchpad_dst_ix(instr);
-        self.scratchpad[ix] = self.read_r(&instr.src);
-    }
-
-    //c..
-
-    pub fn exec_cfround(&mut self, instr: &Instr) {
-        let v_src = self.read_r(&instr.src);
-        let mode = (v_src.rotate_right(instr.imm.unwrap() as u32) % 4) as u32;
-        self.set_rounding_mode(mode);
+    pub fn reset_rounding_mode(&mut self) {
+        self.set_rounding_mode(rounding::ROUND_TO_NEAREST);
     }
 
-    pub fn exec_cbranch(&mut self, instr: &Instr) {
-        let shift = cond_mode(instr) as u64 + CONDITION_OFFSET;
-        let mut imm = u64_from_i32_imm(instr.imm.unwrap()) | 1 << shift;
-        if CONDITION_OFFSET > 0 || shift > 0 {
-            imm &= !(1 << (shift - 1));
-        }
-        let v_dst = self.read_r(&instr.dst).wrapping_add(imm);
-        self.write_r(&instr.dst, v_dst);
-        if v_dst & (CONDITION_MASK << shift) == 0 {
-            self.pc = instr.target.unwrap();
+    pub fn set_rounding_mode(&mut self, mode: u32) {
+        self.fprc = mode;
+        if !self.soft_float {
+            rounding::set_rounding_mode(mode);
         }
     }
 
-    //helper
-
-    fn imm_or_r(&self, instr: &Instr) -> u64 {
-        if instr.src == Store::NONE {
-            return instr.imm.unwrap() as u64;
+    pub fn get_rounding_mode(&self) -> u32 {
+        if self.soft_float {
+            return self.fprc;
         }
-        self.read_r(&instr.src)
+        rounding::get_rounding_mode()
     }
 
-    fn read_float_reg(&self, store: &Store) -> m128d {
-        match store {
-            Store::A(i) => self.reg.a[*i],
-            Store::E(i) => self.reg.e[*i],
-            Store::F(i) => self.reg.f[*i],
-            _ => panic!("illegal read from float register"),
-        }
-    }
+    //float arithmetic, either with the FPU or emulated
 
-    fn write_float_reg(&mut self, store: &Store, v: m128d) {
-        match store {
-            Store::A(i) => self.reg.a[*i] = v,
-            Store::E(i) => self.reg.e[*i] = v,
-            Store::F(i) => self.reg.f[*i] = v,
-            _ => panic!("illegal write to float register"),
+    fn fp_add(&self, a: m128d, b: m128d) -> m128d {
+        if self.soft_float {
+            return soft_float::add_pd(a, b, self.fprc);
         }
+        a + b
     }
 
-    fn read_r(&self, store: &Store) -> u64 {
-        match store {
-            Store::R(i) => self.reg.r[*i],
-            _ => panic!("illegal read from register r"),
-        }
-    }
-    fn write_r(&mut self, store: &Store, v: u64) {
-        match store {
-            Store::R(i) => self.reg.r[*i] = v,
-            _ => panic!("illegal store to register r"),
-        }
-    }
-    fn read_f(&self, store: &Store) -> m128d {
-        match store {
-            Store::F(i) => self.reg.f[*i],
-            _ => panic!("illegal read from register f"),
-        }
-    }
-    fn write_f(&mut self, store: &Store, v: m128d) {
-        match store {
-            Store::F(i) => self.reg.f[*i] = v,
-            _ => panic!("illegal store to register f"),
+    fn fp_sub(&self, a: m128d, b: m128d) -> m128d {
+        if self.soft_float {
+            return soft_float::sub_pd(a, b, self.fprc);
         }
+        a - b
     }
 
-    fn read_a(&self, store: &Store) -> m128d {
-        match store {
-            Store::A(i) => self.reg.a[*i],
-            _ => panic!("illegal read from register a"),
+    fn fp_mul(&self, a: m128d, b: m128d) -> m128d {
+        if self.soft_float {
+            return soft_float::mul_pd(a, b, self.fprc);
         }
+        a * b
     }
 
-    fn read_e(&self, store: &Store) -> m128d {
-        match store {
-            Store::E(i) => self.reg.e[*i],
-            _ => panic!("illegal read from register e"),
+    fn fp_div(&self, a: m128d, b: m128d) -> m128d {
+        if self.soft_float {
+            return soft_float::div_pd(a, b, self.fprc);
         }
+        a / b
     }
 
-    fn write_e(&mut self, store: &Store, v: m128d) {
-        match store {
-            Store::E(i) => self.reg.e[*i] = v,
-            _ => panic!("illegal store to register e"),
-        }
-    }
-    fn scratchpad_src_ix(&self, instr: &Instr) -> usize {
-        let imm = u64_from_i32_imm(instr.imm.unwrap());
-        let addr: usize = match &instr.src {
-            Store::L1(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L1_MASK,
-            Store::L2(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L2_MASK,
-            Store::L3(_) => imm & SCRATCHPAD_L3_MASK,
-            _ => panic!("illegal read from scratchpad"),
+    fn fp_sqrt(&self, a: m128d) -> m128d {
+        if self.soft_float {
+            return soft_float::sqrt_pd(a, self.fprc);
         }
-        .try_into()
-        .unwrap();
-        addr / 8
+        a.sqrt()
     }
 
-    fn scratchpad_dst_ix(&self, instr: &Instr) -> usize {
-        let imm = u64_from_i32_imm(instr.imm.unwrap());
-        let addr: usize = match &instr.dst {
-            Store::L1(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L1_MASK,
-            Store::L2(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L2_MASK,
-            Store::L3(d) => (self.read_r(d).wrapping_add(imm)) & SCRATCHPAD_L3_MASK,
-            _ => panic!("illegal read from scratchpad"),
-        }
-        .try_into()
-        .unwrap();
-        addr / 8
+    fn bytecode_src_ix(&self, instr: &ByteInstr) -> usize {
+        let addr = (self.reg.r[instr.src as usize] & instr.src_mask).wrapping_add(instr.imm);
+        ((addr & instr.mask) / 8) as usize
     }
 
     fn mask_register_exponent_mantissa(&self, v: m128d) -> m128d {
@@ -583,20 +554,6 @@ pub fn hash_to_m128i_array(hash: &Hash) -> [m128i; 4] {
     [i1, i2, i3, i4]
 }
 
-fn shift_mode(instr: &Instr) -> u8 {
-    match instr.mode {
-        Mode::Shft(x) => x,
-        _ => panic!("illegal shift mode {}", instr.mode),
-    }
-}
-
-fn cond_mode(instr: &Instr) -> u8 {
-    match instr.mode {
-        Mode::Cond(x) => x,
-        _ => panic!("illegal cond mode {}", instr.mode),
-    }
-}
-
 pub fn is_zero_or_power_of_2(imm: u64) -> bool {
     imm & imm.wrapping_sub(1) == 0
 }
@@ -621,17 +578,37 @@ fn static_exponent(entropy: u64) -> u64 {
     exponent << MANTISSA_SIZE
 }
 
+/// Creates a Vm for the RandomX variant of `mem`.
 pub fn new_vm(mem: Arc<VmMemory>) -> Vm {
+    let params = *mem.params();
+    let scratchpad = MemoryRegion::new(params.scratchpad_len());
+    debug!("scratchpad allocated with {}", scratchpad.page_kind());
     Vm {
         mem_reg: MemoryRegister { mx: 0, ma: 0 },
         reg: new_register(),
-        scratchpad: vec![0; SCRATCHPAD_SIZE],
-        pc: 0,
+        scratchpad,
         config: VmConfig {
             e_mask: [0; 2],
             read_reg: [0; 4],
         },
         mem,
+        params,
         dataset_offset: 0,
+        jit: None,
+        fprc: 0,
+        soft_float: false,
+        pipeline_seed: None,
+        bytecode: Bytecode::new(),
+    }
+}
+
+/// Creates a Vm running in compiled mode. Falls back to the interpreter
+/// if no executable memory could be allocated.
+pub fn new_jit_vm(mem: Arc<VmMemory>) -> Vm {
+    let mut vm = new_vm(mem);
+    vm.jit = JitProgram::new();
+    if vm.jit.is_none() {
+        warn!("could not allocate executable memory for jit, falling back to interpreter");
     }
+    vm
 }
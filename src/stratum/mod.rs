@@ -18,6 +18,7 @@ pub enum StratumCmd {
     Login {},
     SubmitShare { share: stratum_data::Share },
     KeepAlive { miner_id: String },
+    GetJob { miner_id: String },
     Shutdown {},
 }
 
@@ -27,6 +28,7 @@ pub enum StratumAction {
     Job {
         miner_id: String,
         seed_hash: String,
+        next_seed_hash: Option<String>,
         blob: String,
         job_id: String,
         target: String,
@@ -248,6 +250,13 @@ pub fn submit_share(
     tx.send(StratumCmd::SubmitShare { share })
 }
 
+pub fn request_job(tx: &Sender<StratumCmd>, miner_id: &str) -> Result<(), SendError<StratumCmd>> {
+    info!("nonce space exhausted, requesting a new job");
+    tx.send(StratumCmd::GetJob {
+        miner_id: miner_id.to_string(),
+    })
+}
+
 fn handle_stratum_send(
     rx: &Receiver<StratumCmd>,
     mut writer: BufWriter<TcpStream>,
@@ -258,6 +267,7 @@ fn handle_stratum_send(
             StratumCmd::Login {} => do_stratum_login(&mut writer, pool_conf)?,
             StratumCmd::SubmitShare { share } => do_stratum_submit_share(&mut writer, share)?,
             StratumCmd::KeepAlive { miner_id } => do_stratum_keep_alive(&mut writer, miner_id)?,
+            StratumCmd::GetJob { miner_id } => do_stratum_get_job(&mut writer, miner_id)?,
             StratumCmd::Shutdown {} => {
                 info!("stopping stratum send thread");
                 break;
@@ -280,6 +290,19 @@ fn do_stratum_keep_alive(writer: &mut BufWriter<TcpStream>, miner_id: String) ->
     Ok(())
 }
 
+fn do_stratum_get_job(writer: &mut BufWriter<TcpStream>, miner_id: String) -> Result<(), Error> {
+    let get_job_req = stratum_data::GetJobRequest {
+        id: 1,
+        method: "getjob".to_string(),
+        params: stratum_data::GetJobParams { id: miner_id },
+    };
+
+    let json = serde_json::to_string(&get_job_req).expect("marshaling getjob json");
+    writeln!(writer, "{}", json)?;
+    writer.flush()?;
+    Ok(())
+}
+
 fn do_stratum_submit_share(
     writer: &mut BufWriter<TcpStream>,
     share: stratum_data::Share,
@@ -404,6 +427,7 @@ pub fn parse_line_dispatch_result(
                                 job:
                                     stratum_data::Job {
                                         seed_hash,
+                                        next_seed_hash,
                                         blob,
                                         job_id,
                                         target,
@@ -416,6 +440,7 @@ pub fn parse_line_dispatch_result(
                             action = StratumAction::Job {
                                 miner_id: miner_id.clone(),
                                 seed_hash,
+                                next_seed_hash,
                                 blob,
                                 job_id,
                                 target,
@@ -429,8 +454,16 @@ pub fn parse_line_dispatch_result(
                         }
                     }
                     Err(e) => {
-                        action = StratumAction::Error {
-                            err: format!("{:?}, json received {}", e, line),
+                        //job requested with getjob
+                        let requested: Result<stratum_data::GetJobResponse, serde_json::Error> =
+                            serde_json::from_str(line);
+                        action = match requested {
+                            Ok(stratum_data::GetJobResponse { result: job, .. }) => {
+                                requested_job(job, miner_id_mutx)
+                            }
+                            Err(_) => StratumAction::Error {
+                                err: format!("{:?}, json received {}", e, line),
+                            },
                         }
                     }
                 }
@@ -454,23 +487,32 @@ fn parse_job(line: &str, miner_id_mutx: &Arc<Mutex<Option<String>>>) -> StratumA
     let miner_id = miner_id_guard.clone().expect("miner_id clone");
 
     match result {
-        Ok(stratum_data::JobResponse {
-            params:
-                stratum_data::Job {
-                    seed_hash,
-                    blob,
-                    job_id,
-                    target,
-                },
-        }) => StratumAction::Job {
-            miner_id,
-            seed_hash,
-            blob,
-            job_id,
-            target,
-        },
+        Ok(stratum_data::JobResponse { params: job }) => job_action(miner_id, job),
         _ => StratumAction::Error {
             err: "Error parsing job response".to_string(),
         },
     }
 }
+
+fn requested_job(
+    job: stratum_data::Job,
+    miner_id_mutx: &Arc<Mutex<Option<String>>>,
+) -> StratumAction {
+    match &*miner_id_mutx.lock().expect("miner_id lock") {
+        Some(miner_id) => job_action(miner_id.clone(), job),
+        None => StratumAction::Error {
+            err: "job received before login".to_string(),
+        },
+    }
+}
+
+fn job_action(miner_id: String, job: stratum_data::Job) -> StratumAction {
+    StratumAction::Job {
+        miner_id,
+        seed_hash: job.seed_hash,
+        next_seed_hash: job.next_seed_hash,
+        blob: job.blob,
+        job_id: job.job_id,
+        target: job.target,
+    }
+}
@@ -1,6 +1,8 @@
 extern crate serde;
 extern crate serde_json;
 
+use crate::randomx::params::RandomxParams;
+
 /// For checking the method in the json content and parsing further
 #[derive(Deserialize, Debug)]
 pub struct Method {
@@ -21,6 +23,8 @@ pub struct ErrorResult {
 #[derive(Deserialize)]
 pub struct Job {
     pub seed_hash: String,
+    /// seed hash of the next epoch, only sent by some pools
+    pub next_seed_hash: Option<String>,
     pub blob: String,
     pub job_id: String,
     pub target: String,
@@ -56,6 +60,13 @@ pub struct JobResponse {
     pub params: Job,
 }
 
+/// Response to a `getjob` request
+#[derive(Deserialize)]
+pub struct GetJobResponse {
+    pub id: u32,
+    pub result: Job,
+}
+
 #[derive(Serialize)]
 pub struct LoginParams {
     pub login: String,
@@ -81,6 +92,18 @@ pub struct KeepAliveRequest {
     pub params: KeepAliveParams,
 }
 
+#[derive(Serialize)]
+pub struct GetJobParams {
+    pub id: String,
+}
+
+#[derive(Serialize)]
+pub struct GetJobRequest {
+    pub id: u32,
+    pub method: String,
+    pub params: GetJobParams,
+}
+
 #[derive(Serialize)]
 pub struct SubmitParams {
     pub id: String,
@@ -109,4 +132,6 @@ pub struct PoolConfig {
     pub pool_address: String,
     pub wallet_address: String,
     pub pool_password: String,
+    /// RandomX variant mined on the pool
+    pub algorithm: RandomxParams,
 }
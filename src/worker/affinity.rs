@@ -0,0 +1,177 @@
+//! Pinning of the worker threads to CPUs (`worker.affinity`). RandomX is sensitive
+//! to sharing the L2/L3 cache and the core with an SMT sibling, `Auto` places one
+//! thread per physical core before using the siblings. `Auto` only uses the CPUs
+//! of the process affinity mask (containers, taskset).
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Topology of the CPUs, `/sys/devices/system/cpu` on Linux
+pub const SYSFS_CPU_ROOT: &str = "/sys/devices/system/cpu";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Affinity {
+    /// threads are placed by the OS
+    None,
+    /// one thread per physical core first, read from the sysfs topology
+    Auto,
+    /// thread i runs on the CPU id at i (repeated if there are more threads)
+    Cpus(Vec<usize>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuTopology {
+    pub cpu: usize,
+    pub package: usize,
+    pub core: usize,
+}
+
+/// The online CPUs below the sysfs `root`, ordered by CPU id.
+pub fn read_topology(root: &Path) -> io::Result<Vec<CpuTopology>> {
+    let mut cpus = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let cpu = match name.to_str().and_then(|n| n.strip_prefix("cpu")) {
+            Some(id) => match id.parse() {
+                Ok(cpu) => cpu,
+                Err(_) => continue, //cpufreq, cpuidle
+            },
+            None => continue,
+        };
+        let dir = entry.path();
+        //cpu0 usually has no online file, it can not be taken offline
+        if read_number(&dir.join("online")).is_ok_and(|online| online == 0) {
+            continue;
+        }
+        let topology = dir.join("topology");
+        cpus.push(CpuTopology {
+            cpu,
+            package: read_number(&topology.join("physical_package_id"))?,
+            core: read_number(&topology.join("core_id"))?,
+        });
+    }
+    if cpus.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no CPUs in {:?}", root),
+        ));
+    }
+    cpus.sort_by_key(|c| c.cpu);
+    Ok(cpus)
+}
+
+/// CPU ids in the order threads are placed by `Auto`: the first CPU of each
+/// physical core, then the second ones (SMT siblings) and so on.
+pub fn auto_cpu_order(cpus: &[CpuTopology]) -> Vec<usize> {
+    let mut cores: Vec<((usize, usize), Vec<usize>)> = Vec::new();
+    for cpu in cpus {
+        let key = (cpu.package, cpu.core);
+        match cores.iter_mut().find(|(core, _)| *core == key) {
+            Some((_, siblings)) => siblings.push(cpu.cpu),
+            None => cores.push((key, vec![cpu.cpu])),
+        }
+    }
+
+    let max_siblings = cores.iter().map(|(_, s)| s.len()).max().unwrap_or(0);
+    let mut order = Vec::with_capacity(cpus.len());
+    for level in 0..max_siblings {
+        order.extend(cores.iter().filter_map(|(_, s)| s.get(level)));
+    }
+    order
+}
+
+/// The CPU of each of the `threads` worker threads, None if it is not pinned.
+/// `Auto` only places threads on the `allowed` CPUs (`allowed_cpus`) if given.
+pub fn thread_cpus(
+    affinity: &Affinity,
+    threads: u64,
+    sysfs_root: &Path,
+    allowed: Option<&[usize]>,
+) -> Vec<Option<usize>> {
+    let cpus = match affinity {
+        Affinity::None => Vec::new(),
+        Affinity::Cpus(cpus) => cpus.clone(),
+        Affinity::Auto => match read_topology(sysfs_root) {
+            Ok(mut topology) => {
+                if let Some(allowed) = allowed {
+                    topology.retain(|c| allowed.contains(&c.cpu));
+                }
+                if topology.is_empty() {
+                    warn!("no CPU of the topology in the affinity mask, threads not pinned");
+                }
+                auto_cpu_order(&topology)
+            }
+            Err(err) => {
+                warn!("CPU topology not available ({}), threads not pinned", err);
+                Vec::new()
+            }
+        },
+    };
+    (0..threads as usize)
+        .map(|i| {
+            if cpus.is_empty() {
+                None
+            } else {
+                Some(cpus[i % cpus.len()])
+            }
+        })
+        .collect()
+}
+
+/// The CPUs in the affinity mask of the calling thread, None if it is not available.
+#[cfg(target_os = "linux")]
+pub fn allowed_cpus() -> Option<Vec<usize>> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return None;
+        }
+        Some(
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|cpu| libc::CPU_ISSET(*cpu, &set))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn allowed_cpus() -> Option<Vec<usize>> {
+    None
+}
+
+/// Restricts the calling thread to `cpu`.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpu: usize) -> io::Result<()> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("CPU id {} too large", cpu),
+        ));
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "thread affinity is only supported on Linux",
+    ))
+}
+
+fn read_number(path: &Path) -> io::Result<usize> {
+    let content = fs::read_to_string(path)?;
+    content
+        .trim()
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", path)))
+}
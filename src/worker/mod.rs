@@ -1 +1,3 @@
+pub mod affinity;
+pub mod target;
 pub mod worker_pool;
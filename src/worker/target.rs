@@ -0,0 +1,121 @@
+//! Share targets and their difficulty. Pools send the target as little-endian
+//! hex, either 8 chars (compact 32 bit target, scaled to 64 bit) or 16 chars
+//! (the full 64 bit target, e.g. for high difficulties or solo mining).
+//! A hash meets the target if its last 8 bytes (little-endian) are below it.
+
+use crate::byte_string;
+
+use std::error::Error;
+use std::fmt;
+
+const COMPACT_HEX_LEN: usize = 8;
+const FULL_HEX_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Target(u64);
+
+/// Expected number of hashes per share, `u64::MAX / target`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Difficulty(u64);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TargetError {
+    /// not 8 or 16 hex chars
+    InvalidLength(String),
+    InvalidHex(String),
+    /// no hash is below a zero target
+    Zero,
+}
+
+impl fmt::Display for TargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetError::InvalidLength(v) => write!(f, "target {:?} is not 8 or 16 hex chars", v),
+            TargetError::InvalidHex(v) => write!(f, "target {:?} is not hex", v),
+            TargetError::Zero => write!(f, "target is zero"),
+        }
+    }
+}
+
+impl Error for TargetError {}
+
+impl Target {
+    /// Parses the 8 or 16 hex chars target of a job.
+    pub fn from_hex(hex: &str) -> Result<Target, TargetError> {
+        if hex.len() != COMPACT_HEX_LEN && hex.len() != FULL_HEX_LEN {
+            return Err(TargetError::InvalidLength(hex.to_string()));
+        }
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(TargetError::InvalidHex(hex.to_string()));
+        }
+        let value = byte_string::hex2_u64_le(hex);
+        if value == 0 {
+            return Err(TargetError::Zero);
+        }
+        if hex.len() == COMPACT_HEX_LEN {
+            //same scaling as the reference miners, u32::MAX becomes u64::MAX
+            return Ok(Target(u64::MAX / (u64::from(u32::MAX) / value)));
+        }
+        Ok(Target(value))
+    }
+
+    pub fn from_value(value: u64) -> Target {
+        Target(value)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    /// True if the 32 byte `hash` is below the target.
+    pub fn is_met_by(&self, hash: &[u8]) -> bool {
+        hash_value(hash) < self.0
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty(u64::MAX / self.0.max(1))
+    }
+
+    /// The full 64 bit target as little-endian hex (16 chars).
+    pub fn to_hex(&self) -> String {
+        byte_string::u8_array_to_string(&self.0.to_le_bytes())
+    }
+}
+
+impl Difficulty {
+    pub fn new(difficulty: u64) -> Difficulty {
+        Difficulty(difficulty)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    /// The target of the difficulty, difficulty 0 is treated as 1.
+    pub fn target(&self) -> Target {
+        Target(u64::MAX / self.0.max(1))
+    }
+}
+
+impl From<Difficulty> for Target {
+    fn from(difficulty: Difficulty) -> Target {
+        difficulty.target()
+    }
+}
+
+impl From<Target> for Difficulty {
+    fn from(target: Target) -> Difficulty {
+        target.difficulty()
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The value of a 32 byte hash compared with the target (last 8 bytes, little-endian)
+pub fn hash_value(hash: &[u8]) -> u64 {
+    u64::from_le_bytes(hash[24..32].try_into().expect("32 byte hash"))
+}
@@ -1,28 +1,184 @@
 extern crate crossbeam_channel;
 
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 use self::crossbeam_channel::{unbounded, Receiver, Sender};
 use super::super::byte_string;
-use super::super::randomx::memory::{VmMemory, VmMemoryAllocator};
-use super::super::randomx::vm::new_vm;
+use super::super::metric::MetricEvent;
+use super::super::randomx::memory::{MemoryMode, VmMemory, VmMemoryAllocator};
+use super::super::randomx::verify::{ShareVerifier, VerifierMemory};
+use super::super::randomx::vm::{new_jit_vm, new_vm};
 use super::super::stratum;
 use super::super::stratum::stratum_data;
+use super::affinity::{allowed_cpus, pin_current_thread, thread_cpus, Affinity, SYSFS_CPU_ROOT};
+use super::target::Target;
 
 pub struct WorkerPool {
     thread_chan: Vec<Sender<WorkerCmd>>,
     thread_hnd: Vec<thread::JoinHandle<()>>,
+    /// id of each thread, reported on `stopped_rcvr`
+    thread_ids: Vec<u64>,
+    next_thread_id: u64,
+    /// Threads that stopped on their own (`ShareErrorAction::Stop`), has to be passed to
+    /// `thread_stopped`
+    pub stopped_rcvr: Receiver<u64>,
+    /// threads retired by `resize`, finishing their current hash
+    retired_hnd: Vec<thread::JoinHandle<()>>,
+    setup: ThreadSetup,
     pub vm_memory_allocator: VmMemoryAllocator,
+    /// job waiting for the memory of its seed, the workers continue with the previous job meanwhile
+    pending_job: Option<PendingJob>,
+    /// job the threads are working on, joined by the threads added in `resize`
+    current_job: Option<CurrentJob>,
+    /// each thread gets its own contiguous range of nonces instead of sharing one counter
+    pub nonce_ranges: bool,
 }
 
+#[derive(Clone)]
+struct PendingJob {
+    miner_id: String,
+    seed_hash: String,
+    blob: String,
+    blob_bytes: Vec<u8>,
+    job_id: String,
+    target: String,
+    share_target: Target,
+}
+
+struct CurrentJob {
+    job: PendingJob,
+    memory: Arc<VmMemory>,
+    nonce_space: NonceSpace,
+    /// next nonce index and end of each thread, the same counters for all threads without `nonce_ranges`
+    nonces: Vec<(Arc<AtomicU64>, Arc<AtomicU64>)>,
+    /// ranges left by removed threads, continued by the first thread done with its own
+    spare_nonces: Arc<Mutex<Vec<Range<u64>>>>,
+    active_threads: Arc<AtomicU64>,
+}
+
+/// Settings of the worker threads, kept to start more threads in `resize`
+struct ThreadSetup {
+    jit: bool,
+    affinity: Affinity,
+    share_sndr: Sender<stratum::StratumCmd>,
+    metric_resolution: u64,
+    metric_sndr: Sender<MetricEvent>,
+    share_check: Option<ShareCheck>,
+    stopped_sndr: Sender<u64>,
+}
+
+/// Remaining nonce indices a range needs to be split for an added thread
+const MIN_STEAL_NONCES: u64 = 1 << 16;
+
 #[derive(Clone)]
 pub struct WorkerConfig {
     pub num_threads: u64,
     pub auto_tune: bool,
     pub auto_tune_interval_minutes: u64,
     pub auto_tune_log: String,
+    pub jit: bool,
+    pub eager_dataset: bool,
+    /// None: same as the number of worker threads
+    pub dataset_init_threads: Option<u64>,
+    /// `Auto` is resolved at startup with the available memory
+    pub memory_mode: MemoryMode,
+    /// each worker thread gets its own contiguous nonce range
+    pub nonce_ranges: bool,
+    pub affinity: Affinity,
+    pub huge_pages: bool,
+    /// recompute found shares in light mode before submitting them
+    pub verify_shares: bool,
+    /// share error rate above which `share_error_action` is taken
+    pub max_share_error_rate: f64,
+    pub share_error_action: ShareErrorAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShareErrorAction {
+    /// the thread continues with a new VM (scratchpad and JIT buffer)
+    Restart,
+    Stop,
+}
+
+/// Shares are only rated after this many verified shares of a thread
+pub const MIN_VERIFIED_SHARES: u64 = 10;
+
+/// Verification of found shares before submission, shared by the worker threads.
+/// The threads verify concurrently, only the seed memory is shared.
+#[derive(Clone)]
+pub struct ShareCheck {
+    pub memory: VerifierMemory,
+    pub max_error_rate: f64,
+    pub error_action: ShareErrorAction,
+    /// incremented for each share failing the verification
+    pub mismatches: Arc<AtomicU64>,
+}
+
+impl ShareCheck {
+    pub fn new(conf: &WorkerConfig, mismatches: Arc<AtomicU64>) -> ShareCheck {
+        ShareCheck {
+            memory: VerifierMemory::default(),
+            max_error_rate: conf.max_share_error_rate,
+            error_action: conf.share_error_action,
+            mismatches,
+        }
+    }
+
+    /// Verifier of a worker thread, keeps its VM between the verifications.
+    fn verifier(&self) -> ShareVerifier {
+        ShareVerifier::with_memory(self.memory.clone(), false)
+    }
+
+    /// True if the share is valid and can be submitted.
+    fn verify(
+        &self,
+        verifier: &mut ShareVerifier,
+        job: &JobData,
+        share: &stratum_data::Share,
+    ) -> bool {
+        verifier.set_params(*job.memory.params());
+        match verifier.verify(&job.seed_hash, &job.blob, &job.target, share) {
+            Ok(result) if result.is_valid() => true,
+            Ok(result) => {
+                self.mismatches.fetch_add(1, Ordering::SeqCst);
+                error!(
+                    "SHARE VERIFICATION FAILED, not submitted (job {}, nonce {}): hash {}, recomputed {}, meets target {}. Check the hardware (memory, overclocking)!",
+                    share.job_id, share.nonce, share.hash, result.hash, result.meets_target
+                );
+                false
+            }
+            Err(err) => {
+                self.mismatches.fetch_add(1, Ordering::SeqCst);
+                error!("share verification failed, not submitted: {}", err);
+                false
+            }
+        }
+    }
+}
+
+/// Share verification results of a worker thread
+#[derive(Default)]
+pub struct ShareErrors {
+    pub verified: u64,
+    pub failed: u64,
+}
+
+impl ShareErrors {
+    pub fn record(&mut self, valid: bool) {
+        self.verified += 1;
+        if !valid {
+            self.failed += 1;
+        }
+    }
+
+    pub fn rate_exceeded(&self, max_error_rate: f64) -> bool {
+        self.verified >= MIN_VERIFIED_SHARES
+            && self.failed as f64 / self.verified as f64 > max_error_rate
+    }
 }
 
 pub struct JobData {
@@ -30,55 +186,167 @@ pub struct JobData {
     pub seed_hash: String,
     pub memory: Arc<VmMemory>,
     pub blob: String,
+    /// `blob` decoded, the hash input once the nonce is written at `NONCE_OFFSET`
+    pub blob_bytes: Vec<u8>,
     pub job_id: String,
     pub target: String,
-    pub nonce: Arc<AtomicU32>,
+    /// `target` parsed
+    pub share_target: Target,
+    pub nonce_space: NonceSpace,
+    /// next nonce index of the thread, shared by all threads without `nonce_ranges`
+    pub nonce: Arc<AtomicU64>,
+    /// exclusive end of the nonce indices of the thread, lowered if `resize` hands
+    /// the upper part of the range to an added thread
+    pub nonce_end: Arc<AtomicU64>,
+    /// ranges of removed threads (`claim_nonce`), shared by all threads of the job
+    pub spare_nonces: Arc<Mutex<Vec<Range<u64>>>>,
+    /// threads still working on the job, the last one done requests a new job
+    pub active_threads: Arc<AtomicU64>,
+}
+
+/// The nonces of a job: the whole 32 bit nonce, or only 24 bit if the pool fixed
+/// one byte (NiceHash). Workers count nonce indices `0..size()`, mapped by `nonce`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NonceSpace {
+    /// last nonce byte of the blob (least significant byte of the nonce), set by the pool
+    pub fixed_byte: Option<u8>,
+}
+
+impl NonceSpace {
+    /// NiceHash pools set the last nonce byte in the blob, it is zero otherwise.
+    pub fn for_blob(blob: &[u8]) -> NonceSpace {
+        let fixed = blob[NONCE_OFFSET + NONCE_SIZE - 1];
+        NonceSpace {
+            fixed_byte: if fixed != 0 { Some(fixed) } else { None },
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        match self.fixed_byte {
+            Some(_) => 1 << 24,
+            None => 1 << 32,
+        }
+    }
+
+    /// The nonce with the index `ix` (< `size`)
+    pub fn nonce(&self, ix: u64) -> u32 {
+        match self.fixed_byte {
+            Some(fixed) => ((ix as u32) << 8) | fixed as u32,
+            None => ix as u32,
+        }
+    }
+
+    /// `threads` contiguous ranges of nonce indices covering the whole space
+    pub fn split(&self, threads: u64) -> Vec<Range<u64>> {
+        let len = self.size() / threads.max(1);
+        (0..threads)
+            .map(|i| {
+                let end = if i == threads - 1 {
+                    self.size()
+                } else {
+                    (i + 1) * len
+                };
+                i * len..end
+            })
+            .collect()
+    }
 }
 
 pub enum WorkerCmd {
-    NewJob { job_data: JobData },
+    NewJob {
+        job_data: JobData,
+    },
     Stop,
+    /// leave the current job and terminate, the pool continues with fewer threads
+    Retire,
 }
 
 enum WorkerExit {
     NonceSpaceExhausted,
     NewJob { job_data: JobData },
     Stopped,
+    Retired,
+    ShareErrorRateExceeded,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn start(
     num_threads: u64,
+    jit: bool,
+    affinity: &Affinity,
     share_sndr: &Sender<stratum::StratumCmd>,
     metric_resolution: u64,
-    metric_sndr: &Sender<u64>,
+    metric_sndr: &Sender<MetricEvent>,
     vm_memory_allocator: VmMemoryAllocator,
+    share_check: Option<ShareCheck>,
 ) -> WorkerPool {
-    let mut thread_chan: Vec<Sender<WorkerCmd>> = Vec::with_capacity(num_threads as usize);
-    let mut thread_hnd: Vec<thread::JoinHandle<()>> = Vec::with_capacity(num_threads as usize);
-    for i in 0..num_threads {
-        let (sndr, rcvr) = unbounded();
-        let share_sndr_thread = share_sndr.clone();
-        let metric_sndr_thread = metric_sndr.clone();
-
-        let hnd = thread::Builder::new()
-            .name(format!("worker thread {}", i))
-            .spawn(move || {
-                work(
-                    &rcvr,
-                    &share_sndr_thread,
-                    metric_resolution,
-                    &metric_sndr_thread,
-                )
-            })
-            .expect("worker thread handle");
-        thread_chan.push(sndr);
-        thread_hnd.push(hnd);
-    }
-    WorkerPool {
-        thread_chan,
-        thread_hnd,
+    let (stopped_sndr, stopped_rcvr) = unbounded();
+    let mut pool = WorkerPool {
+        thread_chan: Vec::with_capacity(num_threads as usize),
+        thread_hnd: Vec::with_capacity(num_threads as usize),
+        thread_ids: Vec::with_capacity(num_threads as usize),
+        next_thread_id: 0,
+        stopped_rcvr,
+        retired_hnd: Vec::new(),
+        setup: ThreadSetup {
+            jit,
+            affinity: affinity.clone(),
+            share_sndr: share_sndr.clone(),
+            metric_resolution,
+            metric_sndr: metric_sndr.clone(),
+            share_check,
+            stopped_sndr,
+        },
         vm_memory_allocator,
-    }
+        pending_job: None,
+        current_job: None,
+        nonce_ranges: false,
+    };
+    pool.add_threads(num_threads);
+    pool
+}
+
+fn spawn_worker(
+    setup: &ThreadSetup,
+    id: u64,
+    cpu: Option<usize>,
+) -> (Sender<WorkerCmd>, thread::JoinHandle<()>) {
+    let (sndr, rcvr) = unbounded();
+    let jit = setup.jit;
+    let share_sndr_thread = setup.share_sndr.clone();
+    let metric_resolution = setup.metric_resolution;
+    let metric_sndr_thread = setup.metric_sndr.clone();
+    let share_check_thread = setup.share_check.clone();
+    let stopped_sndr = setup.stopped_sndr.clone();
+
+    let hnd = thread::Builder::new()
+        .name(format!("worker thread {}", id))
+        .spawn(move || {
+            if let Some(cpu) = cpu {
+                match pin_current_thread(cpu) {
+                    Ok(()) => info!("worker thread {} pinned to CPU {}", id, cpu),
+                    Err(err) => {
+                        warn!(
+                            "pinning worker thread {} to CPU {} failed: {}",
+                            id, cpu, err
+                        )
+                    }
+                }
+            }
+            work(
+                &rcvr,
+                jit,
+                &share_sndr_thread,
+                metric_resolution,
+                &metric_sndr_thread,
+                share_check_thread.as_ref(),
+                &|| {
+                    let _ = stopped_sndr.send(id);
+                },
+            )
+        })
+        .expect("worker thread handle");
+    (sndr, hnd)
 }
 
 impl WorkerPool {
@@ -86,27 +354,206 @@ impl WorkerPool {
         &mut self,
         miner_id: &str,
         seed_hash: &str,
+        next_seed_hash: Option<&str>,
         blob: &str,
         job_id: &str,
         target: &str,
     ) {
         info!("job change, blob {}", blob);
-        self.vm_memory_allocator.reallocate(seed_hash.to_string());
-        let nonce = Arc::new(AtomicU32::new(0));
-
-        for (_, tx) in self.thread_chan.iter().enumerate() {
-            tx.send(WorkerCmd::NewJob {
-                job_data: JobData {
-                    miner_id: miner_id.to_string(),
-                    seed_hash: seed_hash.to_string(),
-                    memory: self.vm_memory_allocator.vm_memory.clone(),
-                    blob: blob.to_string(),
-                    job_id: job_id.to_string(),
-                    target: target.to_string(),
-                    nonce: nonce.clone(),
-                },
+        let blob_bytes = byte_string::string_to_u8_array(blob);
+        if blob_bytes.len() < NONCE_OFFSET + NONCE_SIZE {
+            error!("invalid job blob {}, job {} ignored", blob, job_id);
+            return;
+        }
+        let share_target = match Target::from_hex(target) {
+            Ok(share_target) => share_target,
+            Err(err) => {
+                error!("{}, job {} ignored", err, job_id);
+                return;
+            }
+        };
+        let job = PendingJob {
+            miner_id: miner_id.to_string(),
+            seed_hash: seed_hash.to_string(),
+            blob: blob.to_string(),
+            blob_bytes,
+            job_id: job_id.to_string(),
+            target: target.to_string(),
+            share_target,
+        };
+        let num_threads = self.thread_chan.len() as u64;
+        match self.vm_memory_allocator.memory_for(seed_hash, num_threads) {
+            Some(memory) => {
+                self.pending_job = None;
+                self.send_job(job, memory);
+            }
+            None => {
+                info!(
+                    "memory for seed_hash {} not ready, job postponed",
+                    seed_hash
+                );
+                self.pending_job = Some(job);
+            }
+        }
+        if let Some(next_seed_hash) = next_seed_hash {
+            self.vm_memory_allocator
+                .prepare(next_seed_hash, num_threads);
+        }
+    }
+
+    /// Has to be called with the memories received from `vm_memory_allocator.built_rcvr`,
+    /// starts the postponed job if its memory is ready now.
+    pub fn memory_ready(&mut self, seed_hash: String, memory: VmMemory) {
+        let num_threads = self.thread_chan.len() as u64;
+        self.vm_memory_allocator
+            .finished(seed_hash, memory, num_threads);
+
+        let ready = matches!(&self.pending_job, Some(job) if job.seed_hash == self.vm_memory_allocator.vm_memory_seed);
+        if ready {
+            let job = self.pending_job.take().expect("pending job");
+            let memory = self.vm_memory_allocator.vm_memory.clone();
+            self.send_job(job, memory);
+        }
+    }
+
+    fn send_job(&mut self, job: PendingJob, memory: Arc<VmMemory>) {
+        //no job for threads that stopped meanwhile, they would never finish it
+        self.remove_stopped_threads();
+        let nonce_space = NonceSpace::for_blob(&job.blob_bytes);
+        if let Some(fixed) = nonce_space.fixed_byte {
+            info!("nonce byte fixed by the pool to {:02x}", fixed);
+        }
+        let num_threads = self.thread_chan.len() as u64;
+        let ranges = if self.nonce_ranges {
+            nonce_space.split(num_threads)
+        } else {
+            vec![0..nonce_space.size(); num_threads as usize]
+        };
+        let shared_nonce = Arc::new(AtomicU64::new(0));
+        let shared_end = Arc::new(AtomicU64::new(nonce_space.size()));
+        let nonces = ranges
+            .into_iter()
+            .map(|range| {
+                if self.nonce_ranges {
+                    (
+                        Arc::new(AtomicU64::new(range.start)),
+                        Arc::new(AtomicU64::new(range.end)),
+                    )
+                } else {
+                    (shared_nonce.clone(), shared_end.clone())
+                }
             })
-            .expect("sending new job command");
+            .collect();
+        let current = CurrentJob {
+            job,
+            memory,
+            nonce_space,
+            nonces,
+            spare_nonces: Arc::new(Mutex::new(Vec::new())),
+            active_threads: Arc::new(AtomicU64::new(num_threads)),
+        };
+
+        for (i, tx) in self.thread_chan.iter().enumerate() {
+            let send_result = tx.send(WorkerCmd::NewJob {
+                job_data: current.job_data(i),
+            });
+            if send_result.is_err() {
+                //stopped because of its share error rate
+                warn!("worker thread stopped, job not sent");
+            }
+        }
+        self.current_job = Some(current);
+    }
+
+    pub fn num_threads(&self) -> u64 {
+        self.thread_chan.len() as u64
+    }
+
+    /// Starts or retires threads until `num_threads` (at least 1) are running.
+    /// Mining continues on the current job: added threads join it, retired
+    /// threads leave it after their current hash and the remaining threads
+    /// continue their nonces.
+    pub fn resize(&mut self, num_threads: u64) {
+        self.remove_stopped_threads();
+        let current = self.num_threads();
+        if num_threads == 0 || num_threads == current {
+            return;
+        }
+        info!(
+            "resizing worker pool from {} to {} threads",
+            current, num_threads
+        );
+        if num_threads > current {
+            self.add_threads(num_threads - current);
+            return;
+        }
+        for _ in num_threads..current {
+            let tx = self.thread_chan.pop().expect("worker thread");
+            let _ = tx.send(WorkerCmd::Retire);
+            let hnd = self.thread_hnd.pop().expect("worker thread handle");
+            self.retired_hnd.push(hnd);
+            self.thread_ids.pop();
+            let last = self.thread_chan.len();
+            if let Some(job) = &mut self.current_job {
+                job.remove_thread(last, self.nonce_ranges);
+            }
+        }
+    }
+
+    /// Has to be called with the ids received from `stopped_rcvr`, the thread no
+    /// longer gets jobs and is not counted by `num_threads`.
+    pub fn thread_stopped(&mut self, id: u64) {
+        let pos = match self
+            .thread_ids
+            .iter()
+            .position(|thread_id| *thread_id == id)
+        {
+            Some(pos) => pos,
+            None => return, //already removed
+        };
+        self.thread_chan.remove(pos);
+        self.thread_ids.remove(pos);
+        let hnd = self.thread_hnd.remove(pos);
+        self.retired_hnd.push(hnd);
+        if let Some(job) = &mut self.current_job {
+            job.remove_thread(pos, self.nonce_ranges);
+        }
+        warn!(
+            "worker thread {} stopped, {} threads left",
+            id,
+            self.thread_chan.len()
+        );
+    }
+
+    fn remove_stopped_threads(&mut self) {
+        while let Ok(id) = self.stopped_rcvr.try_recv() {
+            self.thread_stopped(id);
+        }
+    }
+
+    fn add_threads(&mut self, count: u64) {
+        let first = self.thread_chan.len();
+        let total = first + count as usize;
+        //the pool runs on the main thread, its mask is the one of the process
+        let cpus = thread_cpus(
+            &self.setup.affinity,
+            total as u64,
+            Path::new(SYSFS_CPU_ROOT),
+            allowed_cpus().as_deref(),
+        );
+        for (i, cpu) in cpus.into_iter().enumerate().skip(first) {
+            let id = self.next_thread_id;
+            self.next_thread_id += 1;
+            let (tx, hnd) = spawn_worker(&self.setup, id, cpu);
+            if let Some(job) = &mut self.current_job {
+                job.add_thread(self.nonce_ranges);
+                let _ = tx.send(WorkerCmd::NewJob {
+                    job_data: job.job_data(i),
+                });
+            }
+            self.thread_chan.push(tx);
+            self.thread_hnd.push(hnd);
+            self.thread_ids.push(id);
         }
     }
 
@@ -120,7 +567,7 @@ impl WorkerPool {
 
     //Waits for completing of all threads in the pool
     pub fn join(self) {
-        for hnd in self.thread_hnd {
+        for hnd in self.thread_hnd.into_iter().chain(self.retired_hnd) {
             let join_result = hnd.join();
             if join_result.is_err() {
                 error!("thread join failed {:?}, waiting for next", join_result)
@@ -129,11 +576,130 @@ impl WorkerPool {
     }
 }
 
+impl CurrentJob {
+    fn job_data(&self, thread: usize) -> JobData {
+        let (nonce, nonce_end) = &self.nonces[thread];
+        JobData {
+            miner_id: self.job.miner_id.clone(),
+            seed_hash: self.job.seed_hash.clone(),
+            memory: self.memory.clone(),
+            blob: self.job.blob.clone(),
+            blob_bytes: self.job.blob_bytes.clone(),
+            job_id: self.job.job_id.clone(),
+            target: self.job.target.clone(),
+            share_target: self.job.share_target,
+            nonce_space: self.nonce_space,
+            nonce: nonce.clone(),
+            nonce_end: nonce_end.clone(),
+            spare_nonces: self.spare_nonces.clone(),
+            active_threads: self.active_threads.clone(),
+        }
+    }
+
+    /// Nonces for a thread joining the job. With `nonce_ranges` it takes a range left
+    /// by a removed thread or the upper half of the largest remaining range, nothing
+    /// if all ranges are nearly done.
+    fn add_thread(&mut self, nonce_ranges: bool) {
+        self.active_threads.fetch_add(1, Ordering::SeqCst);
+        //held while splitting, a thread continuing with a spare range changes its counters
+        let mut spare = lock_spare(&self.spare_nonces);
+        let (start, end) = match self.nonces.first() {
+            Some(shared) if !nonce_ranges => {
+                self.nonces.push(shared.clone());
+                return;
+            }
+            _ => match spare.pop() {
+                Some(range) => (range.start, range.end),
+                None => match self
+                    .nonces
+                    .iter()
+                    .max_by_key(|(next, end)| remaining(next, end))
+                {
+                    Some((next, end)) => split_remaining(next, end),
+                    None => (0, self.nonce_space.size()),
+                },
+            },
+        };
+        drop(spare);
+        self.nonces.push((
+            Arc::new(AtomicU64::new(start)),
+            Arc::new(AtomicU64::new(end)),
+        ));
+    }
+
+    /// Removes the nonces of the thread at `pos`. With `nonce_ranges` the indices it
+    /// did not claim yet are continued by the remaining threads.
+    fn remove_thread(&mut self, pos: usize, nonce_ranges: bool) {
+        let (next, end) = self.nonces.remove(pos);
+        if nonce_ranges {
+            hand_over_remaining(&next, &end, &self.spare_nonces);
+        }
+    }
+}
+
+fn lock_spare(spare: &Mutex<Vec<Range<u64>>>) -> std::sync::MutexGuard<'_, Vec<Range<u64>>> {
+    spare.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn remaining(next: &AtomicU64, end: &AtomicU64) -> u64 {
+    end.load(Ordering::SeqCst)
+        .saturating_sub(next.load(Ordering::SeqCst))
+}
+
+/// Lowers `end` to the middle of the remaining indices and returns the upper half,
+/// an empty range if fewer than `MIN_STEAL_NONCES` remain.
+pub fn split_remaining(next: &AtomicU64, end: &AtomicU64) -> (u64, u64) {
+    let old_end = end.load(Ordering::SeqCst);
+    let left = remaining(next, end);
+    if left < MIN_STEAL_NONCES {
+        return (old_end, old_end);
+    }
+    let mid = old_end - left / 2;
+    end.store(mid, Ordering::SeqCst);
+    (mid, old_end)
+}
+
+/// Moves the indices of a removed thread that it did not claim yet to `spare`. Its
+/// `end` is set to 0, so it only finishes the nonces it already claimed.
+pub fn hand_over_remaining(next: &AtomicU64, end: &AtomicU64, spare: &Mutex<Vec<Range<u64>>>) {
+    let mut spare = lock_spare(spare);
+    let end = end.swap(0, Ordering::SeqCst);
+    let start = next.fetch_max(end, Ordering::SeqCst);
+    if start < end {
+        spare.push(start..end);
+    }
+}
+
+/// The next nonce index of a thread. Once its range is done it continues with a
+/// range of `spare`, None if there is none left or the thread was removed.
+pub fn claim_nonce(
+    next: &AtomicU64,
+    end: &AtomicU64,
+    spare: &Mutex<Vec<Range<u64>>>,
+) -> Option<u64> {
+    loop {
+        let ix = next.fetch_add(1, Ordering::SeqCst);
+        if ix < end.load(Ordering::SeqCst) {
+            return Some(ix);
+        }
+        let mut spare = lock_spare(spare);
+        if end.load(Ordering::SeqCst) == 0 {
+            return None; //removed by `hand_over_remaining`
+        }
+        let range = spare.pop()?;
+        end.store(range.end, Ordering::SeqCst);
+        next.store(range.start, Ordering::SeqCst);
+    }
+}
+
 fn work(
     rcv: &Receiver<WorkerCmd>,
+    jit: bool,
     share_tx: &Sender<stratum::StratumCmd>,
     metric_resolution: u64,
-    metric_tx: &Sender<u64>,
+    metric_tx: &Sender<MetricEvent>,
+    share_check: Option<&ShareCheck>,
+    report_stopped: &dyn Fn(),
 ) {
     let first_job = rcv.recv();
     if first_job.is_err() {
@@ -142,19 +708,32 @@ fn work(
     }
     let mut job = match first_job.unwrap() {
         WorkerCmd::NewJob { job_data } => job_data,
-        WorkerCmd::Stop => {
+        WorkerCmd::Stop | WorkerCmd::Retire => {
             info!("Worker immediately stopped");
             return;
         }
     };
 
+    let mut share_errors = ShareErrors::default();
+    let mut verifier = share_check.map(|check| check.verifier());
     loop {
-        let exit_reason = work_job(&job, rcv, share_tx, metric_resolution, metric_tx);
+        let exit_reason = work_job(
+            &job,
+            rcv,
+            jit,
+            share_tx,
+            metric_resolution,
+            metric_tx,
+            share_check
+                .zip(verifier.as_mut())
+                .map(|(check, verifier)| (check, verifier, &mut share_errors)),
+        );
         //if work_job returns the nonce space was exhausted or a new job was received.
         //In case the nonce space was exhausted, we have to wait blocking for a new job and "idle".
         match exit_reason {
             WorkerExit::NonceSpaceExhausted => {
                 warn!("nonce space exhausted, thread idle");
+                job_done(&job, share_tx);
                 let job_blocking = rcv.recv();
                 if job_blocking.is_err() {
                     error!("job channel was dropped");
@@ -162,57 +741,127 @@ fn work(
                 }
                 job = match job_blocking.unwrap() {
                     WorkerCmd::NewJob { job_data } => job_data,
-                    WorkerCmd::Stop => break, //Terminate thread
+                    WorkerCmd::Stop | WorkerCmd::Retire => break, //Terminate thread
                 };
             }
             WorkerExit::NewJob { job_data } => {
                 job = job_data;
             }
             WorkerExit::Stopped => break, //Terminate thread
+            WorkerExit::Retired => {
+                job_done(&job, share_tx);
+                break;
+            }
+            WorkerExit::ShareErrorRateExceeded => {
+                let action = share_check.map(|check| check.error_action);
+                error!(
+                    "{} of {} shares failed verification, {:?} worker thread",
+                    share_errors.failed, share_errors.verified, action
+                );
+                if action == Some(ShareErrorAction::Stop) {
+                    //reported before the job is done, the next job is not sent to this thread
+                    report_stopped();
+                    job_done(&job, share_tx);
+                    break;
+                }
+                //work_job continues the job with a new VM
+                share_errors = ShareErrors::default();
+            }
         }
     }
 
     info!("Worker stopped")
 }
 
+/// The last thread done with the job asks the pool for a new one.
+fn job_done(job: &JobData, share_tx: &Sender<stratum::StratumCmd>) {
+    if job.active_threads.fetch_sub(1, Ordering::SeqCst) == 1 {
+        let request_result = stratum::request_job(share_tx, &job.miner_id);
+        if request_result.is_err() {
+            error!("requesting new job failed: {:?}", request_result);
+        }
+    }
+}
+
 fn work_job<'a>(
     job: &'a JobData,
     rcv: &'a Receiver<WorkerCmd>,
+    jit: bool,
     share_tx: &Sender<stratum::StratumCmd>,
     metric_resolution: u64,
-    metric_tx: &Sender<u64>,
+    metric_tx: &Sender<MetricEvent>,
+    mut share_check: Option<(&ShareCheck, &mut ShareVerifier, &mut ShareErrors)>,
 ) -> WorkerExit {
-    let num_target = job_target_value(&job.target);
-    let mut nonce = job.nonce.fetch_add(1, Ordering::SeqCst);
+    let mut input = job.blob_bytes.clone();
+    let nonce_ix = claim_nonce(&job.nonce, &job.nonce_end, &job.spare_nonces);
 
     let mut hash_count: u64 = 0;
-    let mut vm = new_vm(job.memory.clone());
+    let mut vm = if jit {
+        new_jit_vm(job.memory.clone())
+    } else {
+        new_vm(job.memory.clone())
+    };
 
-    while nonce <= 65535 {
-        let nonce_hex = nonce_hex(nonce);
-        let hash_in = with_nonce(&job.blob, &nonce_hex);
-        let bytes_in = byte_string::string_to_u8_array(&hash_in);
+    let mut nonce = match nonce_ix {
+        Some(ix) => job.nonce_space.nonce(ix),
+        None => return WorkerExit::NonceSpaceExhausted,
+    };
+    vm.calculate_hash_first(with_nonce_bytes(&mut input, nonce));
 
-        let hash_result = vm.calculate_hash(&bytes_in).to_hex();
-        let hash_val = hash_target_value(&hash_result);
+    //a retired thread finishes the nonce it already claimed
+    let mut retiring = false;
+    loop {
+        //the input of the next nonce is started while the current hash finishes
+        let next_ix = if retiring {
+            None
+        } else {
+            claim_nonce(&job.nonce, &job.nonce_end, &job.spare_nonces)
+        };
+        let next_nonce = next_ix.map(|ix| job.nonce_space.nonce(ix));
+        //the series is started above and only ended by the last hash of the job
+        let hash_result = match next_nonce {
+            Some(next_nonce) => vm.calculate_hash_next(with_nonce_bytes(&mut input, next_nonce)),
+            None => vm.calculate_hash_last(),
+        }
+        .expect("pending hash");
 
-        if hash_val < num_target {
+        if job.share_target.is_met_by(hash_result.as_bytes()) {
             let share = stratum_data::Share {
                 miner_id: job.miner_id.clone(),
                 job_id: job.job_id.clone(),
-                nonce: nonce_hex,
-                hash: hash_result.to_string(),
+                nonce: nonce_hex(nonce),
+                hash: hash_result.to_hex().to_string(),
+            };
+
+            let valid = match &mut share_check {
+                Some((check, verifier, errors)) => {
+                    let valid = check.verify(verifier, job, &share);
+                    errors.record(valid);
+                    valid
+                }
+                None => true,
             };
 
-            let submit_result = stratum::submit_share(share_tx, share);
-            if submit_result.is_err() {
-                error!("submitting share failed: {:?}", submit_result);
+            if valid {
+                let submit_result = stratum::submit_share(share_tx, share);
+                if submit_result.is_err() {
+                    error!("submitting share failed: {:?}", submit_result);
+                }
+                let difficulty = job.share_target.difficulty();
+                if let Err(err) = metric_tx.send(MetricEvent::Share(difficulty)) {
+                    error!("metric submit failed {:?}", err);
+                }
+            }
+            if let Some((check, _, errors)) = &share_check {
+                if errors.rate_exceeded(check.max_error_rate) {
+                    return WorkerExit::ShareErrorRateExceeded;
+                }
             }
         }
 
         hash_count += 1;
         if hash_count % metric_resolution == 0 {
-            let send_result = metric_tx.send(hash_count);
+            let send_result = metric_tx.send(MetricEvent::Hashes(hash_count));
             if send_result.is_err() {
                 error!("metric submit failed {:?}", send_result);
             }
@@ -223,7 +872,7 @@ fn work_job<'a>(
         if let Some(cmd_value) = cmd {
             match cmd_value {
                 WorkerCmd::NewJob { job_data } => {
-                    let send_result = metric_tx.send(hash_count);
+                    let send_result = metric_tx.send(MetricEvent::Hashes(hash_count));
                     if send_result.is_err() {
                         //flush hash_count
                         error!("metric submit failed {:?}", send_result);
@@ -231,12 +880,32 @@ fn work_job<'a>(
                     return WorkerExit::NewJob { job_data };
                 }
                 WorkerCmd::Stop => return WorkerExit::Stopped,
+                WorkerCmd::Retire => retiring = true,
             }
         }
 
-        nonce = job.nonce.fetch_add(1, Ordering::SeqCst);
+        nonce = match next_nonce {
+            Some(next_nonce) => next_nonce,
+            None if retiring => {
+                let send_result = metric_tx.send(MetricEvent::Hashes(hash_count));
+                if send_result.is_err() {
+                    error!("metric submit failed {:?}", send_result);
+                }
+                return WorkerExit::Retired;
+            }
+            None => return WorkerExit::NonceSpaceExhausted,
+        };
     }
-    WorkerExit::NonceSpaceExhausted
+}
+
+/// Byte offset of the nonce in the job blob (hex offset 78 in `with_nonce`)
+pub const NONCE_OFFSET: usize = 39;
+const NONCE_SIZE: usize = 4;
+
+/// Writes `nonce` into the decoded blob, the same bytes as `with_nonce` with `nonce_hex`.
+pub fn with_nonce_bytes(blob: &mut [u8], nonce: u32) -> &[u8] {
+    blob[NONCE_OFFSET..NONCE_OFFSET + NONCE_SIZE].copy_from_slice(&nonce.to_be_bytes());
+    blob
 }
 
 pub fn nonce_hex(nonce: u32) -> String {
@@ -256,12 +925,3 @@ fn check_command_available(rcv: &Receiver<WorkerCmd>) -> Option<WorkerCmd> {
         _ => None,
     }
 }
-
-pub fn job_target_value(hex_str: &str) -> u64 {
-    let t = byte_string::hex2_u32_le(hex_str);
-    u64::max_value() / (u64::from(u32::max_value()) / u64::from(t))
-}
-
-pub fn hash_target_value(hex_str: &str) -> u64 {
-    byte_string::hex2_u64_le(&hex_str[48..])
-}
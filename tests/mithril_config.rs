@@ -1,8 +1,14 @@
 extern crate mithril;
 
+use mithril::bandit_tools;
 use mithril::mithril_config;
+use mithril::randomx::memory::MemoryMode;
+use mithril::worker::affinity::Affinity;
+use mithril::worker::worker_pool::ShareErrorAction;
 
+use std::fs;
 use std::path::Path;
+use std::process;
 use std::time::{Duration, Instant};
 
 #[test]
@@ -12,11 +18,25 @@ fn test_read_default_config() {
     assert_eq!(config.pool_conf.pool_address, "xmrpool.eu:3333");
     assert_eq!(config.pool_conf.wallet_address, "");
     assert_eq!(config.pool_conf.pool_password, "");
+    assert_eq!(config.pool_conf.algorithm.name, "rx/0");
 
     assert_eq!(config.worker_conf.num_threads, 8);
     assert_eq!(config.worker_conf.auto_tune, true);
     assert_eq!(config.worker_conf.auto_tune_interval_minutes, 15);
     assert_eq!(config.worker_conf.auto_tune_log, "./bandit.log");
+    assert!(config.worker_conf.jit);
+    assert!(config.worker_conf.eager_dataset);
+    assert_eq!(config.worker_conf.dataset_init_threads, None);
+    assert_eq!(config.worker_conf.memory_mode, MemoryMode::Auto);
+    assert!(!config.worker_conf.nonce_ranges);
+    assert_eq!(config.worker_conf.affinity, Affinity::Auto);
+    assert!(config.worker_conf.huge_pages);
+    assert!(!config.worker_conf.verify_shares);
+    assert_eq!(config.worker_conf.max_share_error_rate, 0.1);
+    assert_eq!(
+        config.worker_conf.share_error_action,
+        ShareErrorAction::Restart
+    );
 
     assert_eq!(config.metric_conf.enabled, false);
     assert_eq!(config.metric_conf.resolution, std::u32::MAX as u64);
@@ -27,6 +47,16 @@ fn test_read_default_config() {
     assert_eq!(config.metric_conf.report_file, "/dev/null");
 
     assert_eq!(config.donation_conf.percentage, 2.5);
+
+    assert_eq!(config.hardware_conf.has_aes, None);
+
+    assert!(config.cache_conf.enabled);
+    assert_eq!(
+        config.cache_conf.dir,
+        bandit_tools::mithril_folder().join("cache")
+    );
+    assert_eq!(config.cache_conf.max_size_mb, 3072);
+    assert!(!config.cache_conf.dataset);
 }
 
 #[test] //Bugfix test, there should be some "room" so that this value can be added to a time instant
@@ -38,9 +68,41 @@ fn test_disabled_metric_value_should_be_addable_to_now() {
     //Ok if it doesn't panic
 }
 
+#[test]
+fn test_read_affinity_cpu_list() {
+    let config = read_config_with("affinity", "affinity = [1, 3]").expect("config");
+    assert_eq!(config.worker_conf.affinity, Affinity::Cpus(vec![1, 3]));
+
+    assert!(read_config_with("affinity_empty", "affinity = []").is_none());
+    assert!(read_config_with("affinity_negative", "affinity = [-1]").is_none());
+    assert!(read_config_with("affinity_unknown", "affinity = \"all\"").is_none());
+}
+
 //helper
 
 fn read_default_config() -> mithril_config::MithrilConfig {
     let path = &format!("{}{}", "./", "default_config.toml");
     return mithril_config::read_config(Path::new(path), "default_config.toml").unwrap();
 }
+
+/// The default config with the `affinity` line replaced, None if it is invalid
+fn read_config_with(name: &str, affinity: &str) -> Option<mithril_config::MithrilConfig> {
+    let default = fs::read_to_string("default_config.toml").unwrap();
+    let content = default
+        .lines()
+        .map(|l| {
+            if l.starts_with("affinity =") {
+                affinity
+            } else {
+                l
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    //read relative to the working directory like the default config
+    let filename = format!("test_config_{}_{}.toml", name, process::id());
+    fs::write(&filename, content).unwrap();
+    let config = mithril_config::read_config(Path::new(&filename), &filename).ok();
+    let _ = fs::remove_file(&filename);
+    config
+}
@@ -0,0 +1,196 @@
+extern crate mithril;
+
+use mithril::byte_string::u8_array_to_string;
+use mithril::randomx::memory::{init_dataset_item, SeedMemory};
+use mithril::randomx::soft_aes::{self, AesModeGuard};
+use mithril::randomx::{RandomxCache, RandomxDataset, RandomxError, RandomxFlags, RandomxVm};
+
+#[test]
+fn test_calculate_hash_light_mode() {
+    let cache = RandomxCache::new(b"test key 000");
+    let mut vm = RandomxVm::new(RandomxFlags::DEFAULT, Some(&cache), None).unwrap();
+
+    let hash = vm.calculate_hash(b"This is a test");
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(&hash)
+    );
+    let hash = vm.calculate_hash(b"Lorem ipsum dolor sit amet");
+    assert_eq!(
+        "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969",
+        u8_array_to_string(&hash)
+    );
+}
+
+#[test]
+fn test_calculate_hash_recommended_flags() {
+    let cache = RandomxCache::new(b"test key 000");
+    let flags = RandomxFlags::recommended();
+    assert!(!flags.contains(RandomxFlags::FULL_MEM));
+
+    let mut vm = RandomxVm::new(flags, Some(&cache), None).unwrap();
+    assert_eq!(vm.flags(), flags);
+    let hash = vm.calculate_hash(b"This is a test");
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(&hash)
+    );
+}
+
+#[test]
+fn test_cache_without_jit() {
+    let cache = RandomxCache::with_flags(b"test key 000", RandomxFlags::DEFAULT);
+    assert_eq!(cache.flags(), RandomxFlags::DEFAULT);
+    let mut vm = RandomxVm::new(RandomxFlags::DEFAULT, Some(&cache), None).unwrap();
+
+    //dataset items of the interpreted superscalar programs
+    let hash = vm.calculate_hash(b"This is a test");
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(&hash)
+    );
+}
+
+#[test]
+fn test_vm_without_hard_aes_uses_software_aes() {
+    let cache = RandomxCache::new(b"test key 000");
+    let mut vm = RandomxVm::new(RandomxFlags::DEFAULT, Some(&cache), None).unwrap();
+    assert!(!vm.hardware_aes());
+
+    //the thread selection is only overridden while the VM hashes
+    let hardware = soft_aes::detect_hardware_aes();
+    let _thread_aes = AesModeGuard::set(hardware);
+    let hash = vm.calculate_hash(b"This is a test");
+    assert_eq!(soft_aes::hardware_aes(), hardware);
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(&hash)
+    );
+
+    if hardware {
+        let vm = RandomxVm::new(RandomxFlags::HARD_AES, Some(&cache), None).unwrap();
+        assert!(vm.hardware_aes());
+    }
+}
+
+#[test]
+fn test_set_cache() {
+    let mut cache = RandomxCache::new(b"test key 000");
+    let mut vm = RandomxVm::new(RandomxFlags::DEFAULT, Some(&cache), None).unwrap();
+
+    cache.init(b"test key 001");
+    vm.set_cache(&cache).unwrap();
+    let hash =
+        vm.calculate_hash(b"sed do eiusmod tempor incididunt ut labore et dolore magna aliqua");
+    assert_eq!(
+        "e9ff4503201c0c2cca26d285c93ae883f9b1d30c9eb240b820756f2d5a7905fc",
+        u8_array_to_string(&hash)
+    );
+
+    let dataset = RandomxDataset::new(RandomxFlags::DEFAULT).unwrap();
+    assert_eq!(
+        vm.set_dataset(&dataset).err(),
+        Some(RandomxError::WrongMode)
+    );
+}
+
+#[test]
+fn test_calculate_hash_pipelined() {
+    let cache = RandomxCache::new(b"test key 000");
+    let mut vm = RandomxVm::new(RandomxFlags::DEFAULT, Some(&cache), None).unwrap();
+
+    vm.calculate_hash_first(b"This is a test");
+    let hash = vm
+        .calculate_hash_next(b"Lorem ipsum dolor sit amet")
+        .unwrap();
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(&hash)
+    );
+    let hash = vm.calculate_hash_last().unwrap();
+    assert_eq!(
+        "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969",
+        u8_array_to_string(&hash)
+    );
+    assert_eq!(vm.calculate_hash_last(), Err(RandomxError::NoPendingHash));
+}
+
+#[test]
+fn test_calculate_hash_next_without_first() {
+    let cache = RandomxCache::new(b"test key 000");
+    let mut vm = RandomxVm::new(RandomxFlags::DEFAULT, Some(&cache), None).unwrap();
+
+    assert_eq!(
+        vm.calculate_hash_next(b"This is a test"),
+        Err(RandomxError::NoPendingHash)
+    );
+    assert_eq!(vm.calculate_hash_last(), Err(RandomxError::NoPendingHash));
+}
+
+#[test]
+fn test_missing_memory() {
+    let cache = RandomxCache::new(b"test key 000");
+    assert_eq!(
+        RandomxVm::new(RandomxFlags::DEFAULT, None, None).err(),
+        Some(RandomxError::MissingCache)
+    );
+    assert_eq!(
+        RandomxVm::new(RandomxFlags::FULL_MEM, Some(&cache), None).err(),
+        Some(RandomxError::MissingDataset)
+    );
+}
+
+#[test]
+fn test_dataset_init() {
+    let cache = RandomxCache::new(b"test key 000");
+    let seed_memory = SeedMemory::new_initialised(b"test key 000");
+    let mut dataset = RandomxDataset::new(RandomxFlags::DEFAULT).unwrap();
+
+    dataset.init(&cache, 10000000, 100).unwrap();
+    dataset.init_parallel(&cache, 20000000, 1000, 3).unwrap();
+
+    let memory = dataset.memory();
+    assert_eq!(memory.len() as u64, RandomxDataset::ITEM_COUNT * 8);
+    for item in (10000000..10000100).chain(20000000..20001000) {
+        let ix = item as usize * 8;
+        assert_eq!(
+            memory[ix..ix + 8],
+            init_dataset_item(&seed_memory, item as u64)
+        );
+    }
+    //not initialised
+    assert_eq!(memory[(10000100 * 8)..(10000101 * 8)], [0; 8]);
+}
+
+#[test]
+fn test_dataset_init_errors() {
+    let cache = RandomxCache::new(b"test key 000");
+    let mut dataset = RandomxDataset::new(RandomxFlags::DEFAULT).unwrap();
+
+    assert_eq!(
+        dataset.init(&cache, RandomxDataset::ITEM_COUNT - 1, 2),
+        Err(RandomxError::DatasetRange {
+            start_item: RandomxDataset::ITEM_COUNT - 1,
+            item_count: 2
+        })
+    );
+    assert!(dataset.init(&cache, u64::MAX, 2).is_err());
+
+    let vm = RandomxVm::new(RandomxFlags::FULL_MEM, None, Some(&dataset)).unwrap();
+    assert_eq!(dataset.init(&cache, 0, 1), Err(RandomxError::DatasetInUse));
+    drop(vm);
+    assert_eq!(dataset.init(&cache, 0, 1), Ok(()));
+}
+
+#[test]
+fn test_flags() {
+    let flags = RandomxFlags::FULL_MEM | RandomxFlags::JIT;
+    assert_eq!(flags.bits(), 12);
+    assert!(flags.contains(RandomxFlags::JIT));
+    assert!(!flags.contains(RandomxFlags::HARD_AES));
+    assert_eq!(RandomxFlags::from_bits(12), flags);
+    assert_eq!(
+        RandomxError::DatasetInUse.to_string(),
+        "dataset in use by a VM"
+    );
+}
@@ -0,0 +1,125 @@
+extern crate blake2b_simd;
+extern crate mithril;
+
+use self::blake2b_simd::blake2b;
+use mithril::randomx::bytecode::Bytecode;
+use mithril::randomx::hash::{gen_program_aes_4rx4, gen_program_aes_4rx4_with_keys};
+use mithril::randomx::jit::JitProgram;
+use mithril::randomx::m128::m128i;
+use mithril::randomx::memory::VmMemory;
+use mithril::randomx::params::{RandomxParams, RX_0, RX_ARQ, RX_WOW, RX_XLA};
+use mithril::randomx::program::{Opcode, Program, MAX_FLOAT_REG, MAX_REG};
+use mithril::randomx::vm::{hash_to_m128i_array, new_vm, Vm};
+use std::sync::Arc;
+
+#[test]
+fn test_decode_reuses_buffer() {
+    let mut bytecode = Bytecode::new();
+    for i in 0..4u32 {
+        let seed = hash_to_m128i_array(&blake2b(&i.to_le_bytes()));
+        let bytes = gen_program_aes_4rx4(&seed, 136);
+        bytecode.decode(&bytes, &RX_0);
+        let prog = Program::from_bytes(bytes, &RX_0);
+
+        assert_eq!(bytecode.code.len(), 256);
+        assert_eq!(bytecode.code.capacity(), 256);
+        assert_eq!(bytecode.entropy.to_vec(), prog.entropy);
+    }
+}
+
+#[test]
+fn test_decode_opcodes() {
+    let seed = hash_to_m128i_array(&blake2b(b"This is a test"));
+    let bytes = gen_program_aes_4rx4(&seed, 136);
+    let mut bytecode = Bytecode::new();
+    bytecode.decode(&bytes, &RX_0);
+    let prog = Program::from_bytes(bytes, &RX_0);
+
+    for (instr, byte_instr) in prog.program.iter().zip(bytecode.code.iter()) {
+        //decoded as NOP if they are a no-op
+        if byte_instr.op == Opcode::NOP {
+            assert!(instr.op == Opcode::NOP || instr.op == Opcode::IMUL_RCP);
+        } else {
+            assert_eq!(instr.op, byte_instr.op);
+        }
+    }
+}
+
+#[test]
+fn test_bytecode_matches_jit_for_random_programs() {
+    check_random_programs(&RX_0);
+}
+
+#[test]
+fn test_bytecode_matches_jit_for_random_rx_arq_programs() {
+    check_random_programs(&RX_ARQ);
+}
+
+#[test]
+fn test_bytecode_matches_jit_for_random_rx_wow_programs() {
+    check_random_programs(&RX_WOW);
+}
+
+#[test]
+fn test_bytecode_matches_jit_for_random_rx_xla_programs() {
+    check_random_programs(&RX_XLA);
+}
+
+//helper
+
+/// The JIT compiles the `Program` decoding of the same bytes
+fn check_random_programs(params: &RandomxParams) {
+    let memory = Arc::new(VmMemory::no_memory());
+    let mut vm_jit = new_vm(memory.clone());
+    let mut vm_bytecode = new_vm(memory);
+    vm_jit.params = *params;
+    vm_bytecode.params = *params;
+    let mut jit = JitProgram::new().unwrap();
+    let mut bytecode = Bytecode::new();
+
+    for i in 0..200u32 {
+        let seed = hash_to_m128i_array(&blake2b(&i.to_le_bytes()));
+        let bytes =
+            gen_program_aes_4rx4_with_keys(&seed, params.program_bytes_len(), &params.program_keys);
+        bytecode.decode(&bytes, params);
+        let prog = Program::from_bytes(bytes, params);
+
+        init_test_vm(&mut vm_jit, &seed, &prog);
+        init_test_vm(&mut vm_bytecode, &seed, &prog);
+
+        vm_jit.reset_rounding_mode();
+        jit.compile(&prog, &vm_jit.config, params);
+        jit.execute(&mut vm_jit.reg, &mut vm_jit.scratchpad);
+        let mode_jit = vm_jit.get_rounding_mode();
+
+        vm_bytecode.reset_rounding_mode();
+        vm_bytecode.execute_bytecode(&bytecode.code);
+        let mode_bytecode = vm_bytecode.get_rounding_mode();
+        vm_bytecode.reset_rounding_mode();
+
+        assert_eq!(
+            vm_jit.reg.to_bytes().to_vec(),
+            vm_bytecode.reg.to_bytes().to_vec(),
+            "register mismatch for program {}",
+            i
+        );
+        assert!(
+            vm_jit.scratchpad[..] == vm_bytecode.scratchpad[..],
+            "scratchpad mismatch for program {}",
+            i
+        );
+        assert_eq!(mode_jit, mode_bytecode);
+    }
+}
+
+fn init_test_vm(vm: &mut Vm, seed: &[m128i; 4], prog: &Program) {
+    vm.init_scratchpad(seed);
+    vm.init_vm(prog);
+    for i in 0..MAX_REG {
+        vm.reg.r[i] = vm.scratchpad[i];
+    }
+    for i in 0..MAX_FLOAT_REG {
+        vm.reg.f[i] = m128i::from_u64(0, vm.scratchpad[8 + i]).lower_to_m128d();
+        vm.reg.e[i] = m128i::from_u64(0, vm.scratchpad[12 + i]).lower_to_m128d();
+    }
+}
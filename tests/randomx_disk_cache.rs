@@ -0,0 +1,143 @@
+extern crate mithril;
+
+use mithril::randomx::disk_cache::{DiskCache, DiskCacheConfig};
+use mithril::randomx::memory::{init_dataset_item, SeedMemory};
+use mithril::randomx::params::{RX_0, RX_ARQ};
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+#[test]
+fn test_seed_memory_round_trip() {
+    let (_dir, cache) = new_cache("seed", 1024, false);
+    let key = b"test key 000";
+    let seed_memory = SeedMemory::new_initialised(key);
+
+    assert!(cache.load_seed_memory("0a0b", key, &RX_0, true).is_none());
+    cache.store_seed_memory("0a0b", &seed_memory).unwrap();
+
+    let loaded = cache
+        .load_seed_memory("0a0b", key, &RX_0, true)
+        .expect("cached seed memory");
+    assert_eq!(loaded.blocks.len(), seed_memory.blocks.len());
+    assert_eq!(loaded.blocks[12253][29], 0xf1b62fe6210bf8b1);
+    assert_eq!(loaded.blocks[262143][127], 0x1f47f056d05cd99b);
+    assert_eq!(
+        init_dataset_item(&loaded, 10000000),
+        init_dataset_item(&seed_memory, 10000000)
+    );
+}
+
+#[test]
+fn test_dataset_round_trip() {
+    let (_dir, cache) = new_cache("dataset", 1024, true);
+    let dataset: Vec<u64> = (0..1000).map(|i| i * 0x9E3779B97F4A7C15).collect();
+
+    cache.store_dataset("0c0d", &dataset).unwrap();
+
+    let loaded = cache.load_dataset("0c0d", 1000).expect("cached dataset");
+    assert_eq!(loaded[..], dataset[..]);
+}
+
+#[test]
+fn test_dataset_not_stored_if_disabled() {
+    let (dir, cache) = new_cache("no_dataset", 1024, false);
+    cache.store_dataset("0c0d", &[1, 2, 3]).unwrap();
+    assert!(cache.load_dataset("0c0d", 3).is_none());
+    assert!(!dir.path.join("0c0d.dataset").exists());
+}
+
+#[test]
+fn test_invalid_entries_are_deleted() {
+    let (dir, cache) = new_cache("invalid", 1024, true);
+    let path = dir.path.join("0e0f.dataset");
+
+    //wrong size
+    cache.store_dataset("0e0f", &[1, 2, 3]).unwrap();
+    assert!(cache.load_dataset("0e0f", 4).is_none());
+    assert!(!path.exists());
+
+    //corrupted content
+    cache.store_dataset("0e0f", &[1, 2, 3]).unwrap();
+    let mut content = fs::read(&path).unwrap();
+    content[3] ^= 1;
+    fs::write(&path, content).unwrap();
+    assert!(cache.load_dataset("0e0f", 3).is_none());
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_old_seeds_are_evicted() {
+    let (dir, cache) = new_cache("evict", 1, true);
+    let dataset = vec![7; 65536]; //512KiB
+
+    cache.store_dataset("aa", &dataset).unwrap();
+    cache.store_dataset("bb", &dataset).unwrap();
+
+    assert!(!dir.path.join("aa.dataset").exists());
+    assert!(cache.load_dataset("bb", 65536).is_some());
+
+    //does not fit at all
+    cache.store_dataset("cc", &vec![7; 262144]).unwrap();
+    assert!(!dir.path.join("cc.dataset").exists());
+}
+
+#[test]
+fn test_stale_seeds_are_evicted() {
+    let (dir, cache) = new_cache("stale", 1024, true);
+    cache.store_dataset("aa", &[1, 2, 3]).unwrap();
+    cache.store_dataset("aa-rx-arq", &[1, 2, 3]).unwrap();
+    cache.store_dataset("bb", &[1, 2, 3]).unwrap();
+    fs::write(dir.path.join("other.txt"), "not an entry").unwrap();
+
+    cache.evict_stale("bb").unwrap();
+    assert!(!dir.path.join("aa.dataset").exists());
+    assert!(!dir.path.join("aa-rx-arq.dataset").exists());
+    assert!(dir.path.join("bb.dataset").exists());
+    assert!(dir.path.join("other.txt").exists());
+}
+
+#[test]
+fn test_entry_key_of_variants() {
+    assert_eq!(DiskCache::entry_key("0a0b", &RX_0), "0a0b");
+    assert_eq!(DiskCache::entry_key("0a0b", &RX_ARQ), "0a0b-rx-arq");
+
+    let (_dir, cache) = new_cache("variant", 1024, true);
+    cache.store_dataset("0a0b-rx-arq", &[1, 2, 3]).unwrap();
+    assert!(cache.load_dataset("0a0b", 3).is_none());
+    assert!(cache.load_dataset("0a0b-rx-arq", 3).is_some());
+}
+
+#[test]
+fn test_seed_hash_is_no_path() {
+    let (dir, cache) = new_cache("path", 1024, true);
+    cache.store_dataset("../0a", &[1, 2, 3]).unwrap();
+    assert!(cache.load_dataset("../0a", 3).is_none());
+    assert!(!dir.path.parent().unwrap().join("0a.dataset").exists());
+}
+
+//helper
+
+/// Cache directory of a test, removed on drop (also if the test fails)
+struct CacheDir {
+    path: PathBuf,
+}
+
+impl Drop for CacheDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn new_cache(name: &str, max_size_mb: u64, dataset: bool) -> (CacheDir, DiskCache) {
+    let path = std::env::temp_dir().join(format!("mithril_cache_{}_{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    let cache = DiskCache::new(&DiskCacheConfig {
+        enabled: true,
+        dir: path.clone(),
+        max_size_mb,
+        dataset,
+    })
+    .unwrap();
+    (CacheDir { path }, cache)
+}
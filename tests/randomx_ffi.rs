@@ -0,0 +1,144 @@
+extern crate mithril;
+
+use mithril::byte_string::u8_array_to_string;
+use mithril::randomx::ffi::*;
+use mithril::randomx::memory::{init_dataset_item, SeedMemory};
+use std::ffi::c_void;
+use std::ptr;
+use std::slice;
+use std::thread;
+
+#[test]
+fn test_calculate_hash() {
+    unsafe {
+        let cache = randomx_alloc_cache(0);
+        assert!(randomx_create_vm(0, cache, ptr::null_mut()).is_null());
+
+        init_cache(cache, b"test key 000");
+        let vm = randomx_create_vm(randomx_get_flags(), cache, ptr::null_mut());
+        assert!(!vm.is_null());
+        assert_eq!(
+            "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+            calculate_hash(vm, b"This is a test")
+        );
+
+        init_cache(cache, b"test key 001");
+        randomx_vm_set_cache(vm, cache);
+        assert_eq!(
+            "e9ff4503201c0c2cca26d285c93ae883f9b1d30c9eb240b820756f2d5a7905fc",
+            calculate_hash(
+                vm,
+                b"sed do eiusmod tempor incididunt ut labore et dolore magna aliqua"
+            )
+        );
+
+        randomx_destroy_vm(vm);
+        randomx_release_cache(cache);
+    }
+}
+
+#[test]
+fn test_calculate_hash_pipelined() {
+    unsafe {
+        let cache = randomx_alloc_cache(0);
+        init_cache(cache, b"test key 000");
+        let vm = randomx_create_vm(0, cache, ptr::null_mut());
+
+        //without randomx_calculate_hash_first, the first input starts the series
+        let mut hash = [0u8; 32];
+        calculate_hash_last(vm, &mut hash);
+        assert_eq!([0u8; 32], hash);
+        calculate_hash_next(vm, b"This is a test", &mut hash);
+        assert_eq!([0u8; 32], hash);
+
+        calculate_hash_next(vm, b"Lorem ipsum dolor sit amet", &mut hash);
+        assert_eq!(
+            "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+            u8_array_to_string(&hash)
+        );
+        calculate_hash_last(vm, &mut hash);
+        assert_eq!(
+            "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969",
+            u8_array_to_string(&hash)
+        );
+
+        randomx_destroy_vm(vm);
+        randomx_release_cache(cache);
+    }
+}
+
+#[test]
+fn test_init_dataset_concurrently() {
+    unsafe {
+        let cache = randomx_alloc_cache(0);
+        init_cache(cache, b"test key 000");
+        let dataset = randomx_alloc_dataset(0);
+        assert!(!dataset.is_null());
+        assert!(randomx_create_vm(4, cache, ptr::null_mut()).is_null());
+
+        //pointers are not Send, passed as usize
+        let (dataset_addr, cache_addr) = (dataset as usize, cache as usize);
+        thread::scope(|scope| {
+            for start in [1000, 1100] {
+                scope.spawn(move || {
+                    randomx_init_dataset(dataset_addr as *mut _, cache_addr as *mut _, start, 100)
+                });
+            }
+        });
+
+        let memory = slice::from_raw_parts(
+            randomx_get_dataset_memory(dataset) as *const u64,
+            randomx_dataset_item_count() as usize * 8,
+        );
+        let seed_memory = SeedMemory::new_initialised(b"test key 000");
+        for item in 1000..1200 {
+            let ix = item * 8;
+            assert_eq!(
+                memory[ix..ix + 8],
+                init_dataset_item(&seed_memory, item as u64)
+            );
+        }
+
+        //ignored, neither aborts the process
+        let no_key_cache = randomx_alloc_cache(0);
+        randomx_init_dataset(dataset, no_key_cache, 0, 1);
+        randomx_init_dataset(dataset, cache, randomx_dataset_item_count(), 1);
+        randomx_release_cache(no_key_cache);
+
+        let vm = randomx_create_vm(4, ptr::null_mut(), dataset);
+        assert!(!vm.is_null());
+        randomx_release_dataset(dataset); //the VM keeps the memory
+        randomx_destroy_vm(vm);
+        randomx_release_cache(cache);
+    }
+}
+
+//helper
+
+unsafe fn init_cache(cache: *mut Cache, key: &[u8]) {
+    randomx_init_cache(cache, key.as_ptr() as *const c_void, key.len());
+}
+
+unsafe fn calculate_hash(vm: *mut Machine, input: &[u8]) -> String {
+    let mut hash = [0u8; 32];
+    randomx_calculate_hash(
+        vm,
+        input.as_ptr() as *const c_void,
+        input.len(),
+        hash.as_mut_ptr() as *mut c_void,
+    );
+    u8_array_to_string(&hash)
+}
+
+unsafe fn calculate_hash_next(vm: *mut Machine, next_input: &[u8], hash: &mut [u8; 32]) {
+    randomx_calculate_hash_next(
+        vm,
+        next_input.as_ptr() as *const c_void,
+        next_input.len(),
+        hash.as_mut_ptr() as *mut c_void,
+    );
+}
+
+unsafe fn calculate_hash_last(vm: *mut Machine, hash: &mut [u8; 32]) {
+    randomx_calculate_hash_last(vm, hash.as_mut_ptr() as *mut c_void);
+}
@@ -1,7 +1,11 @@
 extern crate mithril;
 
-use mithril::randomx::hash::{gen_program_aes_1rx4, gen_program_aes_4rx4};
+use mithril::randomx::hash::{
+    fill_aes_1rx4_u64, gen_program_aes_1rx4, gen_program_aes_4rx4, gen_program_aes_4rx4_with_keys,
+    hash_aes_1rx4, hash_and_fill_aes_1rx4,
+};
 use mithril::randomx::m128::m128i;
+use mithril::randomx::params::{RX_0, RX_WOW};
 
 #[test]
 #[allow(overflowing_literals)]
@@ -110,3 +114,40 @@ fn test_gen_program_aes_4rx4() {
         m128i::from_i32(0x3f7fdb2f, 0x565cd0c7, 0xbe72f8e3, 0x5da409a1)
     );
 }
+
+#[test]
+fn test_gen_program_aes_4rx4_with_keys() {
+    let input = [
+        m128i::from_u64(1, 2),
+        m128i::from_u64(3, 4),
+        m128i::from_u64(5, 6),
+        m128i::from_u64(7, 8),
+    ];
+    assert_eq!(
+        gen_program_aes_4rx4_with_keys(&input, 40, &RX_0.program_keys),
+        gen_program_aes_4rx4(&input, 40)
+    );
+    assert_ne!(
+        gen_program_aes_4rx4_with_keys(&input, 40, &RX_WOW.program_keys),
+        gen_program_aes_4rx4(&input, 40)
+    );
+}
+
+#[test]
+#[allow(overflowing_literals)]
+fn test_hash_and_fill_aes_1rx4() {
+    let seed0 = m128i::from_i32(0xb53a90c9, 0xf56f1bc9, 0x25a4424b, 0x727ab1b2);
+    let seed1 = m128i::from_i32(0x70152fd1, 0x377f234d, 0xe8027504, 0xfed70bc4);
+    let mut scratchpad = vec![0; 1024];
+    fill_aes_1rx4_u64(&[seed0, seed0, seed1, seed1], &mut scratchpad);
+    let expected_hash = hash_aes_1rx4(&scratchpad);
+    let mut expected_fill = vec![0; 1024];
+    let expected_state = fill_aes_1rx4_u64(&[seed1, seed0, seed1, seed0], &mut expected_fill);
+
+    let mut state = [seed1, seed0, seed1, seed0];
+    let hash = hash_and_fill_aes_1rx4(&mut scratchpad, &mut state);
+
+    assert_eq!(hash, expected_hash);
+    assert_eq!(state, expected_state);
+    assert_eq!(scratchpad, expected_fill);
+}
@@ -0,0 +1,125 @@
+extern crate blake2b_simd;
+extern crate mithril;
+
+use self::blake2b_simd::blake2b;
+use mithril::byte_string::u8_array_to_string;
+use mithril::randomx::bytecode::Bytecode;
+use mithril::randomx::hash::gen_program_aes_4rx4;
+use mithril::randomx::jit::JitProgram;
+use mithril::randomx::m128::m128i;
+use mithril::randomx::memory::VmMemory;
+use mithril::randomx::params::RX_0;
+use mithril::randomx::program::{Program, MAX_FLOAT_REG, MAX_REG};
+use mithril::randomx::vm::{hash_to_m128i_array, new_jit_vm, new_vm, Vm};
+use std::sync::Arc;
+
+#[test]
+fn test_calculate_hash_with_jit_light_memory() {
+    let mut vm = new_jit_vm(Arc::new(VmMemory::light(b"test key 000")));
+    assert!(vm.is_jit());
+
+    let result = vm.calculate_hash(b"This is a test");
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(result.as_bytes())
+    );
+
+    let result = vm.calculate_hash(b"Lorem ipsum dolor sit amet");
+    assert_eq!(
+        "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969",
+        u8_array_to_string(result.as_bytes())
+    );
+
+    let result =
+        vm.calculate_hash(b"sed do eiusmod tempor incididunt ut labore et dolore magna aliqua");
+    assert_eq!(
+        "c36d4ed4191e617309867ed66a443be4075014e2b061bcdaf9ce7b721d2b77a8",
+        u8_array_to_string(result.as_bytes())
+    );
+}
+
+#[test]
+fn test_calculate_hash_2_with_jit_light_memory() {
+    let mut vm = new_jit_vm(Arc::new(VmMemory::light(b"test key 001")));
+
+    let result =
+        vm.calculate_hash(b"sed do eiusmod tempor incididunt ut labore et dolore magna aliqua");
+    assert_eq!(
+        "e9ff4503201c0c2cca26d285c93ae883f9b1d30c9eb240b820756f2d5a7905fc",
+        u8_array_to_string(result.as_bytes())
+    );
+}
+
+#[test]
+fn test_pipelined_hash_with_jit_light_memory() {
+    let mut vm = new_jit_vm(Arc::new(VmMemory::light(b"test key 000")));
+
+    vm.calculate_hash_first(b"This is a test");
+    let result = vm
+        .calculate_hash_next(b"Lorem ipsum dolor sit amet")
+        .unwrap();
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(result.as_bytes())
+    );
+    let result = vm.calculate_hash_last().unwrap();
+    assert_eq!(
+        "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969",
+        u8_array_to_string(result.as_bytes())
+    );
+}
+
+#[test]
+fn test_jit_matches_interpreter_for_random_programs() {
+    let mut vm_interpreter = new_vm(Arc::new(VmMemory::no_memory()));
+    let mut vm_jit = new_vm(Arc::new(VmMemory::no_memory()));
+    let mut jit = JitProgram::new().unwrap();
+    let mut bytecode = Bytecode::new();
+
+    for i in 0..200u32 {
+        let seed = hash_to_m128i_array(&blake2b(&i.to_le_bytes()));
+        let bytes = gen_program_aes_4rx4(&seed, 136);
+        let prog = Program::from_bytes(bytes.clone(), &RX_0);
+
+        init_test_vm(&mut vm_interpreter, &seed, &prog);
+        init_test_vm(&mut vm_jit, &seed, &prog);
+
+        bytecode.decode(&bytes, &RX_0);
+        vm_interpreter.reset_rounding_mode();
+        vm_interpreter.execute_bytecode(&bytecode.code);
+        let mode_interpreter = vm_interpreter.get_rounding_mode();
+
+        vm_jit.reset_rounding_mode();
+        jit.compile(&prog, &vm_jit.config, &RX_0);
+        jit.execute(&mut vm_jit.reg, &mut vm_jit.scratchpad);
+        let mode_jit = vm_jit.get_rounding_mode();
+        vm_jit.reset_rounding_mode();
+
+        assert_eq!(
+            vm_interpreter.reg.to_bytes().to_vec(),
+            vm_jit.reg.to_bytes().to_vec(),
+            "register mismatch for program {}",
+            i
+        );
+        assert!(
+            vm_interpreter.scratchpad[..] == vm_jit.scratchpad[..],
+            "scratchpad mismatch for program {}",
+            i
+        );
+        assert_eq!(mode_interpreter, mode_jit);
+    }
+}
+
+//helper
+
+fn init_test_vm(vm: &mut Vm, seed: &[m128i; 4], prog: &Program) {
+    vm.init_scratchpad(seed);
+    vm.init_vm(prog);
+    for i in 0..MAX_REG {
+        vm.reg.r[i] = vm.scratchpad[i];
+    }
+    for i in 0..MAX_FLOAT_REG {
+        vm.reg.f[i] = m128i::from_u64(0, vm.scratchpad[8 + i]).lower_to_m128d();
+        vm.reg.e[i] = m128i::from_u64(0, vm.scratchpad[12 + i]).lower_to_m128d();
+    }
+}
@@ -2,10 +2,18 @@ extern crate lazy_static;
 extern crate mithril;
 
 use lazy_static::lazy_static;
-use mithril::randomx::memory::{init_dataset_item, SeedMemory};
+use mithril::randomx::memory::{
+    full_memory_required, init_dataset_item, init_dataset_items, init_dataset_range,
+    parse_mem_available, set_huge_pages, MemoryMode, MemoryRegion, PageKind, SeedMemory,
+    VmMemoryAllocator,
+};
+use mithril::randomx::params::{RX_0, RX_ARQ};
+use std::sync::Arc;
 
 lazy_static! {
     static ref TEST_SEED_MEM: SeedMemory = SeedMemory::new_initialised(b"test key 000");
+    static ref TEST_SEED_MEM_INTERPRETED: SeedMemory =
+        SeedMemory::from_blocks(b"test key 000", TEST_SEED_MEM.blocks.clone(), &RX_0, false);
 }
 
 #[test]
@@ -29,3 +37,161 @@ fn test_init_dataset_item() {
     let item = init_dataset_item(&TEST_SEED_MEM, 30000000);
     assert_eq!(item[0], 0x145a5091f7853099);
 }
+
+#[test]
+fn test_init_dataset_item_compiled_matches_interpreter() {
+    assert!(TEST_SEED_MEM.jit.is_some());
+    assert!(TEST_SEED_MEM_INTERPRETED.jit.is_none());
+    for item_num in (0..34078719).step_by(9973) {
+        assert_eq!(
+            init_dataset_item(&TEST_SEED_MEM, item_num),
+            init_dataset_item(&TEST_SEED_MEM_INTERPRETED, item_num)
+        );
+    }
+}
+
+#[test]
+fn test_init_dataset_items_batch() {
+    //odd start and count, the AVX2 lanes (if available) and the scalar rest are used
+    let start = 10000001;
+    let count = 1027;
+    for seed_mem in [&*TEST_SEED_MEM, &*TEST_SEED_MEM_INTERPRETED] {
+        let mut out = vec![0; count * 8];
+        init_dataset_items(seed_mem, start, count, &mut out);
+        for (i, item) in out.chunks_exact(8).enumerate() {
+            assert_eq!(item, init_dataset_item(&TEST_SEED_MEM, start + i as u64));
+        }
+    }
+}
+
+#[test]
+fn test_init_dataset_items() {
+    let mut items = vec![0; 20000 * 8];
+    init_dataset_range(&TEST_SEED_MEM, &mut items, 0, 3);
+
+    for (i, item) in items.chunks(8).enumerate() {
+        assert_eq!(item, init_dataset_item(&TEST_SEED_MEM, i as u64));
+    }
+}
+
+#[test]
+fn test_memory_region() {
+    let mut region = MemoryRegion::new(1000);
+    assert_eq!(region.page_kind(), PageKind::Normal);
+    assert_eq!(region.len(), 1000);
+    assert_eq!(region.as_ptr() as usize % 64, 0);
+    assert!(region.iter().all(|v| *v == 0));
+
+    region[999] = 42;
+    assert_eq!(region[999], 42);
+}
+
+#[test]
+fn test_memory_region_huge_pages() {
+    //falls back to normal pages if no huge pages are available
+    set_huge_pages(true);
+    let mut region = MemoryRegion::new(262144 + 3);
+    set_huge_pages(false);
+
+    assert_eq!(region.len(), 262144 + 3);
+    assert_eq!(region.as_ptr() as usize % 64, 0);
+    assert!(region.iter().all(|v| *v == 0));
+
+    region[262146] = 42;
+    assert_eq!(region[262146], 42);
+}
+
+#[test]
+fn test_vm_memory_allocator_builds_in_background() {
+    let seed_a = "15564c3122550436919ac2f8a71baf7cbaf9a4117b842d7f2b19dfd27dd178e9";
+    let seed_b = "aef2d93d89bcfbe147cdf85ca3827d8a78ef687fd338b4da137ef3b403e7fef5";
+    let mut allocator = VmMemoryAllocator::initial();
+
+    assert!(allocator.memory_for(seed_a, 1).is_none());
+    let (seed, memory) = allocator.built_rcvr.recv().unwrap();
+    assert_eq!(seed, seed_a);
+    allocator.finished(seed, memory, 1);
+    let memory_a = allocator.memory_for(seed_a, 1).expect("memory for seed a");
+
+    //built ahead of time, the current memory stays in use meanwhile
+    allocator.prepare(seed_b, 1);
+    let (seed, memory) = allocator.built_rcvr.recv().unwrap();
+    assert_eq!(seed, seed_b);
+    allocator.finished(seed, memory, 1);
+    assert!(Arc::ptr_eq(&allocator.vm_memory, &memory_a));
+
+    let memory_b = allocator.memory_for(seed_b, 1).expect("prepared memory");
+    assert!(!Arc::ptr_eq(&memory_b, &memory_a));
+    assert_eq!(allocator.vm_memory_seed, seed_b);
+    //the old memory is freed once the last job using it is done
+    assert_eq!(Arc::strong_count(&memory_a), 1);
+}
+
+#[test]
+fn test_vm_memory_allocator_builds_light_memory() {
+    let seed = "15564c3122550436919ac2f8a71baf7cbaf9a4117b842d7f2b19dfd27dd178e9";
+    let mut allocator = VmMemoryAllocator::with_eager_init(Some(1));
+    allocator.light = true;
+    allocator.jit = false;
+
+    assert!(allocator.memory_for(seed, 1).is_none());
+    let (seed, memory) = allocator.built_rcvr.recv().unwrap();
+    allocator.finished(seed.clone(), memory, 1);
+    let memory = allocator.memory_for(&seed, 1).expect("light memory");
+    assert!(memory.is_light());
+    //worker.jit = false, the superscalar programs are interpreted
+    assert!(memory.seed_memory.jit.is_none());
+}
+
+#[test]
+fn test_parse_mem_available() {
+    let meminfo = "MemTotal:        8040424 kB\n\
+                   MemFree:          512744 kB\n\
+                   MemAvailable:    3977676 kB\n\
+                   Buffers:          231164 kB\n";
+    assert_eq!(parse_mem_available(meminfo), Some(3977676 * 1024));
+    //old kernels have no MemAvailable
+    assert_eq!(parse_mem_available("MemTotal: 8040424 kB\n"), None);
+    assert_eq!(parse_mem_available("MemAvailable: lots kB\n"), None);
+}
+
+#[test]
+fn test_memory_mode_resolve() {
+    let required = full_memory_required(&RX_0, 4);
+    assert!(required > 4 * 1024 * 1024 * 1024);
+
+    let auto = MemoryMode::Auto;
+    assert_eq!(auto.resolve(&RX_0, 4, Some(required)), MemoryMode::Full);
+    assert_eq!(
+        auto.resolve(&RX_0, 4, Some(required - 1)),
+        MemoryMode::Light
+    );
+    assert_eq!(auto.resolve(&RX_0, 4, None), MemoryMode::Full);
+    //configured modes are kept
+    assert_eq!(
+        MemoryMode::Full.resolve(&RX_0, 4, Some(0)),
+        MemoryMode::Full
+    );
+    assert_eq!(MemoryMode::Light.resolve(&RX_0, 4, None), MemoryMode::Light);
+}
+
+#[test]
+fn test_vm_memory_allocator_discards_memory_of_other_params() {
+    let seed = "15564c3122550436919ac2f8a71baf7cbaf9a4117b842d7f2b19dfd27dd178e9";
+    let mut allocator = VmMemoryAllocator::initial();
+
+    assert!(allocator.memory_for(seed, 1).is_none());
+    allocator.set_params(RX_ARQ);
+    assert!(allocator.memory_for(seed, 1).is_none());
+
+    //the rx/0 build is discarded and the memory rebuilt
+    let (built_seed, memory) = allocator.built_rcvr.recv().unwrap();
+    assert_eq!(*memory.params(), RX_0);
+    allocator.finished(built_seed, memory, 1);
+    assert!(allocator.memory_for(seed, 1).is_none());
+
+    let (built_seed, memory) = allocator.built_rcvr.recv().unwrap();
+    allocator.finished(built_seed, memory, 1);
+    let memory = allocator.memory_for(seed, 1).expect("memory");
+    assert_eq!(*memory.params(), RX_ARQ);
+}
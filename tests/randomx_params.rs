@@ -0,0 +1,115 @@
+extern crate mithril;
+
+use mithril::byte_string::u8_array_to_string;
+use mithril::randomx::memory::VmMemory;
+use mithril::randomx::params::{RandomxParams, PRESETS, RX_0, RX_ARQ, RX_WOW, RX_XLA};
+use mithril::randomx::vm::{new_jit_vm, new_vm};
+use std::sync::Arc;
+
+#[test]
+fn test_rx_0_opcode_limits() {
+    //the cumulative frequencies of the reference configuration
+    assert_eq!(
+        RX_0.opcode_limits(),
+        [
+            0x10, 0x17, 0x27, 0x2e, 0x3e, 0x42, 0x46, 0x47, 0x4b, 0x4c, 0x54, 0x56, 0x65, 0x6a,
+            0x72, 0x74, 0x78, 0x7c, 0x8c, 0x91, 0xa1, 0xa6, 0xac, 0xcc, 0xd0, 0xd6, 0xef, 0xf0,
+            0x100
+        ]
+    );
+}
+
+#[test]
+fn test_rx_0_derived_values() {
+    assert_eq!(RX_0.scratchpad_l1_mask(), 0x3ff8);
+    assert_eq!(RX_0.scratchpad_l2_mask(), 0x3fff8);
+    assert_eq!(RX_0.scratchpad_l3_mask(), 0x1ffff8);
+    assert_eq!(RX_0.scratchpad_l3_mask64(), 0x1fffc0);
+    assert_eq!(RX_0.scratchpad_len(), 262144);
+    assert_eq!(RX_0.condition_mask(), 0xff);
+    assert_eq!(RX_0.dataset_item_count(), 34078719);
+    assert_eq!(RX_0.dataset_extra_items(), 524287);
+    assert_eq!(RX_0.cache_line_align_mask(), 0x7fffffc0);
+    assert_eq!(RX_0.program_bytes_len(), 136);
+}
+
+#[test]
+fn test_presets_are_consistent() {
+    for params in PRESETS.iter() {
+        let frequencies: u16 = params.frequencies.iter().sum();
+        assert_eq!(frequencies, 256, "{}", params.name);
+        for size in [
+            params.scratchpad_l1_size,
+            params.scratchpad_l2_size,
+            params.scratchpad_l3_size,
+            params.dataset_base_size,
+        ] {
+            assert!(size.is_power_of_two(), "{} {}", params.name, size);
+        }
+        assert!(params.scratchpad_l1_size <= params.scratchpad_l2_size);
+        assert!(params.scratchpad_l2_size <= params.scratchpad_l3_size);
+        assert_eq!(RandomxParams::by_name(params.name), Some(*params));
+    }
+    assert_eq!(RandomxParams::by_name("rx/unknown"), None);
+}
+
+#[test]
+fn test_rx_0_hash_with_params() {
+    let memory = VmMemory::light_with_params(b"test key 000", &RX_0, true);
+    let mut vm = new_vm(Arc::new(memory));
+    let result = vm.calculate_hash(b"This is a test");
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(result.as_bytes())
+    );
+}
+
+//The rx/arq, rx/wow and rx/xla hashes are regression values computed by mithril
+//(interpreter and JIT agree), not yet the published test vectors of RandomARQ,
+//RandomWOW and RandomXL. Replace them with those vectors (and compare the presets
+//with the configuration.h of these repos) before relying on the variants.
+
+#[test]
+fn test_rx_arq_hash() {
+    check_hash(
+        &RX_ARQ,
+        "27f66e4650eb5657513e76c140e09e59336786f21fbef1ed6ff40fc21538221e",
+    );
+}
+
+#[test]
+fn test_rx_wow_hash() {
+    check_hash(
+        &RX_WOW,
+        "aad997cb7bca0aa57d9fa6b3175381ece70766f882c5c925912793a9a6c569d9",
+    );
+}
+
+#[test]
+fn test_rx_xla_hash() {
+    check_hash(
+        &RX_XLA,
+        "c6f857b36a5119cf8ac09f768da4fc486fb265c72aa551888b1f23e85ed07ce1",
+    );
+}
+
+//helper
+
+/// Hashes the key/input pair of the rx/0 test vector with `params`, interpreted and compiled
+fn check_hash(params: &RandomxParams, expected: &str) {
+    let memory = Arc::new(VmMemory::light_with_params(b"test key 000", params, true));
+    let mut vm = new_vm(memory.clone());
+    let mut vm_jit = new_jit_vm(memory);
+    assert_eq!(vm.params, *params);
+    assert_eq!(vm.scratchpad.len(), params.scratchpad_len());
+
+    let result = vm.calculate_hash(b"This is a test");
+    let result_jit = vm_jit.calculate_hash(b"This is a test");
+    assert_eq!(result.as_bytes(), result_jit.as_bytes(), "{}", params.name);
+    assert_eq!(
+        expected,
+        u8_array_to_string(result.as_bytes()),
+        "{}",
+        params.name
+    );
+}
@@ -4,13 +4,21 @@ extern crate difference;
 
 use mithril::randomx::hash::{gen_program_aes_1rx4, gen_program_aes_4rx4};
 use mithril::randomx::m128::m128i;
+use mithril::randomx::params::RX_0;
 use mithril::randomx::program::{decode_instruction, Opcode, Program, MAX_REG};
 
 #[test]
 fn test_decode_instruction_imul_rcp() {
-    let bytes = (Opcode::IMUL_RCP as i64) - 1; //imm = 0
+    let limits = RX_0.opcode_limits();
+    let bytes = limits[Opcode::IMUL_RCP as usize] - 1; //imm = 0
     let mut register_usage = [-1; MAX_REG];
-    let instr = decode_instruction(bytes, 6, &mut register_usage);
+    let instr = decode_instruction(
+        bytes,
+        6,
+        &mut register_usage,
+        &limits,
+        RX_0.scratchpad_l3_mask(),
+    );
 
     assert_eq!(instr.op, Opcode::IMUL_RCP);
     assert_eq!(register_usage[0], -1);
@@ -19,7 +27,7 @@ fn test_decode_instruction_imul_rcp() {
 #[test]
 fn test_decode_program_1000() {
     let bytes = gen_test_program_nonce_1000();
-    let program = Program::from_bytes(bytes);
+    let program = Program::from_bytes(bytes, &RX_0);
     assert_diff!(EXPECTED_OUT_NONCE_1000, &program.to_string(), "\n", 0);
 
     assert_eq!(program.entropy[0], 0x2f620901f788e553);
@@ -43,7 +51,7 @@ fn test_decode_program_1000() {
 #[test]
 fn test_decode_program_1002() {
     let bytes = gen_test_program_nonce_1002();
-    let program = Program::from_bytes(bytes);
+    let program = Program::from_bytes(bytes, &RX_0);
     assert_diff!(EXPECTED_OUT_NONCE_1002, &program.to_string(), "\n", 0);
 
     assert_eq!(program.entropy[0], 0xebdaaac029c3dbbe);
@@ -67,7 +75,7 @@ fn test_decode_program_1002() {
 #[test]
 fn test_decode_program_666() {
     let bytes = gen_test_program_nonce_666();
-    let program = Program::from_bytes(bytes);
+    let program = Program::from_bytes(bytes, &RX_0);
     assert_diff!(EXPECTED_OUT_NONCE_666, &program.to_string(), "\n", 0);
 
     assert_eq!(program.entropy[0], 0x5dcde953f1956205);
@@ -91,7 +99,7 @@ fn test_decode_program_666() {
 #[test]
 fn test_decode_program_1_4rx4_and_register_usage() {
     let bytes = gen_test_program_1_4rx4_with_register_usage();
-    let program = Program::from_bytes(bytes);
+    let program = Program::from_bytes(bytes, &RX_0);
     assert_diff!(
         EXPECTED_OUT_WITH_REGISTER_USAGE_1,
         &program.to_string(),
@@ -111,7 +119,7 @@ fn test_decode_program_1_4rx4_and_register_usage() {
 #[test]
 fn test_decode_program_2_4rx4_and_register_usage() {
     let bytes = gen_test_program_2_4rx4_with_register_usage();
-    let program = Program::from_bytes(bytes);
+    let program = Program::from_bytes(bytes, &RX_0);
     assert_diff!(
         EXPECTED_OUT_WITH_REGISTER_USAGE_2,
         &program.to_string(),
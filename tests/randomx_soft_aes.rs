@@ -0,0 +1,88 @@
+extern crate mithril;
+
+use mithril::randomx::hash::gen_program_aes_1rx4;
+use mithril::randomx::m128::m128i;
+use mithril::randomx::soft_aes::{self, AesModeGuard};
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
+use std::arch::x86_64::{_mm_aesdec_si128, _mm_aesenc_si128};
+
+#[test]
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
+fn test_soft_aes_equals_hardware_aes() {
+    if !soft_aes::detect_hardware_aes() {
+        return; //nothing to compare against
+    }
+
+    let mut state: [u32; 4] = [0x31903876, 0xbb7a2914, 0xb370f616, 0xd6f7e4f3];
+    let mut key: [u32; 4] = [0xb5a8ef67, 0x749809c8, 0xf349884a, 0x05c9f5ef];
+    for _ in 0..1000 {
+        let hw_state = to_m128i(state);
+        let hw_key = to_m128i(key);
+
+        let hw_enc = unsafe { m128i(_mm_aesenc_si128(hw_state.0, hw_key.0)) };
+        let hw_dec = unsafe { m128i(_mm_aesdec_si128(hw_state.0, hw_key.0)) };
+
+        let soft_enc = soft_aes::aesenc(state, key);
+        let soft_dec = soft_aes::aesdec(state, key);
+
+        assert_eq!(hw_enc, to_m128i(soft_enc));
+        assert_eq!(hw_dec, to_m128i(soft_dec));
+
+        state = soft_enc;
+        key = soft_dec;
+    }
+}
+
+#[test]
+#[allow(overflowing_literals)]
+fn test_gen_program_aes_1rx4_with_soft_aes() {
+    soft_aes::set_hardware_aes(false);
+
+    let input0 = m128i::from_i32(0x31903876, 0xbb7a2914, 0xb370f616, 0xd6f7e4f3);
+    let input1 = m128i::from_i32(0xb5a8ef67, 0x749809c8, 0xf349884a, 0x05c9f5ef);
+    let input2 = m128i::from_i32(0xa9a93ab0, 0x22e46d0a, 0x1a1fe305, 0xb42708c0);
+    let input3 = m128i::from_i32(0x68247034, 0xed99ee84, 0x438f563a, 0x138612ff);
+    let input: [m128i; 4] = [input0, input1, input2, input3];
+    let (result, new_seed) = gen_program_aes_1rx4(&input, 136);
+
+    assert_eq!(
+        result[0],
+        m128i::from_i32(0x27117584, 0x121aeeb3, 0x2f620901, 0xf788e553)
+    );
+    assert_eq!(
+        result[135],
+        m128i::from_i32(0x778d555d, 0x82dfe800, 0xedbe8cae, 0x2fe08b9f)
+    );
+    assert_eq!(
+        new_seed[0].as_i64(),
+        (0xbc020491ce094c80, 0x3eb2be0994e80b6a)
+    );
+    assert_eq!(
+        new_seed[1].as_i64(),
+        (0xb5ef741cae93a328, 0x2b0e778ebd40eb43)
+    );
+}
+
+//helper
+
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
+fn to_m128i(lanes: [u32; 4]) -> m128i {
+    m128i::from_i32(
+        lanes[3] as i32,
+        lanes[2] as i32,
+        lanes[1] as i32,
+        lanes[0] as i32,
+    )
+}
+
+#[test]
+fn test_aes_mode_guard() {
+    let _outer = AesModeGuard::set(false);
+    assert!(!soft_aes::hardware_aes());
+    if soft_aes::detect_hardware_aes() {
+        let inner = AesModeGuard::set(true);
+        assert!(soft_aes::hardware_aes());
+        drop(inner);
+    }
+    assert!(!soft_aes::hardware_aes());
+}
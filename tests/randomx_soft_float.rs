@@ -0,0 +1,160 @@
+extern crate mithril;
+
+use mithril::byte_string::u8_array_to_string;
+use mithril::randomx::memory::VmMemory;
+use mithril::randomx::rounding::{
+    get_rounding_mode, reset_rounding_mode, set_rounding_mode, ROUND_DOWN, ROUND_TO_NEAREST,
+    ROUND_TO_ZERO, ROUND_UP,
+};
+use mithril::randomx::soft_float;
+use mithril::randomx::vm::new_vm;
+use std::hint::black_box;
+use std::sync::Arc;
+
+const MODES: [u32; 4] = [ROUND_TO_NEAREST, ROUND_DOWN, ROUND_UP, ROUND_TO_ZERO];
+
+#[test]
+fn test_soft_float_equals_hardware() {
+    let mut rnd = 0x9E3779B97F4A7C15;
+    for _ in 0..200_000 {
+        let a = random_f64(&mut rnd);
+        let b = random_f64(&mut rnd);
+        for mode in MODES {
+            set_rounding_mode(mode);
+            let hw_add = black_box(black_box(a) + black_box(b));
+            let hw_sub = black_box(black_box(a) - black_box(b));
+            let hw_mul = black_box(black_box(a) * black_box(b));
+            let hw_div = black_box(black_box(a) / black_box(b));
+            let hw_sqrt = black_box(black_box(a).abs().sqrt());
+            reset_rounding_mode();
+
+            //the FPU runs with flush to zero/denormals are zero (MXCSR_DEFAULT),
+            //the software path implements gradual underflow
+            if is_subnormal(a) || is_subnormal(b) {
+                continue;
+            }
+            assert_same(hw_add, soft_float::add(a, b, mode), "add", a, b, mode);
+            assert_same(hw_sub, soft_float::sub(a, b, mode), "sub", a, b, mode);
+            assert_same(hw_mul, soft_float::mul(a, b, mode), "mul", a, b, mode);
+            assert_same(hw_div, soft_float::div(a, b, mode), "div", a, b, mode);
+            assert_same(hw_sqrt, soft_float::sqrt(a.abs(), mode), "sqrt", a, b, mode);
+        }
+    }
+}
+
+#[test]
+fn test_soft_float_special_values() {
+    let inf = f64::INFINITY;
+    for mode in MODES {
+        assert!(soft_float::sub(inf, inf, mode).is_nan());
+        assert!(soft_float::mul(inf, 0.0, mode).is_nan());
+        assert!(soft_float::div(0.0, 0.0, mode).is_nan());
+        assert!(soft_float::sqrt(-1.0, mode).is_nan());
+        assert_eq!(soft_float::div(1.0, 0.0, mode), inf);
+        assert_eq!(soft_float::sqrt(-0.0, mode).to_bits(), (-0.0f64).to_bits());
+    }
+    assert_eq!(soft_float::sub(1.0, 1.0, ROUND_TO_NEAREST).to_bits(), 0);
+    assert_eq!(
+        soft_float::sub(1.0, 1.0, ROUND_DOWN).to_bits(),
+        (-0.0f64).to_bits()
+    );
+    assert_eq!(soft_float::mul(f64::MAX, 2.0, ROUND_TO_ZERO), f64::MAX);
+    assert_eq!(soft_float::mul(f64::MAX, 2.0, ROUND_UP), inf);
+    assert_eq!(soft_float::mul(-f64::MAX, 2.0, ROUND_UP), -f64::MAX);
+
+    //gradual underflow
+    let min_subnormal = f64::from_bits(1);
+    assert_eq!(
+        soft_float::div(f64::MIN_POSITIVE, 2.0, ROUND_TO_NEAREST).to_bits(),
+        0x0008000000000000
+    );
+    assert_eq!(
+        soft_float::mul(min_subnormal, 0.5, ROUND_TO_NEAREST).to_bits(),
+        0
+    );
+    assert_eq!(soft_float::mul(min_subnormal, 0.5, ROUND_UP).to_bits(), 1);
+    assert_eq!(
+        soft_float::add(min_subnormal, min_subnormal, ROUND_TO_NEAREST).to_bits(),
+        2
+    );
+    assert_eq!(
+        soft_float::sqrt(min_subnormal, ROUND_TO_NEAREST),
+        2.2227587494850775e-162
+    );
+}
+
+#[test]
+fn test_calculate_hash_with_soft_float() {
+    let mut vm = new_vm(Arc::new(VmMemory::light(b"test key 000")));
+    vm.soft_float = true;
+
+    set_rounding_mode(ROUND_UP);
+
+    let result = vm.calculate_hash(b"This is a test");
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(result.as_bytes())
+    );
+
+    let result = vm.calculate_hash(b"Lorem ipsum dolor sit amet");
+    assert_eq!(
+        "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969",
+        u8_array_to_string(result.as_bytes())
+    );
+
+    //the FPU state of the thread is not touched
+    assert_eq!(get_rounding_mode(), ROUND_UP);
+    reset_rounding_mode();
+}
+
+//helper
+
+//compared on the bits, the FPU treats denormals as zero
+fn is_subnormal(f: f64) -> bool {
+    let bits = f.to_bits() & !0x8000000000000000;
+    bits != 0 && bits < 0x0010000000000000
+}
+
+fn assert_same(hw: f64, soft: f64, op: &str, a: f64, b: f64, mode: u32) {
+    let soft_bits = soft.to_bits() & !0x8000000000000000;
+    if soft_bits != 0 && soft_bits < 0x0020000000000000 {
+        return; //flushed to zero on the FPU
+    }
+    let same = hw.to_bits() == soft.to_bits() || (hw.is_nan() && soft.is_nan());
+    assert!(
+        same,
+        "{} mismatch for {:e} ({:x}), {:e} ({:x}) mode {}: hw {:x}, soft {:x}",
+        op,
+        a,
+        a.to_bits(),
+        b,
+        b.to_bits(),
+        mode,
+        hw.to_bits(),
+        soft.to_bits()
+    );
+}
+
+fn next(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+// random doubles with a bias towards interesting cases (near exponents,
+// subnormals, zero, infinity, NaN, huge and tiny values)
+fn random_f64(state: &mut u64) -> f64 {
+    let r = next(state);
+    let bits = match r % 16 {
+        0 => r >> 12, //subnormal
+        1 => (r & 0x8000000000000000) | 0x7FF0000000000000,
+        2 => 0x7FF8000000000000 | (r >> 20),
+        3 => r & 0x8000000000000000, //signed zero
+        4 => (r & 0x800FFFFFFFFFFFFF) | 0x7FE0000000000000,
+        5 => (r & 0x800FFFFFFFFFFFFF) | 0x0010000000000000,
+        6 => (r & 0x800FFFFFFFFFFFFF) | 0x3FF0000000000000 | (r & 0x7),
+        _ => (r & 0x800FFFFFFFFFFFFF) | ((0x3F0 + (r >> 52) % 32) << 52),
+    };
+    f64::from_bits(bits)
+}
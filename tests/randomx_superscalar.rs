@@ -2,7 +2,9 @@ extern crate mithril;
 #[macro_use(assert_diff)]
 extern crate difference;
 
-use mithril::randomx::superscalar::{Blake2Generator, ScProgram};
+use mithril::randomx::common::randomx_reciprocal;
+use mithril::randomx::jit::SuperscalarJit;
+use mithril::randomx::superscalar::{Blake2Generator, ScOpcode, ScProgram};
 
 #[test]
 fn test_generate_1() {
@@ -29,6 +31,41 @@ fn test_generate_rage() {
 	assert_diff!(EXPECTED_SUPERSCALAR_PROG_RAGE, &prog.to_string(), "\n", 0);
 }
 
+#[test]
+fn test_reciprocal_precomputed() {
+	let mut gen = Blake2Generator::new(b"test key 000", 0);
+	let prog = ScProgram::generate(&mut gen);
+	for instr in &prog.prog {
+		if instr.info.op == ScOpcode::IMUL_RCP {
+			assert_eq!(instr.reciprocal, randomx_reciprocal(instr.imm32 as u64));
+		} else {
+			assert_eq!(instr.reciprocal, 0);
+		}
+	}
+}
+
+#[test]
+fn test_compiled_matches_interpreter() {
+	//a cache of one zero line, the mixing does not change the registers
+	let cache = [0; 8];
+	for key in [&b"test key 000"[..], b"666", b"RageAgainstTheMachine"] {
+		let mut gen = Blake2Generator::new(key, 0);
+		let prog = ScProgram::generate(&mut gen);
+		let jit = SuperscalarJit::compile(std::slice::from_ref(&prog), 1).unwrap();
+
+		for i in 0..100u64 {
+			let mut ds = [0; 8];
+			for (r, v) in ds.iter_mut().enumerate() {
+				*v = (i + 1).wrapping_mul(0x9E3779B97F4A7C15).rotate_left(r as u32 * 8);
+			}
+			let mut ds_jit = ds;
+			prog.execute(&mut ds);
+			jit.execute(&mut ds_jit, &cache, i);
+			assert_eq!(ds, ds_jit);
+		}
+	}
+}
+
 //helper + testdata
 
 const EXPECTED_SUPERSCALAR_PROG_1: &str = r#"op: IMUL_R, src: 0, dst: 3
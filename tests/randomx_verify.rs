@@ -0,0 +1,80 @@
+extern crate mithril;
+
+use mithril::randomx::verify::{ShareVerifier, VerifyError};
+use mithril::stratum::stratum_data::Share;
+
+const SEED_HASH: &str = "15564c3122550436919ac2f8a71baf7cbaf9a4117b842d7f2b19dfd27dd178e9";
+//nonce 000006ad replaced with 00000000
+const BLOB: &str = "0e0e8bb48b8406bf43039198b7712a35031e0607036ebf9afb3096977e7b8fb88c751430e96b020000000082bd221c5e282d0533c5dcca38f30babc2e62cd3aa03a965f8aec8ad6f129f5211";
+const HASH: &str = "312a2ef18681e7b065f87e56b2627f0a11e19b30415314efa898a13f407f5d08";
+
+#[test]
+fn test_verify_share() {
+    let mut verifier = ShareVerifier::new();
+
+    let result = verifier
+        .verify(SEED_HASH, BLOB, "ffffffff", &share("000006ad", HASH))
+        .unwrap();
+    assert_eq!(result.hash, HASH);
+    assert!(result.hash_matches);
+    assert!(result.meets_target);
+    assert!(result.is_valid());
+
+    //same seed, no rebuild of the seed memory
+    let result = verifier
+        .verify(SEED_HASH, BLOB, "01000000", &share("000006ad", HASH))
+        .unwrap();
+    assert!(result.hash_matches);
+    assert!(!result.meets_target);
+    assert!(!result.is_valid());
+
+    let result = verifier
+        .verify(SEED_HASH, BLOB, "ffffffff", &share("000006ae", HASH))
+        .unwrap();
+    assert!(!result.hash_matches);
+    assert_ne!(result.hash, HASH);
+
+    //the VM is rebuilt for another seed and again for the previous one
+    let other_seed = "0000000000000000000000000000000000000000000000000000000000000000";
+    let result = verifier
+        .verify(other_seed, BLOB, "ffffffff", &share("000006ad", HASH))
+        .unwrap();
+    assert!(!result.hash_matches);
+    let result = verifier
+        .verify(SEED_HASH, BLOB, "ffffffff", &share("000006ad", HASH))
+        .unwrap();
+    assert!(result.is_valid());
+}
+
+#[test]
+fn test_verify_invalid_input() {
+    let mut verifier = ShareVerifier::new();
+
+    assert_eq!(
+        verifier.verify("xyz", BLOB, "ffffffff", &share("000006ad", HASH)),
+        Err(VerifyError::InvalidSeedHash("xyz".to_string()))
+    );
+    assert_eq!(
+        verifier.verify(SEED_HASH, &BLOB[..80], "ffffffff", &share("000006ad", HASH)),
+        Err(VerifyError::InvalidBlob(BLOB[..80].to_string()))
+    );
+    assert_eq!(
+        verifier.verify(SEED_HASH, BLOB, "ffffffff", &share("6ad", HASH)),
+        Err(VerifyError::InvalidNonce("6ad".to_string()))
+    );
+    assert_eq!(
+        verifier.verify(SEED_HASH, BLOB, "00000000", &share("000006ad", HASH)),
+        Err(VerifyError::InvalidTarget("00000000".to_string()))
+    );
+}
+
+//helper
+
+fn share(nonce: &str, hash: &str) -> Share {
+    Share {
+        miner_id: "miner".to_string(),
+        job_id: "job".to_string(),
+        nonce: nonce.to_string(),
+        hash: hash.to_string(),
+    }
+}
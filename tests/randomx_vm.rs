@@ -3,16 +3,23 @@ extern crate mithril;
 
 use self::blake2b_simd::blake2b;
 use mithril::byte_string::{string_to_u8_array, u8_array_to_string};
-use mithril::randomx::common::randomx_reciprocal;
+use mithril::randomx::bytecode::ByteInstr;
+use mithril::randomx::common::{randomx_reciprocal, u64_from_i32_imm};
 use mithril::randomx::hash::gen_program_aes_4rx4;
 use mithril::randomx::m128::m128d;
-use mithril::randomx::memory::VmMemory;
+use mithril::randomx::memory::{MemoryRegion, VmMemory};
+use mithril::randomx::params::{RandomxParams, RX_0};
 use mithril::randomx::program::{
     a_reg, e_reg, f_reg, r_reg, Instr, Mode, Opcode, Program, Store, REG_NEEDS_DISPLACEMENT,
     REG_NEEDS_DISPLACEMENT_IX,
 };
-use mithril::randomx::vm::{hash_to_m128i_array, new_register, new_vm, Vm};
+use mithril::randomx::rounding;
+use mithril::randomx::vm::{
+    hash_to_m128i_array, is_zero_or_power_of_2, new_register, new_vm, NoPendingHash, Vm,
+};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
+use std::thread;
 
 #[allow(overflowing_literals)]
 const IMM32: i32 = 0xc0cb96d2; //3234567890
@@ -121,6 +128,141 @@ fn test_calculate_hash_3_with_full_memory() {
     );
 }
 
+#[test]
+fn test_calculate_hash_with_shared_full_memory() {
+    let mem = Arc::new(VmMemory::full(&string_to_u8_array(
+        "15564c3122550436919ac2f8a71baf7cbaf9a4117b842d7f2b19dfd27dd178e9",
+    )));
+    let seed = string_to_u8_array("0e0e8bb48b8406bf43039198b7712a35031e0607036ebf9afb3096977e7b8fb88c751430e96b02000006ad82bd221c5e282d0533c5dcca38f30babc2e62cd3aa03a965f8aec8ad6f129f5211");
+
+    //first round initialises the dataset items concurrently, second round reads them
+    for _ in 0..2 {
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let mem = mem.clone();
+                let seed = seed.clone();
+                thread::spawn(move || new_vm(mem).calculate_hash(&seed))
+            })
+            .collect();
+        for handle in handles {
+            let result = handle.join().unwrap();
+            assert_eq!(
+                "312a2ef18681e7b065f87e56b2627f0a11e19b30415314efa898a13f407f5d08",
+                u8_array_to_string(result.as_bytes())
+            );
+        }
+    }
+}
+
+#[test]
+fn test_pipelined_hash_matches_calculate_hash() {
+    let mem = Arc::new(VmMemory::light(b"test key 000"));
+    let mut vm = new_vm(mem.clone());
+    let mut vm_pipelined = new_vm(mem);
+    let inputs: Vec<Vec<u8>> = (0..4u32)
+        .map(|i| [b"This is a test".as_ref(), &i.to_le_bytes()].concat())
+        .collect();
+
+    vm_pipelined.calculate_hash_first(&inputs[0]);
+    for (i, input) in inputs.iter().enumerate() {
+        let result = match inputs.get(i + 1) {
+            Some(next_input) => vm_pipelined.calculate_hash_next(next_input),
+            None => vm_pipelined.calculate_hash_last(),
+        }
+        .unwrap();
+        assert_eq!(result, vm.calculate_hash(input), "input {}", i);
+    }
+
+    //a new series after a one-shot hash
+    vm_pipelined.calculate_hash(b"Lorem ipsum dolor sit amet");
+    vm_pipelined.calculate_hash_first(b"This is a test");
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(vm_pipelined.calculate_hash_last().unwrap().as_bytes())
+    );
+    //the series ends with the last hash
+    assert_eq!(vm_pipelined.calculate_hash_last(), Err(NoPendingHash));
+}
+
+#[test]
+fn test_calculate_hash_next_without_first() {
+    let mut vm = new_test_vm();
+    assert_eq!(
+        vm.calculate_hash_next(b"This is a test"),
+        Err(NoPendingHash)
+    );
+    assert_eq!(vm.calculate_hash_last(), Err(NoPendingHash));
+}
+
+/// A control state different from the default in more than the rounding mode:
+/// FTZ cleared, DAZ set and the denormal exception unmasked (x86-64),
+/// flush-to-zero and default-NaN set (aarch64).
+#[cfg(target_arch = "x86_64")]
+fn caller_fp_state(mode: u32) -> u64 {
+    let mxcsr = (rounding::MXCSR_DEFAULT & !(1 << 15) & !(1 << 8)) | (1 << 6) | (mode << 13);
+    mxcsr as u64
+}
+
+#[cfg(target_arch = "aarch64")]
+fn caller_fp_state(mode: u32) -> u64 {
+    //FPCR.RMode is numbered differently: 1 = +inf, 2 = -inf
+    let rmode = [0, 2, 1, 3][mode as usize];
+    (1 << 25) | (1 << 24) | (rmode << 22)
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn caller_fp_state(mode: u32) -> u64 {
+    mode as u64
+}
+
+#[test]
+fn test_calculate_hash_restores_fp_state() {
+    let mut vm = new_vm(Arc::new(VmMemory::light(b"test key 000")));
+    let state = caller_fp_state(ROUND_DOWN);
+    rounding::set_fp_state(state);
+
+    let result = vm.calculate_hash(b"This is a test");
+    let after = rounding::fp_state();
+    rounding::reset_rounding_mode();
+    assert_eq!(after, state);
+
+    assert_eq!(
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+        u8_array_to_string(result.as_bytes())
+    );
+}
+
+#[test]
+fn test_calculate_hash_restores_fp_state_on_panic() {
+    //scratchpad too small, the first scratchpad read panics
+    let mut vm = new_test_vm();
+    vm.scratchpad = MemoryRegion::new(8);
+    let state = caller_fp_state(ROUND_TO_ZERO);
+    rounding::set_fp_state(state);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| vm.calculate_hash(b"This is a test")));
+    let after = rounding::fp_state();
+    rounding::reset_rounding_mode();
+    assert!(result.is_err());
+    assert_eq!(after, state);
+}
+
+#[test]
+fn test_run_restores_fp_state() {
+    let mut vm = new_vm(Arc::new(VmMemory::light(b"test key 000")));
+    let hash = blake2b(b"This is a test");
+    let seed = hash_to_m128i_array(&hash);
+    vm.init_scratchpad(&seed);
+    vm.reset_rounding_mode();
+
+    let state = caller_fp_state(ROUND_UP);
+    rounding::set_fp_state(state);
+    vm.run(&seed);
+    let after = rounding::fp_state();
+    rounding::reset_rounding_mode();
+    assert_eq!(after, state);
+}
+
 #[test]
 fn test_init_scratchpad() {
     let mut vm = new_test_vm();
@@ -151,7 +293,7 @@ fn test_init_vm() {
     let hash = blake2b("This is a test".as_bytes());
     let seed = hash_to_m128i_array(&hash);
     let seed = vm.init_scratchpad(&seed);
-    let prog = Program::from_bytes(gen_program_aes_4rx4(&seed, 136));
+    let prog = Program::from_bytes(gen_program_aes_4rx4(&seed, 136), &RX_0);
     vm.init_vm(&prog);
 
     assert_eq!(
@@ -477,12 +619,11 @@ fn test_exec_iadd_rs() {
         unsigned_imm: false,
         mode: Mode::Shft(3),
         target: None,
-        effect: Vm::exec_iadd_rs,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0x8000000000000000;
     vm.reg.r[1] = 0x1000000000000000;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[0], 0x0);
 }
 
@@ -496,12 +637,11 @@ fn test_exec_iadd_rs_with_immediate() {
         unsigned_imm: false,
         mode: Mode::Shft(2),
         target: None,
-        effect: Vm::exec_iadd_rs,
     };
     let mut vm = new_test_vm();
     vm.reg.r[REG_NEEDS_DISPLACEMENT_IX] = 0x8000000000000000;
     vm.reg.r[1] = 0x2000000000000000;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[REG_NEEDS_DISPLACEMENT_IX], IMM64);
 }
 
@@ -516,12 +656,11 @@ fn test_exec_isub_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_isub_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 1;
     vm.reg.r[1] = 0xFFFFFFFF;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[0], 0xFFFFFFFF00000002);
 }
 
@@ -535,11 +674,10 @@ fn test_exec_isub_r_with_immediate() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_isub_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[0], (!IMM64 + 1));
 }
 
@@ -553,13 +691,12 @@ fn test_exec_imul_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imul_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[0], 0x28723424A9108E51);
 }
 
@@ -573,12 +710,11 @@ fn test_exec_imul_r_with_immediate() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imul_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 1;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[0], IMM64);
 }
 
@@ -592,13 +728,12 @@ fn test_exec_imulh_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imulh_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[0], 0xB4676D31D2B34883);
 }
 
@@ -612,13 +747,12 @@ fn test_exec_ismulh_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ismulh_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[0], 0x02D93EF1269D3EE5);
 }
 
@@ -632,12 +766,11 @@ fn test_exec_ineg_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ineg_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xFFFFFFFFFFFFFFFF;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 1);
 }
@@ -652,12 +785,11 @@ fn test_exec_ineg_r_overflow() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ineg_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0x0;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0);
 }
@@ -672,13 +804,12 @@ fn test_exec_ixor_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ixor_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0x8888888888888888;
     vm.reg.r[1] = 0xAAAAAAAAAAAAAAAA;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x2222222222222222);
 }
@@ -693,12 +824,11 @@ fn test_exec_ixor_r_with_immediate() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ixor_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xFFFFFFFFFFFFFFFF;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], !IMM64);
 }
@@ -713,13 +843,12 @@ fn test_exec_iror_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_iror_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 953360005391419562;
     vm.reg.r[1] = 4569451684712230561;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0xD835C455069D81EF);
 }
@@ -735,12 +864,11 @@ fn test_exec_iror_r_with_immediate() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_iror_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 953360005391419562;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0xD835C455069D81EF);
 }
@@ -755,13 +883,12 @@ fn test_exec_irol_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_irol_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 953360005391419562;
     vm.reg.r[1] = 4569451684712230561;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 6978065200552740799);
 }
@@ -777,12 +904,11 @@ fn test_exec_irol_r_with_immediate() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_irol_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 953360005391419562;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 6978065200552740799);
 }
@@ -797,13 +923,12 @@ fn test_exec_iswap_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_iswap_r,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 953360005391419562;
     vm.reg.r[1] = 4569451684712230561;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
     assert_eq!(vm.reg.r[0], 4569451684712230561);
     assert_eq!(vm.reg.r[1], 953360005391419562);
 }
@@ -818,12 +943,11 @@ fn test_exec_fswap_r_from_f_reg() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fswap_r,
     };
     let mut vm = new_test_vm();
     vm.reg.f[0] = m128d::from_u64(953360005391419562, 4569451684712230561);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -841,12 +965,11 @@ fn test_exec_fswap_r_from_e_reg() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fswap_r,
     };
     let mut vm = new_test_vm();
     vm.reg.e[3] = m128d::from_u64(953360005391419562, 4569451684712230561);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[3],
@@ -864,7 +987,6 @@ fn test_exec_fadd_r_round_to_nearest() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fadd_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_TO_NEAREST);
@@ -872,7 +994,7 @@ fn test_exec_fadd_r_round_to_nearest() {
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.reg.a[1] = m128d::from_u64(0x402a26a86a60c8fb, 0x40b8f684057a59e1);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -890,7 +1012,6 @@ fn test_exec_fadd_r_round_down() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fadd_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_DOWN);
@@ -898,7 +1019,7 @@ fn test_exec_fadd_r_round_down() {
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.reg.a[1] = m128d::from_u64(0x402a26a86a60c8fb, 0x40b8f684057a59e1);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -916,7 +1037,6 @@ fn test_exec_fadd_r_round_up() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fadd_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_UP);
@@ -924,7 +1044,7 @@ fn test_exec_fadd_r_round_up() {
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.reg.a[1] = m128d::from_u64(0x402a26a86a60c8fb, 0x40b8f684057a59e1);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -942,7 +1062,6 @@ fn test_exec_fadd_r_round_to_zero() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fadd_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_TO_ZERO);
@@ -950,7 +1069,7 @@ fn test_exec_fadd_r_round_to_zero() {
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.reg.a[1] = m128d::from_u64(0x402a26a86a60c8fb, 0x40b8f684057a59e1);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -968,7 +1087,6 @@ fn test_exec_fsub_r_round_to_nearest() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsub_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_TO_NEAREST);
@@ -976,7 +1094,7 @@ fn test_exec_fsub_r_round_to_nearest() {
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.reg.a[1] = m128d::from_u64(0x402a26a86a60c8fb, 0x40b8f684057a59e1);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -994,7 +1112,6 @@ fn test_exec_fsub_r_round_down() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsub_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_DOWN);
@@ -1002,7 +1119,7 @@ fn test_exec_fsub_r_round_down() {
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.reg.a[1] = m128d::from_u64(0x402a26a86a60c8fb, 0x40b8f684057a59e1);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -1020,7 +1137,6 @@ fn test_exec_fsub_r_round_up() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsub_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_UP);
@@ -1028,7 +1144,7 @@ fn test_exec_fsub_r_round_up() {
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.reg.a[1] = m128d::from_u64(0x402a26a86a60c8fb, 0x40b8f684057a59e1);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -1046,7 +1162,6 @@ fn test_exec_fsub_r_round_to_zero() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsub_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_TO_ZERO);
@@ -1054,7 +1169,7 @@ fn test_exec_fsub_r_round_to_zero() {
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.reg.a[1] = m128d::from_u64(0x402a26a86a60c8fb, 0x40b8f684057a59e1);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -1072,11 +1187,10 @@ fn test_exec_fscal_r() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fscal_r,
     };
     let mut vm = new_test_vm();
     vm.reg.f[0] = m128d::from_u64(0x41dbc35cef248783, 0x40fdfdabb6173d07);
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -1094,7 +1208,6 @@ fn test_exec_fmul_r_round_to_nearest() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fmul_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_TO_NEAREST);
@@ -1102,7 +1215,7 @@ fn test_exec_fmul_r_round_to_nearest() {
     vm.reg.e[0] = m128d::from_u64(0x41dbc35cef248783, 0x40fdfdabb6173d07);
     vm.reg.a[1] = m128d::from_u64(0x40eba861aa31c7c0, 0x41c4561212ae2d50);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1120,7 +1233,6 @@ fn test_exec_fmul_r_round_round_down() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fmul_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_DOWN);
@@ -1128,7 +1240,7 @@ fn test_exec_fmul_r_round_round_down() {
     vm.reg.e[0] = m128d::from_u64(0x41dbc35cef248783, 0x40fdfdabb6173d07);
     vm.reg.a[1] = m128d::from_u64(0x40eba861aa31c7c0, 0x41c4561212ae2d50);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1146,7 +1258,6 @@ fn test_exec_fmul_r_round_up() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fmul_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_UP);
@@ -1154,7 +1265,7 @@ fn test_exec_fmul_r_round_up() {
     vm.reg.e[0] = m128d::from_u64(0x41dbc35cef248783, 0x40fdfdabb6173d07);
     vm.reg.a[1] = m128d::from_u64(0x40eba861aa31c7c0, 0x41c4561212ae2d50);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1172,7 +1283,6 @@ fn test_exec_fmul_r_round_to_zero() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fmul_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_TO_ZERO);
@@ -1180,7 +1290,7 @@ fn test_exec_fmul_r_round_to_zero() {
     vm.reg.e[0] = m128d::from_u64(0x41dbc35cef248783, 0x40fdfdabb6173d07);
     vm.reg.a[1] = m128d::from_u64(0x40eba861aa31c7c0, 0x41c4561212ae2d50);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1198,14 +1308,13 @@ fn test_exec_fsqrt_r_round_to_nearest() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsqrt_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_TO_NEAREST);
 
     vm.reg.e[0] = m128d::from_u64(0x41b6b21c11affea7, 0x40526a7e778d9824);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1223,14 +1332,13 @@ fn test_exec_fsqrt_r_round_up() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsqrt_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_UP);
 
     vm.reg.e[0] = m128d::from_u64(0x41b6b21c11affea7, 0x40526a7e778d9824);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1248,14 +1356,13 @@ fn test_exec_fsqrt_r_round_down() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsqrt_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_DOWN);
 
     vm.reg.e[0] = m128d::from_u64(0x41b6b21c11affea7, 0x40526a7e778d9824);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1273,14 +1380,13 @@ fn test_exec_fsqrt_r_round_to_zero() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsqrt_r,
     };
     let mut vm = new_test_vm();
     vm.set_rounding_mode(ROUND_TO_ZERO);
 
     vm.reg.e[0] = m128d::from_u64(0x41b6b21c11affea7, 0x40526a7e778d9824);
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1299,13 +1405,12 @@ fn test_exec_fadd_m() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fadd_m,
     };
     let mut vm = new_test_vm();
     vm.scratchpad[0] = 0x1234567890abcdef;
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.f[0] = m128d::zero();
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -1323,13 +1428,12 @@ fn test_exec_fsub_m() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fsub_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.f[0] = m128d::from_u64(0x3ffd2c97cc4ef015, 0xc1ce30b3c4223576);
     vm.scratchpad[0] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.f[0],
@@ -1347,14 +1451,13 @@ fn test_exec_cfround() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_cfround,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xFFFFFFFFFFFC6800;
 
     assert_eq!(vm.get_rounding_mode(), ROUND_TO_NEAREST); //new vm starts with default rounding mode
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.get_rounding_mode(), ROUND_TO_ZERO);
 }
@@ -1369,16 +1472,20 @@ fn test_exec_cbranch_taken() {
         imm: Some(0xFFFFFFFFC0CB9AD2),
         unsigned_imm: false,
         mode: Mode::Cond(3),
-        target: Some(100),
-        effect: Vm::exec_cbranch,
+        target: Some(2),
     };
     let mut vm = new_test_vm();
-    vm.pc = 200;
     vm.reg.r[0] = 0xFFFFFFFFFFFC6800;
+    vm.reg.r[1] = 1;
 
-    instr.execute(&mut vm);
+    //the taken branch skips the INEG_R
+    let code = [
+        byte_instr(&instr, &vm.params),
+        byte_instr(&ineg_r1(), &vm.params),
+    ];
+    vm.execute_bytecode(&code);
 
-    assert_eq!(vm.pc, 100)
+    assert_eq!(vm.reg.r[1], 1)
 }
 
 #[test]
@@ -1391,16 +1498,19 @@ fn test_exec_cbranch_not_taken() {
         imm: Some(0xFFFFFFFFC0CB9AD2),
         unsigned_imm: false,
         mode: Mode::Cond(3),
-        target: None,
-        effect: Vm::exec_cbranch,
+        target: Some(2),
     };
     let mut vm = new_test_vm();
-    vm.pc = 200;
     vm.reg.r[0] = 0;
+    vm.reg.r[1] = 1;
 
-    instr.execute(&mut vm);
+    let code = [
+        byte_instr(&instr, &vm.params),
+        byte_instr(&ineg_r1(), &vm.params),
+    ];
+    vm.execute_bytecode(&code);
 
-    assert_eq!(vm.pc, 200)
+    assert_eq!(vm.reg.r[1], u64::MAX)
 }
 
 #[test]
@@ -1414,13 +1524,12 @@ fn test_exec_istore_l1() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_istore,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFC6800;
     vm.reg.r[0] = 0xFFFFFFFFC0C802D2;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.scratchpad[0x19A0 / 8], 0xFFFFFFFFFFFC6800);
 }
@@ -1436,13 +1545,12 @@ fn test_exec_istore_l2() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_istore,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFC6800;
     vm.reg.r[0] = 0xFFFFFFFFC0C802D2;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.scratchpad[0x399A0 / 8], 0xFFFFFFFFFFFC6800);
 }
@@ -1458,13 +1566,12 @@ fn test_exec_istore_l3() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_istore,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFC6800;
     vm.reg.r[0] = 0xFFFFFFFFC0C802D2;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.scratchpad[0x1399A0 / 8], 0xFFFFFFFFFFFC6800);
 }
@@ -1479,13 +1586,12 @@ fn test_exec_iadd_m_l1() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_iadd_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x869);
 }
@@ -1500,13 +1606,12 @@ fn test_exec_iadd_m_l2() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_iadd_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0x38000 / 8] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x869);
 }
@@ -1521,13 +1626,12 @@ fn test_exec_iadd_m_l3() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_iadd_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0xb96d0 / 8] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x869);
 }
@@ -1542,13 +1646,12 @@ fn test_exec_isub_m_l1() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_isub_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 - 0x203);
 }
@@ -1563,13 +1666,12 @@ fn test_exec_isub_m_l2() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_isub_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0x38000 / 8] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 - 0x0203);
 }
@@ -1584,13 +1686,12 @@ fn test_exec_isub_m_l3() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_isub_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0xb96d0 / 8] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 - 0x0203);
 }
@@ -1605,13 +1706,12 @@ fn test_exec_imul_m_l1() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imul_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 * 0x203);
 }
@@ -1626,13 +1726,12 @@ fn test_exec_imul_m_l2() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imul_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0x38000 / 8] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 * 0x0203);
 }
@@ -1647,13 +1746,12 @@ fn test_exec_imul_m_l3() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imul_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0xb96d0 / 8] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 * 0x0203);
 }
@@ -1668,14 +1766,13 @@ fn test_exec_imulh_m_l1() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imulh_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.scratchpad[0] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0xB4676D31D2B34883);
 }
@@ -1690,14 +1787,13 @@ fn test_exec_imulh_m_l2() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imulh_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.scratchpad[0x38000 / 8] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0xB4676D31D2B34883);
 }
@@ -1712,14 +1808,13 @@ fn test_exec_imulh_m_l3() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imulh_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.scratchpad[0xb96d0 / 8] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0xB4676D31D2B34883);
 }
@@ -1734,14 +1829,13 @@ fn test_exec_ismulh_m_l1() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ismulh_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.scratchpad[0] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x02D93EF1269D3EE5);
 }
@@ -1756,14 +1850,13 @@ fn test_exec_ismulh_m_l2() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ismulh_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.scratchpad[0x38000 / 8] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x02D93EF1269D3EE5);
 }
@@ -1778,14 +1871,13 @@ fn test_exec_ismulh_m_l3() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ismulh_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0xBC550E96BA88A72B;
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.scratchpad[0xb96d0 / 8] = 0xF5391FA9F18D6273;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x02D93EF1269D3EE5);
 }
@@ -1800,12 +1892,11 @@ fn test_exec_imul_rcp_non_zero_imm_from_reg() {
         unsigned_imm: true,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imul_rcp,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 666;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x2B2462DE8506B218);
 }
@@ -1820,12 +1911,11 @@ fn test_exec_imul_rcp_zero_imm() {
         unsigned_imm: true,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_imul_rcp,
     };
     let mut vm = new_test_vm();
     vm.reg.r[0] = 0x666;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666);
 }
@@ -1840,13 +1930,12 @@ fn test_exec_ixor_m_l1() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ixor_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 ^ 0x203);
 }
@@ -1861,13 +1950,12 @@ fn test_exec_ixor_m_l2() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ixor_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0x38000 / 8] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 ^ 0x203);
 }
@@ -1882,13 +1970,12 @@ fn test_exec_ixor_m_l3() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_ixor_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
     vm.reg.r[0] = 0x666;
     vm.scratchpad[0xb96d0 / 8] = 0x0203;
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(vm.reg.r[0], 0x666 ^ 0x203);
 }
@@ -1903,7 +1990,6 @@ fn test_exec_fdiv_m_round_to_nearest() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fdiv_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
@@ -1913,7 +1999,7 @@ fn test_exec_fdiv_m_round_to_nearest() {
     vm.config.e_mask[1] = 0x39000000001ba31e;
     vm.scratchpad[0] = 0x8b2460d9d350a1b6;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1931,7 +2017,6 @@ fn test_exec_fdiv_m_round_down_and_to_zero() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fdiv_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
@@ -1941,7 +2026,7 @@ fn test_exec_fdiv_m_round_down_and_to_zero() {
     vm.config.e_mask[1] = 0x39000000001ba31e;
     vm.scratchpad[0] = 0x8b2460d9d350a1b6;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1959,7 +2044,6 @@ fn test_exec_fdiv_m_round_to_zero() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fdiv_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
@@ -1969,7 +2053,7 @@ fn test_exec_fdiv_m_round_to_zero() {
     vm.config.e_mask[1] = 0x39000000001ba31e;
     vm.scratchpad[0] = 0x8b2460d9d350a1b6;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -1987,7 +2071,6 @@ fn test_exec_fdiv_m_round_up() {
         unsigned_imm: false,
         mode: Mode::None,
         target: None,
-        effect: Vm::exec_fdiv_m,
     };
     let mut vm = new_test_vm();
     vm.reg.r[1] = 0xFFFFFFFFFFFFE930;
@@ -1997,7 +2080,7 @@ fn test_exec_fdiv_m_round_up() {
     vm.config.e_mask[1] = 0x39000000001ba31e;
     vm.scratchpad[0] = 0x8b2460d9d350a1b6;
 
-    instr.execute(&mut vm);
+    execute(&mut vm, &instr);
 
     assert_eq!(
         vm.reg.e[0],
@@ -2016,3 +2099,116 @@ fn test_randomx_reciprocal() {
 fn new_test_vm() -> Vm {
     new_vm(Arc::new(VmMemory::no_memory()))
 }
+
+/// Runs `instr` in the interpreter
+fn execute(vm: &mut Vm, instr: &Instr) {
+    let code = [byte_instr(instr, &vm.params)];
+    vm.execute_bytecode(&code);
+}
+
+/// `instr` in the form `Bytecode::decode` produces for it
+fn byte_instr(instr: &Instr, params: &RandomxParams) -> ByteInstr {
+    let imm = instr.imm.map_or(0, u64_from_i32_imm);
+    let mut byte_instr = ByteInstr {
+        op: instr.op,
+        dst: reg_ix(&instr.dst),
+        src: reg_ix(&instr.src),
+        shift: 0,
+        target: 0,
+        imm: 0,
+        src_mask: !0,
+        mask: 0,
+    };
+    match instr.op {
+        Opcode::IADD_RS => {
+            if let Mode::Shft(shift) = instr.mode {
+                byte_instr.shift = shift;
+            }
+            byte_instr.imm = imm;
+        }
+        Opcode::ISUB_R | Opcode::IMUL_R | Opcode::IXOR_R | Opcode::IROR_R | Opcode::IROL_R => {
+            if instr.src == Store::NONE {
+                byte_instr.imm = imm;
+                byte_instr.src_mask = 0;
+            }
+        }
+        Opcode::IMUL_RCP => {
+            let divisor = instr.imm.unwrap() as u64;
+            if is_zero_or_power_of_2(divisor) {
+                byte_instr.op = Opcode::NOP;
+            } else {
+                byte_instr.imm = randomx_reciprocal(divisor & 0xFFFFFFFF);
+            }
+        }
+        Opcode::IADD_M
+        | Opcode::ISUB_M
+        | Opcode::IMUL_M
+        | Opcode::IMULH_M
+        | Opcode::ISMULH_M
+        | Opcode::IXOR_M
+        | Opcode::FADD_M
+        | Opcode::FSUB_M
+        | Opcode::FDIV_M => {
+            byte_instr.imm = imm;
+            byte_instr.mask = scratchpad_mask(&instr.src, params);
+            if let Store::L3(_) = instr.src {
+                byte_instr.src_mask = 0;
+            }
+        }
+        Opcode::ISTORE => {
+            byte_instr.imm = imm;
+            byte_instr.mask = scratchpad_mask(&instr.dst, params);
+        }
+        Opcode::FSWAP_R => {
+            byte_instr.src = matches!(instr.dst, Store::E(_)) as u8;
+        }
+        Opcode::CBRANCH => {
+            let cond = match instr.mode {
+                Mode::Cond(cond) => cond,
+                _ => 0,
+            };
+            let shift = cond as u64 + params.jump_offset;
+            let mut branch_imm = imm | 1 << shift;
+            if params.jump_offset > 0 || shift > 0 {
+                branch_imm &= !(1 << (shift - 1));
+            }
+            byte_instr.imm = branch_imm;
+            byte_instr.shift = shift as u8;
+            byte_instr.mask = params.condition_mask() << shift;
+            byte_instr.target = instr.target.unwrap_or(0) as u16;
+        }
+        Opcode::CFROUND => {
+            byte_instr.imm = (instr.imm.unwrap() & 63) as u64;
+        }
+        _ => {}
+    }
+    byte_instr
+}
+
+fn reg_ix(store: &Store) -> u8 {
+    match store {
+        Store::R(i) | Store::F(i) | Store::E(i) | Store::A(i) => *i as u8,
+        Store::L1(reg) | Store::L2(reg) | Store::L3(reg) => reg_ix(reg),
+        _ => 0,
+    }
+}
+
+fn scratchpad_mask(store: &Store, params: &RandomxParams) -> u64 {
+    match store {
+        Store::L1(_) => params.scratchpad_l1_mask(),
+        Store::L2(_) => params.scratchpad_l2_mask(),
+        _ => params.scratchpad_l3_mask(),
+    }
+}
+
+fn ineg_r1() -> Instr {
+    Instr {
+        op: Opcode::INEG_R,
+        dst: r_reg(1),
+        src: Store::NONE,
+        imm: None,
+        unsigned_imm: false,
+        mode: Mode::None,
+        target: None,
+    }
+}
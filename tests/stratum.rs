@@ -44,6 +44,22 @@ fn test_ser_login_json() {
     );
 }
 
+#[test]
+fn test_ser_get_job_json() {
+    let get_job_req = stratum_data::GetJobRequest {
+        id: 1,
+        method: "getjob".to_string(),
+        params: stratum_data::GetJobParams {
+            id: "miner".to_string(),
+        },
+    };
+
+    assert_eq!(
+        serde_json::to_string(&get_job_req).unwrap(),
+        "{\"id\":1,\"method\":\"getjob\",\"params\":{\"id\":\"miner\"}}"
+    );
+}
+
 #[test]
 fn test_parse_method_with_method_field() {
     let method: stratum_data::Method =
@@ -112,11 +128,13 @@ fn test_parse_line_dispatch_result_initial_job() {
         stratum::StratumAction::Job {
             miner_id,
             seed_hash,
+            next_seed_hash,
             blob,
             job_id,
             target,
         } => {
             assert_eq!(miner_id, "930717205908149");
+            assert_eq!(next_seed_hash, None);
             assert_eq!(
                 seed_hash,
                 "ae2b3c3b6e013f9c3512a94a4e9f2cf0552f28a3dd0383ba7bac3f54ec06b56f"
@@ -208,11 +226,13 @@ fn test_parse_line_dispatch_job_method() {
         stratum::StratumAction::Job {
             miner_id,
             seed_hash,
+            next_seed_hash,
             blob,
             job_id,
             target,
         } => {
             assert_eq!(miner_id, "test_miner_id");
+            assert_eq!(next_seed_hash, None);
             assert_eq!(blob, "0606fcb29bcf051b9c7bfc60c98885de404ef48f721f09b8f51d37faf280470880bd120d4e9e0500000000577192c076fed53a24372bc43a3bed1d448a061ad06a262ac5e7f6803a28ccc705");
             assert_eq!(job_id, "878440772206522");
             assert_eq!(target, "169f0200");
@@ -225,6 +245,66 @@ fn test_parse_line_dispatch_job_method() {
     }
 }
 
+#[test]
+fn test_parse_line_dispatch_job_method_with_next_seed_hash() {
+    let (tx, rx) = unbounded();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let line = r#"{
+        "jsonrpc":"2.0",
+        "method":"job",
+        "params":{
+            "blob":"0606fcb29bcf051b9c7bfc60c98885de404ef48f721f09b8f51d37faf280470880bd120d4e9e0500000000577192c076fed53a24372bc43a3bed1d448a061ad06a262ac5e7f6803a28ccc705",
+            "job_id":"878440772206522",
+            "target":"169f0200",
+            "seed_hash":"ae2b3c3b6e013f9c3512a94a4e9f2cf0552f28a3dd0383ba7bac3f54ec06b56f",
+            "next_seed_hash":"15564c3122550436919ac2f8a71baf7cbaf9a4117b842d7f2b19dfd27dd178e9"
+        }}"#;
+
+    thread::spawn(move || {
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    });
+
+    match rx.recv().unwrap() {
+        stratum::StratumAction::Job { next_seed_hash, .. } => assert_eq!(
+            next_seed_hash,
+            Some("15564c3122550436919ac2f8a71baf7cbaf9a4117b842d7f2b19dfd27dd178e9".to_string())
+        ),
+        result => panic!("Wrong result returned: {:?}", result),
+    }
+}
+
+#[test]
+fn test_parse_line_dispatch_get_job_result() {
+    let (tx, rx) = unbounded();
+    let miner_id_mutex = Arc::new(Mutex::new(Option::Some("test_miner_id".to_string())));
+
+    let line = r#"{
+        "id":1,
+        "jsonrpc":"2.0",
+        "error":null,
+        "result":{
+            "blob":"0606fcb29bcf051b9c7bfc60c98885de404ef48f721f09b8f51d37faf280470880bd120d4e9e0500000000577192c076fed53a24372bc43a3bed1d448a061ad06a262ac5e7f6803a28ccc705",
+            "job_id":"878440772206523",
+            "target":"169f0200",
+            "seed_hash":"ae2b3c3b6e013f9c3512a94a4e9f2cf0552f28a3dd0383ba7bac3f54ec06b56f"
+        }}"#;
+
+    thread::spawn(move || {
+        stratum::parse_line_dispatch_result(line, &tx, &miner_id_mutex);
+    });
+
+    match rx.recv().unwrap() {
+        stratum::StratumAction::Job {
+            miner_id, job_id, ..
+        } => {
+            assert_eq!(miner_id, "test_miner_id");
+            assert_eq!(job_id, "878440772206523");
+        }
+        result => panic!("Wrong result returned: {:?}", result),
+    }
+}
+
 #[test]
 fn test_parse_line_dispatch_job_method_missing_miner_id() {
     let (tx, rx) = unbounded();
@@ -1,8 +1,10 @@
 extern crate mithril;
 
 use mithril::mithril_config::DonationConfig;
+use mithril::randomx::memory::MemoryMode;
 use mithril::timer;
-use mithril::worker::worker_pool::WorkerConfig;
+use mithril::worker::affinity::Affinity;
+use mithril::worker::worker_pool::{ShareErrorAction, WorkerConfig};
 
 #[test]
 fn test_interval_mod_setup_donation_disabled_auto_tune_enabled() {
@@ -11,6 +13,16 @@ fn test_interval_mod_setup_donation_disabled_auto_tune_enabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        jit: true,
+        eager_dataset: false,
+        dataset_init_threads: None,
+        memory_mode: MemoryMode::Full,
+        nonce_ranges: false,
+        affinity: Affinity::None,
+        huge_pages: true,
+        verify_shares: false,
+        max_share_error_rate: 0.1,
+        share_error_action: ShareErrorAction::Restart,
     };
     let donation_conf = DonationConfig { percentage: 0.0 };
 
@@ -26,6 +38,16 @@ fn test_interval_mod_setup_donation_below_threshold_auto_tune_enabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        jit: true,
+        eager_dataset: false,
+        dataset_init_threads: None,
+        memory_mode: MemoryMode::Full,
+        nonce_ranges: false,
+        affinity: Affinity::None,
+        huge_pages: true,
+        verify_shares: false,
+        max_share_error_rate: 0.1,
+        share_error_action: ShareErrorAction::Restart,
     };
     let donation_conf = DonationConfig {
         percentage: 1.0 / 10.0 - std::f64::EPSILON,
@@ -43,6 +65,16 @@ fn test_interval_mod_setup_donation_disabled_auto_tune_disabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        jit: true,
+        eager_dataset: false,
+        dataset_init_threads: None,
+        memory_mode: MemoryMode::Full,
+        nonce_ranges: false,
+        affinity: Affinity::None,
+        huge_pages: true,
+        verify_shares: false,
+        max_share_error_rate: 0.1,
+        share_error_action: ShareErrorAction::Restart,
     };
     let donation_conf = DonationConfig { percentage: 0.0 };
 
@@ -58,6 +90,16 @@ fn test_interval_mod_setup_donation_enabled_auto_tune_disabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        jit: true,
+        eager_dataset: false,
+        dataset_init_threads: None,
+        memory_mode: MemoryMode::Full,
+        nonce_ranges: false,
+        affinity: Affinity::None,
+        huge_pages: true,
+        verify_shares: false,
+        max_share_error_rate: 0.1,
+        share_error_action: ShareErrorAction::Restart,
     };
     let donation_conf = DonationConfig { percentage: 2.5 };
 
@@ -73,6 +115,16 @@ fn test_interval_mod_setup_donation_enabled_auto_tune_enabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        jit: true,
+        eager_dataset: false,
+        dataset_init_threads: None,
+        memory_mode: MemoryMode::Full,
+        nonce_ranges: false,
+        affinity: Affinity::None,
+        huge_pages: true,
+        verify_shares: false,
+        max_share_error_rate: 0.1,
+        share_error_action: ShareErrorAction::Restart,
     };
     let donation_conf = DonationConfig { percentage: 2.5 };
 
@@ -88,6 +140,16 @@ fn test_interval_mod_setup_donation_100_percent_enabled_auto_tune_enabled() {
         auto_tune_interval_minutes: 15,
         auto_tune_log: "/log/file".to_string(),
         num_threads: 8,
+        jit: true,
+        eager_dataset: false,
+        dataset_init_threads: None,
+        memory_mode: MemoryMode::Full,
+        nonce_ranges: false,
+        affinity: Affinity::None,
+        huge_pages: true,
+        verify_shares: false,
+        max_share_error_rate: 0.1,
+        share_error_action: ShareErrorAction::Restart,
     };
     let donation_conf = DonationConfig { percentage: 100.0 };
 
@@ -0,0 +1,157 @@
+extern crate mithril;
+
+use mithril::worker::affinity::{
+    allowed_cpus, auto_cpu_order, pin_current_thread, read_topology, thread_cpus, Affinity,
+    CpuTopology,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[test]
+fn test_read_topology() {
+    //2 cores with SMT, cpu3 offline
+    let root = sysfs_fixture("smt", &[(0, 0, 0), (1, 0, 1), (2, 0, 0), (3, 0, 1)]);
+    fs::write(root.path.join("cpu3/online"), "0\n").unwrap();
+    fs::write(root.path.join("cpu1/online"), "1\n").unwrap();
+
+    let cpus = read_topology(&root.path).unwrap();
+    assert_eq!(
+        cpus,
+        vec![topology(0, 0, 0), topology(1, 0, 1), topology(2, 0, 0),]
+    );
+}
+
+#[test]
+fn test_read_topology_missing() {
+    let root = sysfs_fixture("empty", &[]);
+    assert!(read_topology(&root.path).is_err());
+    assert!(read_topology(&root.path.join("missing")).is_err());
+}
+
+#[test]
+fn test_auto_cpu_order_physical_cores_first() {
+    //siblings numbered like Intel (n, n + cores)
+    let cpus = [
+        topology(0, 0, 0),
+        topology(1, 0, 1),
+        topology(2, 0, 2),
+        topology(3, 0, 0),
+        topology(4, 0, 1),
+        topology(5, 0, 2),
+    ];
+    assert_eq!(auto_cpu_order(&cpus), vec![0, 1, 2, 3, 4, 5]);
+
+    //siblings numbered like AMD (2n, 2n + 1)
+    let cpus = [
+        topology(0, 0, 0),
+        topology(1, 0, 0),
+        topology(2, 0, 1),
+        topology(3, 0, 1),
+    ];
+    assert_eq!(auto_cpu_order(&cpus), vec![0, 2, 1, 3]);
+
+    //same core ids in two packages are different cores
+    let cpus = [
+        topology(0, 0, 0),
+        topology(1, 1, 0),
+        topology(2, 0, 0),
+        topology(3, 1, 0),
+    ];
+    assert_eq!(auto_cpu_order(&cpus), vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn test_thread_cpus() {
+    let root = sysfs_fixture("threads", &[(0, 0, 0), (1, 0, 0), (2, 0, 1), (3, 0, 1)]);
+
+    assert_eq!(
+        thread_cpus(&Affinity::Auto, 3, &root.path, None),
+        vec![Some(0), Some(2), Some(1)]
+    );
+    //more threads than CPUs start over
+    assert_eq!(
+        thread_cpus(&Affinity::Cpus(vec![4, 6]), 3, &root.path, None),
+        vec![Some(4), Some(6), Some(4)]
+    );
+    assert_eq!(
+        thread_cpus(&Affinity::None, 2, &root.path, None),
+        vec![None, None]
+    );
+    //no topology, not pinned
+    assert_eq!(
+        thread_cpus(&Affinity::Auto, 2, &root.path.join("missing"), None),
+        vec![None, None]
+    );
+}
+
+#[test]
+fn test_thread_cpus_affinity_mask() {
+    let root = sysfs_fixture("mask", &[(0, 0, 0), (1, 0, 0), (2, 0, 1), (3, 0, 1)]);
+
+    //taskset -c 1-3, cpu0 of the first core is not allowed
+    assert_eq!(
+        thread_cpus(&Affinity::Auto, 3, &root.path, Some(&[1, 2, 3])),
+        vec![Some(1), Some(2), Some(3)]
+    );
+    //explicit CPUs are not restricted
+    assert_eq!(
+        thread_cpus(&Affinity::Cpus(vec![0]), 1, &root.path, Some(&[1])),
+        vec![Some(0)]
+    );
+    //no CPU of the topology allowed, not pinned
+    assert_eq!(
+        thread_cpus(&Affinity::Auto, 2, &root.path, Some(&[8])),
+        vec![None, None]
+    );
+}
+
+#[test]
+fn test_allowed_cpus() {
+    if let Some(cpus) = allowed_cpus() {
+        assert!(!cpus.is_empty());
+    }
+}
+
+#[test]
+fn test_pin_current_thread_invalid_cpu() {
+    assert!(pin_current_thread(usize::MAX).is_err());
+}
+
+//helper
+
+fn topology(cpu: usize, package: usize, core: usize) -> CpuTopology {
+    CpuTopology { cpu, package, core }
+}
+
+/// Fake sysfs tree of a test, removed on drop (also if the test fails)
+struct SysfsDir {
+    path: PathBuf,
+}
+
+impl Drop for SysfsDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// sysfs cpu directory with (cpu, package, core) entries
+fn sysfs_fixture(name: &str, cpus: &[(usize, usize, usize)]) -> SysfsDir {
+    let root = std::env::temp_dir().join(format!("mithril_sysfs_{}_{}", name, process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    //not a CPU
+    fs::create_dir_all(root.join("cpufreq")).unwrap();
+    fs::write(root.join("online"), "0-3\n").unwrap();
+    for (cpu, package, core) in cpus {
+        let topology = root.join(format!("cpu{}/topology", cpu));
+        fs::create_dir_all(&topology).unwrap();
+        write(&topology.join("physical_package_id"), *package);
+        write(&topology.join("core_id"), *core);
+    }
+    SysfsDir { path: root }
+}
+
+fn write(path: &Path, value: usize) {
+    fs::write(path, format!("{}\n", value)).unwrap();
+}
@@ -1,9 +1,17 @@
 #![allow(unknown_lints)]
 #![allow(clippy::unreadable_literal)]
 
+extern crate crossbeam_channel;
 extern crate mithril;
 
+use self::crossbeam_channel::unbounded;
+use mithril::byte_string;
+use mithril::randomx::memory::VmMemoryAllocator;
+use mithril::worker::affinity::Affinity;
+use mithril::worker::target::{self, Target};
 use mithril::worker::worker_pool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 
 #[test]
 fn test_with_nonce() {
@@ -16,19 +24,175 @@ fn test_with_nonce() {
 #[test]
 fn test_hash_target_value() {
     assert_eq!(
-        worker_pool::hash_target_value(
+        target::hash_value(&byte_string::string_to_u8_array(
             "c5c49db95a9da3f0802a34c6f97c364e7455fca7e41f72254fd4624dd2f91578"
-        ),
+        )),
         0x7815f9d24d62d44f
     );
 }
 
+#[test]
+fn test_with_nonce_bytes() {
+    let blob = "0606cbe692d005ecfebc7d2249d2b43535c237c02359e888b8b05d2e980c1405779241ac3ab48500000000e62a06e71559c98a37e7b6743465f4f72e42784c5719411c935dc002e347826b05";
+    let mut bytes = byte_string::string_to_u8_array(blob);
+    for nonce in [0, 666, 0x12345678, u32::MAX] {
+        let blob_hex = worker_pool::with_nonce(blob, &worker_pool::nonce_hex(nonce));
+        let expected = byte_string::string_to_u8_array(&blob_hex);
+        assert_eq!(
+            worker_pool::with_nonce_bytes(&mut bytes, nonce),
+            &expected[..]
+        );
+    }
+}
+
+#[test]
+fn test_nonce_space() {
+    let blob = "0606cbe692d005ecfebc7d2249d2b43535c237c02359e888b8b05d2e980c1405779241ac3ab48500000000e62a06e71559c98a37e7b6743465f4f72e42784c5719411c935dc002e347826b05";
+    let space = worker_pool::NonceSpace::for_blob(&byte_string::string_to_u8_array(blob));
+    assert_eq!(space.fixed_byte, None);
+    assert_eq!(space.size(), 1 << 32);
+    assert_eq!(space.nonce(70000), 70000);
+    assert_eq!(space.nonce(space.size() - 1), u32::MAX);
+}
+
+#[test]
+fn test_nonce_space_nicehash() {
+    let blob = "0606cbe692d005ecfebc7d2249d2b43535c237c02359e888b8b05d2e980c1405779241ac3ab485000000a7e62a06e71559c98a37e7b6743465f4f72e42784c5719411c935dc002e347826b05";
+    let mut bytes = byte_string::string_to_u8_array(blob);
+    let space = worker_pool::NonceSpace::for_blob(&bytes);
+    assert_eq!(space.fixed_byte, Some(0xa7));
+    assert_eq!(space.size(), 1 << 24);
+
+    //the pool byte is kept in every nonce
+    for ix in [0, 1, 666, space.size() - 1] {
+        let nonce = space.nonce(ix);
+        let hex = byte_string::u8_array_to_string(worker_pool::with_nonce_bytes(&mut bytes, nonce));
+        assert_eq!(&hex[84..86], "a7");
+    }
+    assert_eq!(space.nonce(1), 0x1a7);
+}
+
+#[test]
+fn test_nonce_space_split() {
+    let space = worker_pool::NonceSpace { fixed_byte: None };
+    let ranges = space.split(3);
+    assert_eq!(ranges.len(), 3);
+    assert_eq!(ranges[0].start, 0);
+    for pair in ranges.windows(2) {
+        assert_eq!(pair[0].end, pair[1].start);
+    }
+    assert_eq!(ranges[2].end, 1 << 32);
+
+    let space = worker_pool::NonceSpace {
+        fixed_byte: Some(1),
+    };
+    assert_eq!(space.split(1), vec![0..1 << 24]);
+}
+
+#[test]
+fn test_split_remaining() {
+    let next = AtomicU64::new(1000);
+    let end = AtomicU64::new(1 << 20);
+    let (start, stolen_end) = worker_pool::split_remaining(&next, &end);
+    assert_eq!(stolen_end, 1 << 20);
+    assert_eq!(end.load(Ordering::SeqCst), start);
+    assert_eq!(start - 1000, (1 << 20) - start);
+
+    //too few nonces left, the added thread gets an empty range
+    let next = AtomicU64::new(100);
+    let end = AtomicU64::new(200);
+    assert_eq!(worker_pool::split_remaining(&next, &end), (200, 200));
+    assert_eq!(end.load(Ordering::SeqCst), 200);
+}
+
+#[test]
+fn test_shrink_mid_job_covers_nonce_space() {
+    //3 threads with 100 nonces each, the third retired after 10 nonces
+    let spare = Mutex::new(Vec::new());
+    let threads: Vec<(AtomicU64, AtomicU64)> = (0..3)
+        .map(|i| (AtomicU64::new(i * 100), AtomicU64::new((i + 1) * 100)))
+        .collect();
+    let mut hashed = Vec::new();
+    for (next, end) in &threads {
+        for _ in 0..10 {
+            hashed.push(worker_pool::claim_nonce(next, end, &spare).unwrap());
+        }
+    }
+    let (next, end) = &threads[2];
+    worker_pool::hand_over_remaining(next, end, &spare);
+    assert_eq!(*spare.lock().unwrap(), vec![210..300]);
+    //the retired thread gets no more nonces, its rest is continued by the others
+    assert_eq!(worker_pool::claim_nonce(next, end, &spare), None);
+    for (next, end) in &threads[..2] {
+        while let Some(ix) = worker_pool::claim_nonce(next, end, &spare) {
+            hashed.push(ix);
+        }
+    }
+
+    hashed.sort_unstable();
+    assert_eq!(hashed, (0..300).collect::<Vec<u64>>());
+}
+
 #[test]
 fn test_job_target_value() {
-    assert_eq!(worker_pool::job_target_value("8b4f0100"), 368934881474191);
+    assert_eq!(
+        Target::from_hex("8b4f0100").unwrap().value(),
+        368934881474191
+    );
 }
 
 #[test]
 fn test_nonce_hex() {
     assert_eq!(worker_pool::nonce_hex(666), "0000029a");
 }
+
+#[test]
+fn test_share_errors_rate_exceeded() {
+    let mut errors = worker_pool::ShareErrors::default();
+    for _ in 0..(worker_pool::MIN_VERIFIED_SHARES - 1) {
+        errors.record(false);
+    }
+    //not rated before MIN_VERIFIED_SHARES
+    assert!(!errors.rate_exceeded(0.1));
+    errors.record(true);
+    assert!(errors.rate_exceeded(0.1));
+
+    let mut errors = worker_pool::ShareErrors::default();
+    errors.record(false);
+    for _ in 0..(worker_pool::MIN_VERIFIED_SHARES - 1) {
+        errors.record(true);
+    }
+    assert!(!errors.rate_exceeded(0.1));
+    errors.record(false);
+    assert!(errors.rate_exceeded(0.1));
+    assert_eq!(errors.verified, worker_pool::MIN_VERIFIED_SHARES + 1);
+    assert_eq!(errors.failed, 2);
+}
+
+#[test]
+fn test_thread_stopped() {
+    let (share_sndr, _share_rcvr) = unbounded();
+    let (metric_sndr, _metric_rcvr) = unbounded();
+    let mut pool = worker_pool::start(
+        3,
+        false,
+        &Affinity::None,
+        &share_sndr,
+        100,
+        &metric_sndr,
+        VmMemoryAllocator::initial(),
+        None,
+    );
+    assert_eq!(pool.num_threads(), 3);
+
+    //as reported on stopped_rcvr, later jobs are not sent to the thread
+    pool.thread_stopped(1);
+    assert_eq!(pool.num_threads(), 2);
+    pool.thread_stopped(1);
+    assert_eq!(pool.num_threads(), 2);
+
+    pool.resize(3);
+    assert_eq!(pool.num_threads(), 3);
+    pool.stop();
+    pool.join();
+}
@@ -0,0 +1,84 @@
+extern crate mithril;
+
+use mithril::byte_string;
+use mithril::worker::target::{hash_value, Difficulty, Target, TargetError};
+
+#[test]
+fn test_compact_target() {
+    let target = Target::from_hex("8b4f0100").unwrap();
+    assert_eq!(target.value(), 368934881474191);
+    assert_eq!(target.difficulty(), Difficulty::new(50000));
+
+    assert_eq!(Target::from_hex("ffffffff").unwrap().value(), u64::MAX);
+    assert_eq!(
+        Target::from_hex("01000000").unwrap().value(),
+        u64::MAX / u64::from(u32::MAX)
+    );
+}
+
+#[test]
+fn test_full_target() {
+    let target = Target::from_hex("1c9de00000000000").unwrap();
+    assert_eq!(target.value(), 0xe09d1c);
+    assert_eq!(target.to_hex(), "1c9de00000000000");
+    assert_eq!(target.difficulty(), Difficulty::new(u64::MAX / 0xe09d1c));
+
+    //beyond the compact targets
+    let high = Difficulty::new(1_000_000_000_000).target();
+    assert_eq!(Target::from_hex(&high.to_hex()).unwrap(), high);
+    //integer division, the round trip is only exact up to the rounding of the target
+    let rounding = high.difficulty().value() - 1_000_000_000_000;
+    assert!(rounding < 1_000_000_000_000 / high.value());
+}
+
+#[test]
+fn test_invalid_targets() {
+    assert_eq!(Target::from_hex("00000000"), Err(TargetError::Zero));
+    assert_eq!(Target::from_hex("0000000000000000"), Err(TargetError::Zero));
+    assert_eq!(
+        Target::from_hex("8b4f01"),
+        Err(TargetError::InvalidLength("8b4f01".to_string()))
+    );
+    assert_eq!(
+        Target::from_hex("8b4f0100ff"),
+        Err(TargetError::InvalidLength("8b4f0100ff".to_string()))
+    );
+    assert_eq!(
+        Target::from_hex("8b4f01zz"),
+        Err(TargetError::InvalidHex("8b4f01zz".to_string()))
+    );
+    assert_eq!(
+        Target::from_hex(""),
+        Err(TargetError::InvalidLength(String::new()))
+    );
+}
+
+#[test]
+fn test_difficulty_edge_cases() {
+    assert_eq!(Difficulty::new(0).target().value(), u64::MAX);
+    assert_eq!(Difficulty::new(1).target().value(), u64::MAX);
+    assert_eq!(Difficulty::new(u64::MAX).target().value(), 1);
+    assert_eq!(
+        Target::from_value(0).difficulty(),
+        Difficulty::new(u64::MAX)
+    );
+    assert_eq!(
+        Target::from(Difficulty::new(50000)).difficulty(),
+        Difficulty::new(50000)
+    );
+    assert_eq!(
+        Difficulty::from(Target::from_value(u64::MAX)),
+        Difficulty::new(1)
+    );
+}
+
+#[test]
+fn test_is_met_by() {
+    let hash = "c5c49db95a9da3f0802a34c6f97c364e7455fca7e41f72254fd4624dd2f91578";
+    let bytes = byte_string::string_to_u8_array(hash);
+    assert_eq!(hash_value(&bytes), 0x7815f9d24d62d44f);
+
+    assert!(Target::from_value(0x7815f9d24d62d450).is_met_by(&bytes));
+    assert!(!Target::from_value(0x7815f9d24d62d44f).is_met_by(&bytes));
+    assert!(!Target::from_hex("8b4f0100").unwrap().is_met_by(&bytes));
+}